@@ -0,0 +1,257 @@
+extern crate ghakuf;
+
+use ghakuf::diff::Diff;
+use ghakuf::messages::*;
+use ghakuf::midicsv;
+use ghakuf::rmid;
+use ghakuf::smf::Smf;
+use ghakuf::text::Text;
+use ghakuf::transform::{absolute, Normalise};
+use ghakuf::validate::validate;
+use std::io::{self, Write};
+use std::{env, fs, process};
+
+const USAGE: &str = "Usage:
+    ghakuf info <file>
+    ghakuf dump <file>
+    ghakuf convert <input> <output> [--format 0|1] [--rmid | --smf] [--running-status on|off]
+                                    [--normalise]
+    ghakuf validate <file>
+    ghakuf diff <old> <new> [--ignore-order] [--ignore-note-off]
+
+Files may be SMF, RIFF MIDI (RMID), text (*.txt) or midicsv (*.csv). Running status of the input is kept unless
+--running-status is given.";
+
+fn main() {
+    let args: Vec<String> = env::args().skip(1).collect();
+    let result = match args.first().map(|command| command.as_str()) {
+        Some("info") if args.len() == 2 => info(&args[1]),
+        Some("dump") if args.len() == 2 => dump(&args[1]),
+        Some("convert") if args.len() >= 3 => convert(&args[1], &args[2], &args[3..]),
+        Some("validate") if args.len() == 2 => validate_file(&args[1]),
+        Some("diff") if args.len() >= 3 => diff(&args[1], &args[2], &args[3..]),
+        Some("help") | Some("--help") | Some("-h") => {
+            writeln!(io::stdout(), "{}", USAGE).map_err(stdout_error)
+        }
+        _ => {
+            eprintln!("{}", USAGE);
+            process::exit(2);
+        }
+    };
+    if let Err(message) = result {
+        eprintln!("ghakuf: {}", message);
+        process::exit(1);
+    }
+}
+
+// Exits quietly when stdout is closed by the reader (e.g. `ghakuf dump song.mid | head`).
+fn stdout_error(err: io::Error) -> String {
+    if err.kind() == io::ErrorKind::BrokenPipe {
+        process::exit(0);
+    }
+    format!("stdout: {}", err)
+}
+
+fn read(path: &str) -> Result<Smf, String> {
+    if is_text(path) || is_csv(path) {
+        return read_text(path).map(|text| text.smf);
+    }
+    let data = fs::read(path).map_err(|err| format!("{}: {}", path, err))?;
+    Smf::from_bytes(&data).map_err(|err| format!("{}: {}", path, err))
+}
+
+fn read_text(path: &str) -> Result<Text, String> {
+    if is_text(path) {
+        let text = fs::read_to_string(path).map_err(|err| format!("{}: {}", path, err))?;
+        return text.parse().map_err(|err| format!("{}: {}", path, err));
+    }
+    if is_csv(path) {
+        let csv = fs::read_to_string(path).map_err(|err| format!("{}: {}", path, err))?;
+        return midicsv::text_from_csv(&csv).map_err(|err| format!("{}: {}", path, err));
+    }
+    let data = fs::read(path).map_err(|err| format!("{}: {}", path, err))?;
+    Text::from_bytes(&data).or_else(|err| {
+        // the messages are still converted, though not into the same bytes
+        let smf = Smf::from_bytes(&data).map_err(|err| format!("{}: {}", path, err))?;
+        eprintln!("ghakuf: {}: {}", path, err);
+        Ok(Text::new(smf))
+    })
+}
+
+fn is_text(path: &str) -> bool {
+    path.to_lowercase().ends_with(".txt")
+}
+
+fn is_csv(path: &str) -> bool {
+    path.to_lowercase().ends_with(".csv")
+}
+
+fn division(time_base: u16) -> String {
+    if time_base & 0x8000 == 0 {
+        format!("{} ticks per quarter note", time_base)
+    } else {
+        format!(
+            "SMPTE {} fps, {} ticks per frame",
+            -((time_base >> 8) as u8 as i8),
+            time_base & 0xff
+        )
+    }
+}
+
+fn info(path: &str) -> Result<(), String> {
+    let stdout = io::stdout();
+    let mut out = stdout.lock();
+    let smf = read(path)?;
+    let tempo_map = smf.tempo_map();
+    let length = smf.length();
+    let bpm: Vec<f64> = tempo_map
+        .tempos()
+        .iter()
+        .filter(|&&(tick, _)| tick <= length)
+        .map(|&(_, tempo)| 60_000_000.0 / tempo as f64)
+        .collect();
+    let min = bpm.iter().cloned().fold(f64::INFINITY, f64::min);
+    let max = bpm.iter().cloned().fold(0.0, f64::max);
+    writeln!(out, "format: {}", smf.format).map_err(stdout_error)?;
+    writeln!(out, "division: {}", division(smf.time_base)).map_err(stdout_error)?;
+    writeln!(out, "tracks: {}", smf.tracks.len()).map_err(stdout_error)?;
+    writeln!(
+        out,
+        "duration: {:.3} s ({} ticks)",
+        tempo_map.seconds(length),
+        length
+    )
+    .map_err(stdout_error)?;
+    if (max - min).abs() < 0.001 {
+        writeln!(out, "tempo: {:.2} bpm", max).map_err(stdout_error)?;
+    } else {
+        writeln!(out, "tempo: {:.2} - {:.2} bpm", min, max).map_err(stdout_error)?;
+    }
+    Ok(())
+}
+
+fn dump(path: &str) -> Result<(), String> {
+    let stdout = io::stdout();
+    let mut out = stdout.lock();
+    let smf = read(path)?;
+    let tempo_map = smf.tempo_map();
+    writeln!(
+        out,
+        "format: {}, division: {}, tracks: {}",
+        smf.format,
+        division(smf.time_base),
+        smf.tracks.len()
+    )
+    .map_err(stdout_error)?;
+    for (i, track) in smf.tracks.iter().enumerate() {
+        writeln!(out, "track {}", i).map_err(stdout_error)?;
+        for (tick, message) in absolute(track) {
+            let event = match message {
+                Message::MetaEvent {
+                    ref event,
+                    ref data,
+                    ..
+                } => match message.text(None) {
+                    Ok(text) => format!("Meta {} {:?}", event, text),
+                    Err(_) => format!("Meta {} {:02x?}", event, data),
+                },
+                Message::MidiEvent { ref event, .. } => format!("MIDI {:#}", event),
+                Message::SysExEvent {
+                    ref event,
+                    ref data,
+                    ..
+                } => format!("SysEx {} {:02x?}", event, data),
+                Message::TrackChange => continue,
+            };
+            writeln!(
+                out,
+                "{:>10} {:>12.6} {}",
+                tick,
+                tempo_map.seconds(tick),
+                event
+            )
+            .map_err(stdout_error)?;
+        }
+    }
+    Ok(())
+}
+
+fn convert(input: &str, output: &str, options: &[String]) -> Result<(), String> {
+    let mut text = read_text(input)?;
+    let mut wrap = output.to_lowercase().ends_with(".rmi");
+    let mut options = options.iter();
+    while let Some(option) = options.next() {
+        if option != "--rmid" && option != "--smf" {
+            // encodings of the original events don't apply to rewritten ones
+            text.encodings.clear();
+        }
+        match option.as_str() {
+            "--format" => match options.next().map(|value| value.as_str()) {
+                Some("0") => {
+                    text.smf.set_format(0);
+                }
+                Some("1") => {
+                    text.smf.set_format(1);
+                }
+                _ => return Err("--format must be 0 or 1".to_string()),
+            },
+            "--rmid" => wrap = true,
+            "--smf" => wrap = false,
+            "--running-status" => match options.next().map(|value| value.as_str()) {
+                Some("on") => text.running_status = true,
+                Some("off") => text.running_status = false,
+                _ => return Err("--running-status must be on or off".to_string()),
+            },
+            "--normalise" => {
+                text.smf.normalise(&Normalise::new());
+            }
+            option => return Err(format!("unknown option: {}", option)),
+        }
+    }
+    if is_text(output) {
+        return fs::write(output, text.to_string()).map_err(|err| format!("{}: {}", output, err));
+    }
+    if is_csv(output) {
+        return fs::write(output, midicsv::text_to_csv(&text))
+            .map_err(|err| format!("{}: {}", output, err));
+    }
+    let mut data = text.to_bytes();
+    if wrap {
+        data = rmid::wrap(&data);
+    }
+    fs::write(output, data).map_err(|err| format!("{}: {}", output, err))
+}
+
+fn validate_file(path: &str) -> Result<(), String> {
+    let stdout = io::stdout();
+    let mut out = stdout.lock();
+    let issues = validate(&read(path)?);
+    for issue in &issues {
+        writeln!(out, "{}", issue).map_err(stdout_error)?;
+    }
+    if issues.is_empty() {
+        writeln!(out, "{}: OK", path).map_err(stdout_error)?;
+        Ok(())
+    } else {
+        Err(format!("{}: {} problem(s) found", path, issues.len()))
+    }
+}
+
+fn diff(old: &str, new: &str, options: &[String]) -> Result<(), String> {
+    let mut diff = Diff::new();
+    for option in options {
+        match option.as_str() {
+            "--ignore-order" => diff.ignore_order(true),
+            "--ignore-note-off" => diff.ignore_note_off(true),
+            option => return Err(format!("unknown option: {}", option)),
+        };
+    }
+    let report = diff.compare(&read(old)?, &read(new)?);
+    let stdout = io::stdout();
+    write!(stdout.lock(), "{}", report).map_err(stdout_error)?;
+    if report.is_empty() {
+        Ok(())
+    } else {
+        Err(format!("{} change(s) found", report.changes.len()))
+    }
+}
@@ -0,0 +1,601 @@
+use messages::*;
+use smf::Smf;
+use std::fmt;
+use transform::absolute;
+
+/// Structural diff of two SMFs by header fields, track names and events in absolute time.
+///
+/// Events are compared at absolute ticks, so running status and different splits of delta time
+/// never make differences. When time bases (ticks per quarter note) differ, SMF of coarser time
+/// base is rescaled to finer one. Order of simultaneous events and Note Off against Note On with
+/// velocity 0 can also be ignored.
+///
+/// # Examples
+///
+/// ```
+/// use ghakuf::diff::{Change, Diff};
+/// use ghakuf::messages::*;
+/// use ghakuf::smf::Smf;
+///
+/// let note = |delta_time, note, velocity| Message::MidiEvent {
+///     delta_time,
+///     event: MidiEvent::NoteOn { ch: 0, note, velocity },
+/// };
+/// let mut old = Smf::new(0, 480);
+/// old.tracks.push(vec![note(0, 60, 100), note(480, 60, 0)]);
+/// let mut new = Smf::new(0, 960);
+/// new.tracks.push(vec![note(0, 60, 80), note(960, 60, 0), note(0, 64, 100)]);
+///
+/// let report = Diff::new().compare(&old, &new);
+/// assert_eq!(report.time_base, 960);
+/// assert_eq!(
+///     report.changes,
+///     vec![
+///         Change::TimeBase { old: 480, new: 960 },
+///         Change::Changed { track: 0, tick: 0, old: note(0, 60, 100), new: note(0, 60, 80) },
+///         Change::Added { track: 0, tick: 960, message: note(0, 64, 100) },
+///     ]
+/// );
+/// assert_eq!(
+///     report.to_string(),
+///     "time base: 480 -> 960\n\
+///      track 0 @ 0: ~ NoteOn ch: 0, note: 60 (C4), velocity: 100 -> NoteOn ch: 0, note: 60 (C4), velocity: 80\n\
+///      track 0 @ 960: + NoteOn ch: 0, note: 64 (E4), velocity: 100\n"
+/// );
+/// ```
+pub struct Diff {
+    ignore_order: bool,
+    ignore_note_off: bool,
+}
+impl Diff {
+    /// Builds Diff which takes order of simultaneous events and Note Off into account.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use ghakuf::diff::Diff;
+    ///
+    /// let diff = Diff::new();
+    /// ```
+    pub fn new() -> Diff {
+        Diff {
+            ignore_order: false,
+            ignore_note_off: false,
+        }
+    }
+    /// Ignores order of events at the same tick.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use ghakuf::diff::Diff;
+    /// use ghakuf::messages::*;
+    /// use ghakuf::smf::Smf;
+    ///
+    /// let note = |note| Message::MidiEvent { delta_time: 0, event: MidiEvent::NoteOn { ch: 0, note, velocity: 100 } };
+    /// let mut old = Smf::new(0, 480);
+    /// old.tracks.push(vec![note(60), note(64)]);
+    /// let mut new = Smf::new(0, 480);
+    /// new.tracks.push(vec![note(64), note(60)]);
+    ///
+    /// assert!(!Diff::new().compare(&old, &new).is_empty());
+    /// assert!(Diff::new().ignore_order(true).compare(&old, &new).is_empty());
+    /// ```
+    pub fn ignore_order(&mut self, ignore_order: bool) -> &mut Diff {
+        self.ignore_order = ignore_order;
+        self
+    }
+    /// Ignores Note Off against Note On with velocity 0 (release velocity of Note Off is ignored too).
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use ghakuf::diff::Diff;
+    /// use ghakuf::messages::*;
+    /// use ghakuf::smf::Smf;
+    ///
+    /// let mut old = Smf::new(0, 480);
+    /// old.tracks.push(vec![Message::MidiEvent {
+    ///     delta_time: 0,
+    ///     event: MidiEvent::NoteOff { ch: 0, note: 60, velocity: 64 },
+    /// }]);
+    /// let mut new = Smf::new(0, 480);
+    /// new.tracks.push(vec![Message::MidiEvent {
+    ///     delta_time: 0,
+    ///     event: MidiEvent::NoteOn { ch: 0, note: 60, velocity: 0 },
+    /// }]);
+    ///
+    /// assert_eq!(Diff::new().compare(&old, &new).changes.len(), 1);
+    /// assert!(Diff::new().ignore_note_off(true).compare(&old, &new).is_empty());
+    /// ```
+    pub fn ignore_note_off(&mut self, ignore_note_off: bool) -> &mut Diff {
+        self.ignore_note_off = ignore_note_off;
+        self
+    }
+    /// Compares old SMF with new SMF.
+    ///
+    /// Tracks are compared by index, and every event of a track found in only one SMF is
+    /// added or removed. The first SequenceOrTrackName of a renamed track is reported only as
+    /// TrackName. Messages in the report have delta time 0.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use ghakuf::diff::{Change, Diff};
+    /// use ghakuf::smf::Smf;
+    /// use std::path;
+    ///
+    /// let old = Smf::read(path::Path::new("tests/test.mid")).unwrap();
+    /// let mut new = old.clone();
+    /// new.set_format(0);
+    ///
+    /// assert!(Diff::new().compare(&old, &old).is_empty());
+    /// let report = Diff::new().compare(&old, &new);
+    /// assert_eq!(report.changes[0], Change::Format { old: 1, new: 0 });
+    /// assert_eq!(report.changes[1], Change::TrackCount { old: 2, new: 1 });
+    /// ```
+    pub fn compare(&self, old: &Smf, new: &Smf) -> Report {
+        let mut changes = Vec::new();
+        if old.format != new.format {
+            changes.push(Change::Format {
+                old: old.format,
+                new: new.format,
+            });
+        }
+        if old.time_base != new.time_base {
+            changes.push(Change::TimeBase {
+                old: old.time_base,
+                new: new.time_base,
+            });
+        }
+        if old.tracks.len() != new.tracks.len() {
+            changes.push(Change::TrackCount {
+                old: old.tracks.len(),
+                new: new.tracks.len(),
+            });
+        }
+        let (old, new) = conform(old, new);
+        let empty = Vec::new();
+        for track in 0..old.tracks.len().max(new.tracks.len()) {
+            let old_track = old.tracks.get(track).unwrap_or(&empty);
+            let new_track = new.tracks.get(track).unwrap_or(&empty);
+            let (old_name, new_name) = (track_name(old_track), track_name(new_track));
+            let renamed = old_name != new_name;
+            if renamed {
+                changes.push(Change::TrackName {
+                    track,
+                    old: old_name,
+                    new: new_name,
+                });
+            }
+            self.compare_track(track, old_track, new_track, renamed, &mut changes);
+        }
+        Report {
+            time_base: old.time_base,
+            changes,
+        }
+    }
+    fn compare_track(
+        &self,
+        track: usize,
+        old: &[Message],
+        new: &[Message],
+        renamed: bool,
+        changes: &mut Vec<Change>,
+    ) {
+        let (old, new) = (self.events(old, renamed), self.events(new, renamed));
+        let (mut i, mut j) = (0, 0);
+        while i < old.len() || j < new.len() {
+            let tick = match (old.get(i), new.get(j)) {
+                (Some(old), Some(new)) => old.0.min(new.0),
+                (Some(old), None) => old.0,
+                (None, Some(new)) => new.0,
+                (None, None) => break,
+            };
+            let old_end = i + old[i..].iter().take_while(|event| event.0 == tick).count();
+            let new_end = j + new[j..].iter().take_while(|event| event.0 == tick).count();
+            compare_events(track, tick, &old[i..old_end], &new[j..new_end], changes);
+            i = old_end;
+            j = new_end;
+        }
+    }
+    // (absolute tick, message compared, message reported) sorted by tick, without the track name
+    // event if it is reported as TrackName
+    fn events(&self, track: &[Message], renamed: bool) -> Vec<(u64, Message, Message)> {
+        let mut events: Vec<(u64, Message, Message)> = absolute(track)
+            .into_iter()
+            .map(|(tick, mut message)| {
+                message.set_delta_time(0);
+                let compared = match message {
+                    Message::MidiEvent {
+                        event: MidiEvent::NoteOff { ch, note, .. },
+                        ..
+                    } if self.ignore_note_off => Message::MidiEvent {
+                        delta_time: 0,
+                        event: MidiEvent::NoteOn {
+                            ch,
+                            note,
+                            velocity: 0,
+                        },
+                    },
+                    _ => message.clone(),
+                };
+                (tick, compared, message)
+            })
+            .collect();
+        if let Some(index) = track_name_index(track).filter(|_| renamed) {
+            events.remove(index);
+        }
+        if self.ignore_order {
+            events.sort_by_key(|event| (event.0, event.1.binary()));
+        }
+        events
+    }
+}
+impl Default for Diff {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Result of Diff, which is written as a text report by `Display`.
+#[derive(PartialEq, Clone, Debug)]
+pub struct Report {
+    /// Time base of ticks in changes.
+    pub time_base: u16,
+    pub changes: Vec<Change>,
+}
+impl Report {
+    /// Returns whether no changes have found or not.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use ghakuf::diff::Diff;
+    /// use ghakuf::smf::Smf;
+    ///
+    /// assert!(Diff::new().compare(&Smf::new(1, 480), &Smf::new(1, 480)).is_empty());
+    /// ```
+    pub fn is_empty(&self) -> bool {
+        self.changes.is_empty()
+    }
+}
+impl fmt::Display for Report {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for change in &self.changes {
+            writeln!(f, "{}", change)?;
+        }
+        Ok(())
+    }
+}
+
+/// An enum representing a difference found by Diff.
+///
+/// Track is 0-based, and tick is absolute in the track.
+#[derive(PartialEq, Clone, Debug)]
+pub enum Change {
+    /// Format of header is changed.
+    Format { old: u16, new: u16 },
+    /// Time base of header is changed.
+    TimeBase { old: u16, new: u16 },
+    /// Number of tracks is changed.
+    TrackCount { old: usize, new: usize },
+    /// First SequenceOrTrackName of track is changed.
+    TrackName {
+        track: usize,
+        old: Option<String>,
+        new: Option<String>,
+    },
+    /// Event is found only in new SMF.
+    Added {
+        track: usize,
+        tick: u64,
+        message: Message,
+    },
+    /// Event is found only in old SMF.
+    Removed {
+        track: usize,
+        tick: u64,
+        message: Message,
+    },
+    /// Event of the same kind (e.g. note, controller or meta event type) at the same tick is changed.
+    Changed {
+        track: usize,
+        tick: u64,
+        old: Message,
+        new: Message,
+    },
+}
+impl fmt::Display for Change {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        use diff::Change::*;
+        match *self {
+            Format { old, new } => write!(f, "format: {} -> {}", old, new),
+            TimeBase { old, new } => write!(f, "time base: {} -> {}", old, new),
+            TrackCount { old, new } => write!(f, "tracks: {} -> {}", old, new),
+            TrackName {
+                track,
+                ref old,
+                ref new,
+            } => write!(f, "track {} name: {:?} -> {:?}", track, old, new),
+            Added {
+                track,
+                tick,
+                ref message,
+            } => write!(f, "track {} @ {}: + {}", track, tick, describe(message)),
+            Removed {
+                track,
+                tick,
+                ref message,
+            } => write!(f, "track {} @ {}: - {}", track, tick, describe(message)),
+            Changed {
+                track,
+                tick,
+                ref old,
+                ref new,
+            } => write!(
+                f,
+                "track {} @ {}: ~ {} -> {}",
+                track,
+                tick,
+                describe(old),
+                describe(new)
+            ),
+        }
+    }
+}
+
+fn conform(old: &Smf, new: &Smf) -> (Smf, Smf) {
+    let (mut old, mut new) = (old.clone(), new.clone());
+    let metrical = |time_base: u16| time_base & 0x8000 == 0;
+    if old.time_base != new.time_base && metrical(old.time_base) && metrical(new.time_base) {
+        if old.time_base < new.time_base {
+            old.set_time_base(new.time_base);
+        } else {
+            new.set_time_base(old.time_base);
+        }
+    }
+    (old, new)
+}
+
+fn track_name(track: &[Message]) -> Option<String> {
+    track_name_index(track).and_then(|index| track[index].text(None).ok())
+}
+
+fn track_name_index(track: &[Message]) -> Option<usize> {
+    track.iter().position(|message| match *message {
+        Message::MetaEvent { ref event, .. } => *event == MetaEvent::SequenceOrTrackName,
+        _ => false,
+    })
+}
+
+// Events of the same kind are reported as changed instead of removed and added.
+fn kind(message: &Message) -> (u8, u8) {
+    match *message {
+        Message::MetaEvent { ref event, .. } => (0xff, event.binary()[1]),
+        Message::MidiEvent { ref event, .. } => match *event {
+            MidiEvent::NoteOff { ch, note, .. } | MidiEvent::NoteOn { ch, note, .. } => {
+                (0x90 | ch, note)
+            }
+            MidiEvent::PolyphonicKeyPressure { note, .. } => (event.status_byte(), note),
+            MidiEvent::ControlChange { control, .. } => (event.status_byte(), control),
+            _ => (event.status_byte(), 0),
+        },
+        Message::SysExEvent { ref event, .. } => (event.status_byte(), 0),
+        Message::TrackChange => (0, 0),
+    }
+}
+
+fn compare_events(
+    track: usize,
+    tick: u64,
+    old: &[(u64, Message, Message)],
+    new: &[(u64, Message, Message)],
+    changes: &mut Vec<Change>,
+) {
+    // longest common subsequence of compared messages
+    let mut lengths = vec![vec![0; new.len() + 1]; old.len() + 1];
+    for i in (0..old.len()).rev() {
+        for j in (0..new.len()).rev() {
+            lengths[i][j] = if old[i].1 == new[j].1 {
+                lengths[i + 1][j + 1] + 1
+            } else {
+                lengths[i + 1][j].max(lengths[i][j + 1])
+            };
+        }
+    }
+    let (mut removed, mut added) = (Vec::new(), Vec::new());
+    let (mut i, mut j) = (0, 0);
+    while i < old.len() || j < new.len() {
+        if i < old.len() && j < new.len() && old[i].1 == new[j].1 {
+            i += 1;
+            j += 1;
+        } else if j < new.len() && (i == old.len() || lengths[i][j + 1] >= lengths[i + 1][j]) {
+            added.push(&new[j]);
+            j += 1;
+        } else {
+            removed.push(&old[i]);
+            i += 1;
+        }
+    }
+    for old in removed {
+        match added
+            .iter()
+            .position(|new| new.1 != old.1 && kind(&new.1) == kind(&old.1))
+        {
+            Some(index) => changes.push(Change::Changed {
+                track,
+                tick,
+                old: old.2.clone(),
+                new: added.remove(index).2.clone(),
+            }),
+            None => changes.push(Change::Removed {
+                track,
+                tick,
+                message: old.2.clone(),
+            }),
+        }
+    }
+    changes.extend(added.into_iter().map(|new| Change::Added {
+        track,
+        tick,
+        message: new.2.clone(),
+    }));
+}
+
+fn describe(message: &Message) -> String {
+    match *message {
+        Message::MetaEvent {
+            ref event,
+            ref data,
+            ..
+        } => match message.text(None) {
+            Ok(text) => format!("{} {:?}", event, text),
+            Err(_) => format!("{} {:02x?}", event, data),
+        },
+        Message::MidiEvent { ref event, .. } => format!("{:#}", event),
+        Message::SysExEvent {
+            ref event,
+            ref data,
+            ..
+        } => format!("SysEx {} {:02x?}", event, data),
+        Message::TrackChange => "TrackChange".to_string(),
+    }
+}
+
+#[cfg(test)]
+mod diff_tests {
+    use diff::*;
+
+    fn note(delta_time: u32, note: u8, velocity: u8) -> Message {
+        Message::MidiEvent {
+            delta_time,
+            event: MidiEvent::NoteOn {
+                ch: 0,
+                note,
+                velocity,
+            },
+        }
+    }
+    fn name(name: &str) -> Message {
+        Message::MetaEvent {
+            delta_time: 0,
+            event: MetaEvent::SequenceOrTrackName,
+            data: name.as_bytes().to_vec(),
+        }
+    }
+    #[test]
+    fn track_names_and_tracks() {
+        let mut old = Smf::new(1, 480);
+        old.tracks.push(vec![name("Piano"), note(0, 60, 100)]);
+        let mut new = Smf::new(1, 480);
+        new.tracks.push(vec![name("Strings"), note(0, 60, 100)]);
+        new.tracks.push(vec![note(10, 62, 100)]);
+        let report = Diff::new().compare(&old, &new);
+        assert_eq!(
+            report.changes,
+            vec![
+                Change::TrackCount { old: 1, new: 2 },
+                Change::TrackName {
+                    track: 0,
+                    old: Some("Piano".to_string()),
+                    new: Some("Strings".to_string()),
+                },
+                Change::Added {
+                    track: 1,
+                    tick: 10,
+                    message: note(0, 62, 100),
+                },
+            ]
+        );
+        assert_eq!(
+            report.to_string(),
+            "tracks: 1 -> 2\n\
+             track 0 name: Some(\"Piano\") -> Some(\"Strings\")\n\
+             track 1 @ 10: + NoteOn ch: 0, note: 62 (D4), velocity: 100\n"
+        );
+        let mut unnamed = old.clone();
+        unnamed.tracks[0].remove(0);
+        assert_eq!(
+            Diff::new().compare(&old, &unnamed).changes,
+            vec![Change::TrackName {
+                track: 0,
+                old: Some("Piano".to_string()),
+                new: None,
+            }]
+        );
+    }
+    #[test]
+    fn delta_time_split_and_time_base() {
+        let mut old = Smf::new(0, 960);
+        old.tracks.push(vec![note(960, 60, 100), note(480, 60, 0)]);
+        let mut new = Smf::new(0, 480);
+        new.tracks.push(vec![
+            Message::MetaEvent {
+                delta_time: 240,
+                event: MetaEvent::Marker,
+                data: b"A".to_vec(),
+            },
+            note(240, 60, 100),
+            note(240, 60, 0),
+        ]);
+        let report = Diff::new().compare(&old, &new);
+        assert_eq!(report.time_base, 960);
+        assert_eq!(
+            report.changes,
+            vec![
+                Change::TimeBase { old: 960, new: 480 },
+                Change::Added {
+                    track: 0,
+                    tick: 480,
+                    message: Message::MetaEvent {
+                        delta_time: 0,
+                        event: MetaEvent::Marker,
+                        data: b"A".to_vec(),
+                    },
+                },
+            ]
+        );
+    }
+    #[test]
+    fn removed_and_reordered() {
+        let mut old = Smf::new(0, 480);
+        old.tracks.push(vec![
+            note(0, 60, 100),
+            note(0, 64, 100),
+            note(0, 67, 100),
+            note(480, 72, 100),
+        ]);
+        let mut new = Smf::new(0, 480);
+        new.tracks
+            .push(vec![note(0, 67, 100), note(0, 60, 100), note(0, 64, 100)]);
+        assert_eq!(
+            Diff::new().ignore_order(true).compare(&old, &new).changes,
+            vec![Change::Removed {
+                track: 0,
+                tick: 480,
+                message: note(0, 72, 100),
+            }]
+        );
+        assert_eq!(
+            Diff::new().compare(&old, &new).changes,
+            vec![
+                Change::Removed {
+                    track: 0,
+                    tick: 0,
+                    message: note(0, 67, 100),
+                },
+                Change::Added {
+                    track: 0,
+                    tick: 0,
+                    message: note(0, 67, 100),
+                },
+                Change::Removed {
+                    track: 0,
+                    tick: 480,
+                    message: note(0, 72, 100),
+                },
+            ]
+        );
+    }
+}
@@ -0,0 +1,218 @@
+use encoding_rs::{DecoderResult, EncoderResult, SHIFT_JIS};
+use std::{error, fmt, str};
+
+/// An enum representing character encoding of text meta events.
+///
+/// # Examples
+///
+/// ```
+/// use ghakuf::encoding::Encoding;
+///
+/// let data = [0x82, 0xa0, 0x82, 0xa2]; // "あい" in Shift-JIS
+/// let encoding = Encoding::detect(&data);
+/// assert_eq!(encoding, Encoding::ShiftJis);
+/// assert_eq!(encoding.decode(&data).unwrap(), "あい");
+/// ```
+#[derive(PartialEq, Eq, Hash, Clone, Copy, Debug)]
+pub enum Encoding {
+    /// UTF-8 (including ASCII)
+    Utf8,
+    /// Shift-JIS (Japanese)
+    ShiftJis,
+    /// ISO-8859-1 (Latin-1, Western European)
+    Latin1,
+}
+impl Encoding {
+    /// Detects encoding of data heuristically.
+    ///
+    /// Data valid as UTF-8 (ASCII only data, too) is UTF-8, data valid as Shift-JIS with some double-byte
+    /// characters (kana, kanji or full-width characters) is Shift-JIS, and others are Latin-1 because every
+    /// byte is valid in it. Shift-JIS text consisting only of half-width katakana is detected as Latin-1
+    /// since their codes overlap with accented letters.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use ghakuf::encoding::Encoding;
+    ///
+    /// assert_eq!(Encoding::detect(b"Hello"), Encoding::Utf8);
+    /// assert_eq!(Encoding::detect("Große Fuge".as_bytes()), Encoding::Utf8);
+    /// assert_eq!(Encoding::detect(b"Gro\xdfe Fuge"), Encoding::Latin1);
+    /// ```
+    pub fn detect(data: &[u8]) -> Encoding {
+        if str::from_utf8(data).is_ok() {
+            Encoding::Utf8
+        } else if Encoding::ShiftJis
+            .decode(data)
+            .map(|text| {
+                text.chars()
+                    .any(|c| c >= '\u{3000}' && !('\u{ff61}'..='\u{ff9f}').contains(&c))
+            })
+            .unwrap_or(false)
+        {
+            Encoding::ShiftJis
+        } else {
+            Encoding::Latin1
+        }
+    }
+    /// Decodes data to String.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use ghakuf::encoding::{Encoding, EncodingError};
+    ///
+    /// assert_eq!(Encoding::Latin1.decode(b"caf\xe9").unwrap(), "café");
+    /// assert_eq!(
+    ///     Encoding::Utf8.decode(b"caf\xe9"),
+    ///     Err(EncodingError::Decode { encoding: Encoding::Utf8, position: 3 })
+    /// );
+    /// ```
+    pub fn decode(&self, data: &[u8]) -> Result<String, EncodingError> {
+        match *self {
+            Encoding::Utf8 => match str::from_utf8(data) {
+                Ok(text) => Ok(text.to_string()),
+                Err(err) => Err(EncodingError::Decode {
+                    encoding: *self,
+                    position: err.valid_up_to(),
+                }),
+            },
+            Encoding::ShiftJis => {
+                let mut decoder = SHIFT_JIS.new_decoder_without_bom_handling();
+                let mut text = String::with_capacity(
+                    decoder
+                        .max_utf8_buffer_length_without_replacement(data.len())
+                        .unwrap_or(data.len() * 3),
+                );
+                let (result, read) =
+                    decoder.decode_to_string_without_replacement(data, &mut text, true);
+                match result {
+                    DecoderResult::InputEmpty => Ok(text),
+                    DecoderResult::Malformed(malformed, extra) => Err(EncodingError::Decode {
+                        encoding: *self,
+                        position: read - malformed as usize - extra as usize,
+                    }),
+                    DecoderResult::OutputFull => unreachable!(),
+                }
+            }
+            Encoding::Latin1 => Ok(data.iter().map(|&byte| byte as char).collect()),
+        }
+    }
+    /// Encodes text to data.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use ghakuf::encoding::{Encoding, EncodingError};
+    ///
+    /// assert_eq!(Encoding::ShiftJis.encode("あい").unwrap(), [0x82, 0xa0, 0x82, 0xa2]);
+    /// assert_eq!(
+    ///     Encoding::Latin1.encode("あ"),
+    ///     Err(EncodingError::Encode { encoding: Encoding::Latin1, character: 'あ' })
+    /// );
+    /// ```
+    pub fn encode(&self, text: &str) -> Result<Vec<u8>, EncodingError> {
+        match *self {
+            Encoding::Utf8 => Ok(text.as_bytes().to_vec()),
+            Encoding::ShiftJis => {
+                let mut encoder = SHIFT_JIS.new_encoder();
+                let mut data = Vec::with_capacity(
+                    encoder
+                        .max_buffer_length_from_utf8_without_replacement(text.len())
+                        .unwrap_or(text.len() * 2),
+                );
+                match encoder.encode_from_utf8_to_vec_without_replacement(text, &mut data, true) {
+                    (EncoderResult::InputEmpty, _) => Ok(data),
+                    (EncoderResult::Unmappable(character), _) => Err(EncodingError::Encode {
+                        encoding: *self,
+                        character,
+                    }),
+                    (EncoderResult::OutputFull, _) => unreachable!(),
+                }
+            }
+            Encoding::Latin1 => text
+                .chars()
+                .map(|character| {
+                    if (character as u32) < 0x100 {
+                        Ok(character as u8)
+                    } else {
+                        Err(EncodingError::Encode {
+                            encoding: *self,
+                            character,
+                        })
+                    }
+                })
+                .collect(),
+        }
+    }
+}
+impl fmt::Display for Encoding {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Encoding::Utf8 => write!(f, "UTF-8"),
+            Encoding::ShiftJis => write!(f, "Shift-JIS"),
+            Encoding::Latin1 => write!(f, "ISO-8859-1"),
+        }
+    }
+}
+
+/// An enum represents errors of text encoding.
+#[derive(PartialEq, Clone, Debug)]
+pub enum EncodingError {
+    /// Data has invalid byte sequence at position.
+    Decode { encoding: Encoding, position: usize },
+    /// Character can't be represented in encoding.
+    Encode { encoding: Encoding, character: char },
+    /// Message isn't a text meta event.
+    NotText,
+}
+impl fmt::Display for EncodingError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        use encoding::EncodingError::*;
+        match *self {
+            Decode { encoding, position } => write!(
+                f,
+                "Invalid {} byte sequence has found at {}",
+                encoding, position
+            ),
+            Encode {
+                encoding,
+                character,
+            } => write!(f, "'{}' can't be encoded in {}", character, encoding),
+            NotText => write!(f, "Message isn't a text meta event."),
+        }
+    }
+}
+impl error::Error for EncodingError {}
+
+#[cfg(test)]
+mod encoding_tests {
+    use encoding::*;
+    #[test]
+    fn detect() {
+        // "Résumé" is also a valid byte sequence in Shift-JIS except the last byte.
+        assert_eq!(Encoding::detect(b"R\xe9sum\xe9"), Encoding::Latin1);
+        assert_eq!(
+            Encoding::detect(&Encoding::ShiftJis.encode("君が代").unwrap()),
+            Encoding::ShiftJis
+        );
+        assert_eq!(Encoding::detect(b""), Encoding::Utf8);
+    }
+    #[test]
+    fn shift_jis_error_position() {
+        assert_eq!(
+            Encoding::ShiftJis.decode(b"ab\x82\xa0\x82"),
+            Err(EncodingError::Decode {
+                encoding: Encoding::ShiftJis,
+                position: 4
+            })
+        );
+        assert_eq!(
+            Encoding::ShiftJis.encode("a€"),
+            Err(EncodingError::Encode {
+                encoding: Encoding::ShiftJis,
+                character: '€'
+            })
+        );
+    }
+}
@@ -1,3 +1,5 @@
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
 use std::fmt;
 
 /// An enum representing SMF format(0-2).
@@ -11,6 +13,7 @@ use std::fmt;
 /// assert_eq!(format.binary(), [0x00, 0x01]);
 /// ```
 #[derive(PartialEq, Clone, Copy, Debug)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub enum Format {
     /// Format 0 (binary literal: [0x00, 0x00])
     F0,
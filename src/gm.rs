@@ -0,0 +1,581 @@
+use std::fmt;
+
+/// MIDI channel reserved for percussion by General MIDI (channel 10, zero-based 9).
+pub const PERCUSSION_CHANNEL: u8 = 9;
+/// Bank select MSB of GM2 melodic sounds.
+pub const GM2_MELODIC_BANK: u8 = 0x79;
+/// Bank select MSB of GM2 rhythm (drum kit) sounds.
+pub const GM2_RHYTHM_BANK: u8 = 0x78;
+
+/// An enum representing General MIDI instrument families (eight programs each).
+///
+/// # Examples
+///
+/// ```
+/// use ghakuf::gm::{self, Family};
+///
+/// assert_eq!(gm::family(40), Family::Strings);
+/// assert_eq!(format!("{}", Family::SynthLead), "Synth Lead");
+/// ```
+#[derive(PartialEq, Eq, Clone, Copy, Debug)]
+pub enum Family {
+    Piano,
+    ChromaticPercussion,
+    Organ,
+    Guitar,
+    Bass,
+    Strings,
+    Ensemble,
+    Brass,
+    Reed,
+    Pipe,
+    SynthLead,
+    SynthPad,
+    SynthEffects,
+    Ethnic,
+    Percussive,
+    SoundEffects,
+}
+impl fmt::Display for Family {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        use gm::Family::*;
+        write!(
+            f,
+            "{}",
+            match *self {
+                Piano => "Piano",
+                ChromaticPercussion => "Chromatic Percussion",
+                Organ => "Organ",
+                Guitar => "Guitar",
+                Bass => "Bass",
+                Strings => "Strings",
+                Ensemble => "Ensemble",
+                Brass => "Brass",
+                Reed => "Reed",
+                Pipe => "Pipe",
+                SynthLead => "Synth Lead",
+                SynthPad => "Synth Pad",
+                SynthEffects => "Synth Effects",
+                Ethnic => "Ethnic",
+                Percussive => "Percussive",
+                SoundEffects => "Sound Effects",
+            }
+        )
+    }
+}
+
+/// Returns instrument family of GM program (0-127).
+///
+/// # Examples
+///
+/// ```
+/// use ghakuf::gm::{self, Family};
+///
+/// assert_eq!(gm::family(0), Family::Piano);
+/// assert_eq!(gm::family(127), Family::SoundEffects);
+/// ```
+pub fn family(program: u8) -> Family {
+    use gm::Family::*;
+    [
+        Piano,
+        ChromaticPercussion,
+        Organ,
+        Guitar,
+        Bass,
+        Strings,
+        Ensemble,
+        Brass,
+        Reed,
+        Pipe,
+        SynthLead,
+        SynthPad,
+        SynthEffects,
+        Ethnic,
+        Percussive,
+        SoundEffects,
+    ][(program as usize & 0x7f) / 8]
+}
+
+/// Returns GM1 instrument name of program (0-127).
+///
+/// # Examples
+///
+/// ```
+/// use ghakuf::gm;
+///
+/// assert_eq!(gm::program_name(0), "Acoustic Grand Piano");
+/// assert_eq!(gm::program_name(24), "Acoustic Guitar (nylon)");
+/// ```
+pub fn program_name(program: u8) -> &'static str {
+    PROGRAM_NAMES[program as usize & 0x7f]
+}
+
+/// Returns instrument name taking bank select into account.
+///
+/// Bank MSB 0 selects GM1 sounds and bank MSB 0x79 selects GM2 variations by bank LSB. A GM2
+/// variation which doesn't exist falls back to its capital sound as GM2 devices do. Bank MSB 0x78
+/// selects GM2 drum kits. Other banks are vendor specific, so this function returns `None`.
+///
+/// # Examples
+///
+/// ```
+/// use ghakuf::gm;
+///
+/// assert_eq!(gm::instrument_name(0, 0, 25), Some("Acoustic Guitar (steel)"));
+/// assert_eq!(gm::instrument_name(0x79, 2, 25), Some("Mandolin"));
+/// assert_eq!(gm::instrument_name(0x79, 9, 25), Some("Acoustic Guitar (steel)"));
+/// assert_eq!(gm::instrument_name(0x78, 0, 40), Some("Brush Kit"));
+/// assert_eq!(gm::instrument_name(0x51, 0, 0), None);
+/// ```
+pub fn instrument_name(bank_msb: u8, bank_lsb: u8, program: u8) -> Option<&'static str> {
+    let program = program & 0x7f;
+    match bank_msb {
+        0 => Some(program_name(program)),
+        GM2_MELODIC_BANK => Some(
+            GM2_VARIATIONS
+                .iter()
+                .find(|&&(variation_program, lsb, _)| {
+                    variation_program == program && lsb == bank_lsb
+                })
+                .map_or(program_name(program), |&(_, _, name)| name),
+        ),
+        GM2_RHYTHM_BANK => drum_kit_name(program),
+        _ => None,
+    }
+}
+
+/// Returns GM2 drum kit name of program selected on rhythm bank.
+///
+/// # Examples
+///
+/// ```
+/// use ghakuf::gm;
+///
+/// assert_eq!(gm::drum_kit_name(0), Some("Standard Kit"));
+/// assert_eq!(gm::drum_kit_name(1), None);
+/// ```
+pub fn drum_kit_name(program: u8) -> Option<&'static str> {
+    DRUM_KITS
+        .iter()
+        .find(|&&(kit_program, _)| kit_program == program)
+        .map(|&(_, name)| name)
+}
+
+/// Returns percussion name of key on the percussion channel.
+///
+/// Keys 35-81 are defined by GM1 and keys 27-34 and 82-87 are added by GM2.
+///
+/// # Examples
+///
+/// ```
+/// use ghakuf::gm;
+///
+/// assert_eq!(gm::percussion_name(36), Some("Bass Drum 1"));
+/// assert_eq!(gm::percussion_name(27), Some("High Q"));
+/// assert_eq!(gm::percussion_name(100), None);
+/// ```
+pub fn percussion_name(key: u8) -> Option<&'static str> {
+    match key {
+        27..=87 => Some(PERCUSSION_NAMES[key as usize - 27]),
+        _ => None,
+    }
+}
+
+/// Returns name of control change number.
+///
+/// # Examples
+///
+/// ```
+/// use ghakuf::gm;
+///
+/// assert_eq!(gm::controller_name(7), Some("Channel Volume"));
+/// assert_eq!(gm::controller_name(3), None);
+/// ```
+pub fn controller_name(control: u8) -> Option<&'static str> {
+    Some(match control {
+        0 => "Bank Select",
+        1 => "Modulation",
+        2 => "Breath Controller",
+        4 => "Foot Controller",
+        5 => "Portamento Time",
+        6 => "Data Entry",
+        7 => "Channel Volume",
+        8 => "Balance",
+        10 => "Pan",
+        11 => "Expression",
+        12 => "Effect Control 1",
+        13 => "Effect Control 2",
+        16..=19 => "General Purpose Controller",
+        32 => "Bank Select LSB",
+        38 => "Data Entry LSB",
+        33..=63 => "Controller LSB",
+        64 => "Sustain",
+        65 => "Portamento",
+        66 => "Sostenuto",
+        67 => "Soft Pedal",
+        68 => "Legato Footswitch",
+        69 => "Hold 2",
+        70..=79 => "Sound Controller",
+        80..=83 => "General Purpose Controller",
+        84 => "Portamento Control",
+        91 => "Reverb Send Level",
+        92 => "Tremolo Depth",
+        93 => "Chorus Send Level",
+        94 => "Celeste Depth",
+        95 => "Phaser Depth",
+        96 => "Data Increment",
+        97 => "Data Decrement",
+        98 => "NRPN LSB",
+        99 => "NRPN MSB",
+        100 => "RPN LSB",
+        101 => "RPN MSB",
+        120 => "All Sound Off",
+        121 => "Reset All Controllers",
+        122 => "Local Control",
+        123 => "All Notes Off",
+        124 => "Omni Mode Off",
+        125 => "Omni Mode On",
+        126 => "Mono Mode On",
+        127 => "Poly Mode On",
+        _ => return None,
+    })
+}
+
+static PROGRAM_NAMES: [&str; 128] = [
+    "Acoustic Grand Piano",
+    "Bright Acoustic Piano",
+    "Electric Grand Piano",
+    "Honky-tonk Piano",
+    "Electric Piano 1",
+    "Electric Piano 2",
+    "Harpsichord",
+    "Clavi",
+    "Celesta",
+    "Glockenspiel",
+    "Music Box",
+    "Vibraphone",
+    "Marimba",
+    "Xylophone",
+    "Tubular Bells",
+    "Dulcimer",
+    "Drawbar Organ",
+    "Percussive Organ",
+    "Rock Organ",
+    "Church Organ",
+    "Reed Organ",
+    "Accordion",
+    "Harmonica",
+    "Tango Accordion",
+    "Acoustic Guitar (nylon)",
+    "Acoustic Guitar (steel)",
+    "Electric Guitar (jazz)",
+    "Electric Guitar (clean)",
+    "Electric Guitar (muted)",
+    "Overdriven Guitar",
+    "Distortion Guitar",
+    "Guitar harmonics",
+    "Acoustic Bass",
+    "Electric Bass (finger)",
+    "Electric Bass (pick)",
+    "Fretless Bass",
+    "Slap Bass 1",
+    "Slap Bass 2",
+    "Synth Bass 1",
+    "Synth Bass 2",
+    "Violin",
+    "Viola",
+    "Cello",
+    "Contrabass",
+    "Tremolo Strings",
+    "Pizzicato Strings",
+    "Orchestral Harp",
+    "Timpani",
+    "String Ensemble 1",
+    "String Ensemble 2",
+    "SynthStrings 1",
+    "SynthStrings 2",
+    "Choir Aahs",
+    "Voice Oohs",
+    "Synth Voice",
+    "Orchestra Hit",
+    "Trumpet",
+    "Trombone",
+    "Tuba",
+    "Muted Trumpet",
+    "French Horn",
+    "Brass Section",
+    "SynthBrass 1",
+    "SynthBrass 2",
+    "Soprano Sax",
+    "Alto Sax",
+    "Tenor Sax",
+    "Baritone Sax",
+    "Oboe",
+    "English Horn",
+    "Bassoon",
+    "Clarinet",
+    "Piccolo",
+    "Flute",
+    "Recorder",
+    "Pan Flute",
+    "Blown Bottle",
+    "Shakuhachi",
+    "Whistle",
+    "Ocarina",
+    "Lead 1 (square)",
+    "Lead 2 (sawtooth)",
+    "Lead 3 (calliope)",
+    "Lead 4 (chiff)",
+    "Lead 5 (charang)",
+    "Lead 6 (voice)",
+    "Lead 7 (fifths)",
+    "Lead 8 (bass + lead)",
+    "Pad 1 (new age)",
+    "Pad 2 (warm)",
+    "Pad 3 (polysynth)",
+    "Pad 4 (choir)",
+    "Pad 5 (bowed)",
+    "Pad 6 (metallic)",
+    "Pad 7 (halo)",
+    "Pad 8 (sweep)",
+    "FX 1 (rain)",
+    "FX 2 (soundtrack)",
+    "FX 3 (crystal)",
+    "FX 4 (atmosphere)",
+    "FX 5 (brightness)",
+    "FX 6 (goblins)",
+    "FX 7 (echoes)",
+    "FX 8 (sci-fi)",
+    "Sitar",
+    "Banjo",
+    "Shamisen",
+    "Koto",
+    "Kalimba",
+    "Bag pipe",
+    "Fiddle",
+    "Shanai",
+    "Tinkle Bell",
+    "Agogo",
+    "Steel Drums",
+    "Woodblock",
+    "Taiko Drum",
+    "Melodic Tom",
+    "Synth Drum",
+    "Reverse Cymbal",
+    "Guitar Fret Noise",
+    "Breath Noise",
+    "Seashore",
+    "Bird Tweet",
+    "Telephone Ring",
+    "Helicopter",
+    "Applause",
+    "Gunshot",
+];
+
+// (program, bank LSB, name) of GM2 variation sounds. Bank LSB 0 is the capital sound.
+static GM2_VARIATIONS: [(u8, u8, &str); 128] = [
+    (0, 1, "Acoustic Grand Piano (wide)"),
+    (0, 2, "Acoustic Grand Piano (dark)"),
+    (1, 1, "Bright Acoustic Piano (wide)"),
+    (2, 1, "Electric Grand Piano (wide)"),
+    (3, 1, "Honky-tonk Piano (wide)"),
+    (4, 1, "Detuned Electric Piano 1"),
+    (4, 2, "Electric Piano 1 (velocity mix)"),
+    (4, 3, "60's Electric Piano"),
+    (5, 1, "Detuned Electric Piano 2"),
+    (5, 2, "Electric Piano 2 (velocity mix)"),
+    (5, 3, "EP Legend"),
+    (5, 4, "EP Phase"),
+    (6, 1, "Harpsichord (octave mix)"),
+    (6, 2, "Harpsichord (wide)"),
+    (6, 3, "Harpsichord (with key off)"),
+    (7, 1, "Pulse Clavi"),
+    (11, 1, "Vibraphone (wet)"),
+    (12, 1, "Marimba (wide)"),
+    (14, 1, "Church Bell"),
+    (14, 2, "Carillon"),
+    (16, 1, "Detuned Drawbar Organ"),
+    (16, 2, "Italian 60's Organ"),
+    (16, 3, "Drawbar Organ 2"),
+    (17, 1, "Detuned Percussive Organ"),
+    (17, 2, "Percussive Organ 2"),
+    (19, 1, "Church Organ (octave mix)"),
+    (19, 2, "Detuned Church Organ"),
+    (20, 1, "Puff Organ"),
+    (21, 1, "Accordion 2"),
+    (24, 1, "Ukulele"),
+    (24, 2, "Acoustic Guitar (nylon 2)"),
+    (24, 3, "Acoustic Guitar (nylon + key off)"),
+    (25, 1, "12-Strings Guitar"),
+    (25, 2, "Mandolin"),
+    (25, 3, "Steel Guitar with Body Sound"),
+    (26, 1, "Electric Guitar (pedal steel)"),
+    (27, 1, "Electric Guitar (detuned clean)"),
+    (27, 2, "Mid Tone Guitar"),
+    (28, 1, "Electric Guitar (funky cutting)"),
+    (28, 2, "Electric Guitar (muted velo-sw)"),
+    (28, 3, "Jazz Man"),
+    (29, 1, "Guitar Pinch"),
+    (30, 1, "Distortion Guitar (with feedback)"),
+    (30, 2, "Distorted Rhythm Guitar"),
+    (31, 1, "Guitar Feedback"),
+    (33, 1, "Finger Slap Bass"),
+    (38, 1, "Synth Bass (warm)"),
+    (38, 2, "Synth Bass 3 (resonance)"),
+    (38, 3, "Clavi Bass"),
+    (38, 4, "Hammer"),
+    (39, 1, "Synth Bass 4 (attack)"),
+    (39, 2, "Synth Bass (rubber)"),
+    (39, 3, "Attack Pulse"),
+    (40, 1, "Violin (slow attack)"),
+    (46, 1, "Yang Chin"),
+    (48, 1, "Strings and Brass"),
+    (48, 2, "60s Strings"),
+    (50, 1, "Synth Strings 3"),
+    (52, 1, "Choir Aahs 2"),
+    (53, 1, "Humming"),
+    (54, 1, "Analog Voice"),
+    (55, 1, "Bass Hit Plus"),
+    (55, 2, "6th Hit"),
+    (55, 3, "Euro Hit"),
+    (56, 1, "Dark Trumpet Soft"),
+    (57, 1, "Trombone 2"),
+    (57, 2, "Bright Trombone"),
+    (59, 1, "Muted Trumpet 2"),
+    (60, 1, "French Horn 2 (warm)"),
+    (61, 1, "Brass Section 2 (octave mix)"),
+    (62, 1, "Synth Brass 3"),
+    (62, 2, "Analog Synth Brass 1"),
+    (62, 3, "Jump Brass"),
+    (63, 1, "Synth Brass 4"),
+    (63, 2, "Analog Synth Brass 2"),
+    (80, 1, "Lead 1a (square 2)"),
+    (80, 2, "Lead 1b (sine)"),
+    (81, 1, "Lead 2a (sawtooth 2)"),
+    (81, 2, "Lead 2b (saw + pulse)"),
+    (81, 3, "Lead 2c (double sawtooth)"),
+    (81, 4, "Lead 2d (sequenced analog)"),
+    (84, 1, "Lead 5a (wire lead)"),
+    (87, 1, "Lead 8a (soft wrl)"),
+    (89, 1, "Pad 2a (sine pad)"),
+    (91, 1, "Pad 4a (itopia)"),
+    (98, 1, "FX 3a (synth mallet)"),
+    (102, 1, "FX 7a (echo bell)"),
+    (102, 2, "FX 7b (echo pan)"),
+    (104, 1, "Sitar 2 (bend)"),
+    (107, 1, "Taisho Koto"),
+    (115, 1, "Castanets"),
+    (116, 1, "Concert Bass Drum"),
+    (117, 1, "Melodic Tom 2 (power)"),
+    (118, 1, "Rhythm Box Tom"),
+    (118, 2, "Electric Drum"),
+    (120, 1, "Guitar Cutting Noise"),
+    (120, 2, "Acoustic Bass String Slap"),
+    (121, 1, "Flute Key Click"),
+    (122, 1, "Rain"),
+    (122, 2, "Thunder"),
+    (122, 3, "Wind"),
+    (122, 4, "Stream"),
+    (122, 5, "Bubble"),
+    (123, 1, "Dog"),
+    (123, 2, "Horse Gallop"),
+    (123, 3, "Bird Tweet 2"),
+    (124, 1, "Telephone Ring 2"),
+    (124, 2, "Door Creaking"),
+    (124, 3, "Door"),
+    (124, 4, "Scratch"),
+    (124, 5, "Wind Chime"),
+    (125, 1, "Car Engine"),
+    (125, 2, "Car Stop"),
+    (125, 3, "Car Pass"),
+    (125, 4, "Car Crash"),
+    (125, 5, "Siren"),
+    (125, 6, "Train"),
+    (125, 7, "Jetplane"),
+    (125, 8, "Starship"),
+    (125, 9, "Burst Noise"),
+    (126, 1, "Laughing"),
+    (126, 2, "Screaming"),
+    (126, 3, "Punch"),
+    (126, 4, "Heart Beat"),
+    (126, 5, "Footsteps"),
+    (127, 1, "Machine Gun"),
+    (127, 2, "Lasergun"),
+    (127, 3, "Explosion"),
+];
+
+static DRUM_KITS: [(u8, &str); 9] = [
+    (0, "Standard Kit"),
+    (8, "Room Kit"),
+    (16, "Power Kit"),
+    (24, "Electronic Kit"),
+    (25, "Analog Kit"),
+    (32, "Jazz Kit"),
+    (40, "Brush Kit"),
+    (48, "Orchestra Kit"),
+    (56, "SFX Kit"),
+];
+
+static PERCUSSION_NAMES: [&str; 61] = [
+    "High Q",
+    "Slap",
+    "Scratch Push",
+    "Scratch Pull",
+    "Sticks",
+    "Square Click",
+    "Metronome Click",
+    "Metronome Bell",
+    "Acoustic Bass Drum",
+    "Bass Drum 1",
+    "Side Stick",
+    "Acoustic Snare",
+    "Hand Clap",
+    "Electric Snare",
+    "Low Floor Tom",
+    "Closed Hi Hat",
+    "High Floor Tom",
+    "Pedal Hi-Hat",
+    "Low Tom",
+    "Open Hi-Hat",
+    "Low-Mid Tom",
+    "Hi-Mid Tom",
+    "Crash Cymbal 1",
+    "High Tom",
+    "Ride Cymbal 1",
+    "Chinese Cymbal",
+    "Ride Bell",
+    "Tambourine",
+    "Splash Cymbal",
+    "Cowbell",
+    "Crash Cymbal 2",
+    "Vibraslap",
+    "Ride Cymbal 2",
+    "Hi Bongo",
+    "Low Bongo",
+    "Mute Hi Conga",
+    "Open Hi Conga",
+    "Low Conga",
+    "High Timbale",
+    "Low Timbale",
+    "High Agogo",
+    "Low Agogo",
+    "Cabasa",
+    "Maracas",
+    "Short Whistle",
+    "Long Whistle",
+    "Short Guiro",
+    "Long Guiro",
+    "Claves",
+    "Hi Wood Block",
+    "Low Wood Block",
+    "Mute Cuica",
+    "Open Cuica",
+    "Mute Triangle",
+    "Open Triangle",
+    "Shaker",
+    "Jingle Bell",
+    "Belltree",
+    "Castanets",
+    "Mute Surdo",
+    "Open Surdo",
+];
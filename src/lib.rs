@@ -82,15 +82,85 @@
 //! writer.write(&path);
 //! ```
 //!
+//! ## Serde
+//!
+//! With `serde` feature, `Message`, `MetaEvent`, `MidiEvent`, `SysExEvent` and `Format` implement
+//! `Serialize` and `Deserialize`. Enums of events are internally tagged with variant name as `type`,
+//! fields keep their names and data is an array of bytes. `Format` is a string (`"F0"`, `"F1"`,
+//! `"F2"` or `"Unknown"`). This representation is kept stable across versions.
+//!
+//! ```
+//! # #[cfg(feature = "serde")]
+//! # {
+//! # extern crate ghakuf;
+//! # extern crate serde_json;
+//! use ghakuf::messages::*;
+//!
+//! let message = Message::MidiEvent {
+//!     delta_time: 480,
+//!     event: MidiEvent::NoteOn { ch: 0, note: 60, velocity: 100 },
+//! };
+//! let json = r#"{"type":"MidiEvent","delta_time":480,"event":{"type":"NoteOn","ch":0,"note":60,"velocity":100}}"#;
+//! assert_eq!(serde_json::to_string(&message).unwrap(), json);
+//! assert_eq!(serde_json::from_str::<Message>(json).unwrap(), message);
+//!
+//! let message = Message::MetaEvent {
+//!     delta_time: 0,
+//!     event: MetaEvent::SetTempo,
+//!     data: vec![0x07, 0xa1, 0x20],
+//! };
+//! let json = r#"{"type":"MetaEvent","delta_time":0,"event":{"type":"SetTempo"},"data":[7,161,32]}"#;
+//! assert_eq!(serde_json::to_string(&message).unwrap(), json);
+//! assert_eq!(
+//!     serde_json::to_string(&MetaEvent::Unknown { event_type: 0x60 }).unwrap(),
+//!     r#"{"type":"Unknown","event_type":96}"#
+//! );
+//! assert_eq!(serde_json::to_string(&Message::TrackChange).unwrap(), r#"{"type":"TrackChange"}"#);
+//! # }
+//! ```
+//!
 extern crate byteorder;
+extern crate encoding_rs;
 #[macro_use]
 extern crate log;
+#[cfg(feature = "serde")]
+extern crate serde;
 
+/// Structural diff of two SMFs (header, track names and events in absolute time)
+pub mod diff;
+/// Text encoding detection and conversion (UTF-8, Shift-JIS, Latin-1) for text meta events
+pub mod encoding;
 /// SMF Formats and Variable Length Quantity
 pub mod formats;
+/// General MIDI instrument, drum kit, percussion and controller names
+pub mod gm;
+/// Timed lyrics of Lyric meta events and .kar text events
+pub mod lyrics;
 /// enums representing SMF messages (Meta event, MIDI event, System exclusive event)
 pub mod messages;
+/// midicsv compatible CSV import and export
+pub mod midicsv;
+/// RPN/NRPN and 14-bit control change aggregation
+pub mod parameters;
 /// SMF parser and handler
 pub mod reader;
-/// SMF builder
+/// RIFF MIDI (RMID) wrapping and unwrapping
+pub mod rmid;
+/// MIDI channel prefix and MIDI port routing of meta and system exclusive events
+pub mod routing;
+/// Whole SMF on memory read and written through Reader and Writer (tempo scaling, slicing, concatenation)
+pub mod smf;
+/// Channel state tracking for seeking (controller/program/pitch chase)
+pub mod state;
+/// System exclusive data decoding (manufacturer ID and Universal messages)
+pub mod sysex;
+/// Human readable text format of SMF rebuilt into the same messages and bytes
+pub mod text;
+/// Tempo map and meter map converting ticks to seconds and musical time (bar, beat, tick)
+pub mod timing;
+/// Transforms of message streams (quantisation, normalisation, transposition, velocity, channel and program remap)
+pub mod transform;
+/// SMF validation
+pub mod validate;
+/// SMF builder (Writer) and streaming SMF builder (StreamWriter)
 pub mod writer;
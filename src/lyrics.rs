@@ -0,0 +1,427 @@
+use encoding::*;
+use messages::*;
+use reader::Handler;
+use timing::TempoMap;
+
+/// An enum representing break before a lyric syllable.
+///
+/// Breaks are ordered by strength, `Syllable < Line < Paragraph`.
+#[derive(PartialEq, Eq, PartialOrd, Ord, Hash, Clone, Copy, Debug)]
+pub enum Break {
+    /// Continues the current line.
+    Syllable,
+    /// Starts a new line (`/` in .kar, CR in Lyric meta event).
+    Line,
+    /// Starts a new paragraph, or clears the screen (`\` in .kar, LF in Lyric meta event).
+    Paragraph,
+}
+
+/// A struct representing a timed lyric syllable.
+#[derive(PartialEq, Clone, Debug)]
+pub struct LyricEntry {
+    /// Absolute tick in the track
+    pub tick: u64,
+    /// Time in seconds from the tempo map
+    pub seconds: f64,
+    /// Text without break markers
+    pub text: String,
+    /// Break before this syllable
+    pub break_before: Break,
+}
+
+/// An enum representing how lyrics are written into a track.
+#[derive(PartialEq, Eq, Clone, Copy, Debug)]
+pub enum LyricStyle {
+    /// Lyric meta events with CR (line) and LF (paragraph) at the end of syllables
+    Lyric,
+    /// TextEvent meta events of .kar with `/` (line) and `\` (paragraph) at the head of syllables
+    Karaoke,
+}
+
+/// A struct representing a lyric timeline with .kar headers.
+///
+/// # Examples
+///
+/// ```
+/// use ghakuf::lyrics::{Break, Lyrics};
+///
+/// let mut lyrics = Lyrics::new();
+/// lyrics
+///     .push(0, "Twin", Break::Paragraph)
+///     .push(240, "kle", Break::Syllable)
+///     .push(480, "Up a", Break::Line);
+/// assert_eq!(lyrics.text(), "Twinkle\nUp a");
+/// ```
+#[derive(PartialEq, Clone, Debug)]
+pub struct Lyrics {
+    /// Titles (`@T` of .kar; title, author and copyright conventionally)
+    pub title: Vec<String>,
+    /// Language (`@L` of .kar)
+    pub language: Option<String>,
+    /// Information (`@I` of .kar)
+    pub info: Vec<String>,
+    /// Lyric syllables in order of tick
+    pub entries: Vec<LyricEntry>,
+}
+impl Lyrics {
+    /// Builds empty Lyrics.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use ghakuf::lyrics::Lyrics;
+    ///
+    /// assert!(Lyrics::new().entries.is_empty());
+    /// ```
+    pub fn new() -> Lyrics {
+        Lyrics {
+            title: Vec::new(),
+            language: None,
+            info: Vec::new(),
+            entries: Vec::new(),
+        }
+    }
+    /// Pushes lyric syllable at tick (seconds is left 0.0).
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use ghakuf::lyrics::{Break, Lyrics};
+    ///
+    /// let mut lyrics = Lyrics::new();
+    /// lyrics.push(0, "La", Break::Paragraph);
+    /// assert_eq!(lyrics.entries[0].text, "La");
+    /// ```
+    pub fn push(&mut self, tick: u64, text: &str, break_before: Break) -> &mut Lyrics {
+        self.entries.push(LyricEntry {
+            tick,
+            seconds: 0.0,
+            text: text.to_string(),
+            break_before,
+        });
+        self
+    }
+    /// Returns whole text joining syllables with "\n" for lines and "\n\n" for paragraphs.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use ghakuf::lyrics::{Break, Lyrics};
+    ///
+    /// let mut lyrics = Lyrics::new();
+    /// lyrics.push(0, "A", Break::Paragraph).push(1, "B", Break::Paragraph);
+    /// assert_eq!(lyrics.text(), "A\n\nB");
+    /// ```
+    pub fn text(&self) -> String {
+        let mut text = String::new();
+        for (i, entry) in self.entries.iter().enumerate() {
+            if i > 0 {
+                match entry.break_before {
+                    Break::Syllable => (),
+                    Break::Line => text.push('\n'),
+                    Break::Paragraph => text.push_str("\n\n"),
+                }
+            }
+            text.push_str(&entry.text);
+        }
+        text
+    }
+    /// Makes messages of a lyric track (without TrackChange, with EndOfTrack).
+    ///
+    /// .kar headers are written as TextEvent meta events at the head of the track in both styles.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use ghakuf::encoding::Encoding;
+    /// use ghakuf::lyrics::{Break, LyricStyle, Lyrics};
+    /// use ghakuf::messages::{Message, MetaEvent};
+    ///
+    /// let mut lyrics = Lyrics::new();
+    /// lyrics.push(0, "Twin", Break::Paragraph).push(240, "kle", Break::Syllable);
+    /// let messages = lyrics.messages(LyricStyle::Karaoke, Encoding::Latin1).unwrap();
+    /// assert_eq!(
+    ///     messages[2],
+    ///     Message::MetaEvent { delta_time: 0, event: MetaEvent::TextEvent, data: b"\\Twin".to_vec() }
+    /// );
+    /// assert_eq!(
+    ///     messages[3],
+    ///     Message::MetaEvent { delta_time: 240, event: MetaEvent::TextEvent, data: b"kle".to_vec() }
+    /// );
+    /// ```
+    pub fn messages(
+        &self,
+        style: LyricStyle,
+        encoding: Encoding,
+    ) -> Result<Vec<Message>, EncodingError> {
+        let mut messages: Vec<Message> = Vec::new();
+        let mut headers: Vec<String> = Vec::new();
+        if style == LyricStyle::Karaoke {
+            headers.push("@KMIDI KARAOKE FILE".to_string());
+            headers.push("@V0100".to_string());
+        }
+        if let Some(ref language) = self.language {
+            headers.push(format!("@L{}", language));
+        }
+        headers.extend(self.title.iter().map(|title| format!("@T{}", title)));
+        headers.extend(self.info.iter().map(|info| format!("@I{}", info)));
+        for header in &headers {
+            messages.push(Message::text_event(0, header, encoding)?);
+        }
+        let mut entries: Vec<&LyricEntry> = self.entries.iter().collect();
+        entries.sort_by_key(|entry| entry.tick);
+        let mut tick = 0;
+        for (i, entry) in entries.iter().enumerate() {
+            let delta_time = (entry.tick - tick) as u32;
+            tick = entry.tick;
+            let message = match style {
+                LyricStyle::Karaoke => {
+                    let prefix = match entry.break_before {
+                        Break::Syllable => "",
+                        Break::Line => "/",
+                        Break::Paragraph => "\\",
+                    };
+                    Message::text_event(delta_time, &format!("{}{}", prefix, entry.text), encoding)?
+                }
+                LyricStyle::Lyric => {
+                    let suffix = match entries.get(i + 1).map(|next| next.break_before) {
+                        Some(Break::Line) => "\r",
+                        Some(Break::Paragraph) => "\n",
+                        _ => "",
+                    };
+                    Message::lyric(delta_time, &format!("{}{}", entry.text, suffix), encoding)?
+                }
+            };
+            messages.push(message);
+        }
+        messages.push(Message::MetaEvent {
+            delta_time: 0,
+            event: MetaEvent::EndOfTrack,
+            data: Vec::new(),
+        });
+        Ok(messages)
+    }
+}
+impl Default for Lyrics {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Handler collecting lyrics from Lyric and TextEvent meta events.
+///
+/// Lyric meta events are used if the file has any, otherwise TextEvent meta events (.kar) are used.
+/// TextEvent meta events beginning with `@` are .kar headers. Text is decoded by the encoding detected
+/// from all the text unless encoding is given.
+///
+/// # Examples
+///
+/// ```
+/// use ghakuf::encoding::Encoding;
+/// use ghakuf::lyrics::{Break, LyricStyle, Lyrics, LyricsHandler};
+/// use ghakuf::reader::Handler;
+///
+/// let mut lyrics = Lyrics::new();
+/// lyrics.title.push("かえるの歌".to_string());
+/// lyrics.push(0, "か", Break::Paragraph).push(480, "え", Break::Syllable).push(960, "る", Break::Line);
+///
+/// let mut handler = LyricsHandler::new();
+/// handler.header(1, 1, 480);
+/// for message in lyrics.messages(LyricStyle::Karaoke, Encoding::ShiftJis).unwrap() {
+///     if let ghakuf::messages::Message::MetaEvent { delta_time, event, data } = message {
+///         handler.meta_event(delta_time, &event, &data);
+///     }
+/// }
+/// let read = handler.lyrics().unwrap();
+/// assert_eq!(read.title, ["かえるの歌"]);
+/// assert_eq!(read.entries[2].break_before, Break::Line);
+/// assert_eq!(read.entries[2].seconds, 1.0);
+/// ```
+pub struct LyricsHandler {
+    tempo_map: TempoMap,
+    tick: u64,
+    lyric_events: Vec<(u64, Vec<u8>)>,
+    text_events: Vec<(u64, Vec<u8>)>,
+    encoding: Option<Encoding>,
+}
+impl LyricsHandler {
+    /// Builds LyricsHandler detecting encoding.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use ghakuf::lyrics::LyricsHandler;
+    ///
+    /// let handler = LyricsHandler::new();
+    /// assert!(handler.lyrics().unwrap().entries.is_empty());
+    /// ```
+    pub fn new() -> LyricsHandler {
+        LyricsHandler {
+            tempo_map: TempoMap::new(480),
+            tick: 0,
+            lyric_events: Vec::new(),
+            text_events: Vec::new(),
+            encoding: None,
+        }
+    }
+    /// Sets encoding of text (None detects it).
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use ghakuf::encoding::Encoding;
+    /// use ghakuf::lyrics::LyricsHandler;
+    ///
+    /// let mut handler = LyricsHandler::new();
+    /// handler.encoding(Some(Encoding::ShiftJis));
+    /// ```
+    pub fn encoding(&mut self, encoding: Option<Encoding>) -> &mut LyricsHandler {
+        self.encoding = encoding;
+        self
+    }
+    /// Returns tempo map collected from SetTempo meta events.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use ghakuf::lyrics::LyricsHandler;
+    ///
+    /// assert_eq!(LyricsHandler::new().tempo_map().time_base(), 480);
+    /// ```
+    pub fn tempo_map(&self) -> &TempoMap {
+        &self.tempo_map
+    }
+    /// Returns decoded lyrics with time in seconds.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use ghakuf::lyrics::{Break, LyricsHandler};
+    /// use ghakuf::messages::MetaEvent;
+    /// use ghakuf::reader::Handler;
+    ///
+    /// let mut handler = LyricsHandler::new();
+    /// handler.meta_event(0, &MetaEvent::Lyric, &b"Hel".to_vec());
+    /// handler.meta_event(240, &MetaEvent::Lyric, &b"lo\r".to_vec());
+    /// handler.meta_event(240, &MetaEvent::Lyric, &b"World".to_vec());
+    /// let lyrics = handler.lyrics().unwrap();
+    /// assert_eq!(lyrics.text(), "Hello\nWorld");
+    /// assert_eq!(lyrics.entries[2].break_before, Break::Line);
+    /// ```
+    pub fn lyrics(&self) -> Result<Lyrics, EncodingError> {
+        let encoding = match self.encoding {
+            Some(encoding) => encoding,
+            None => {
+                let all: Vec<u8> = self
+                    .lyric_events
+                    .iter()
+                    .chain(self.text_events.iter())
+                    .flat_map(|(_, data)| data.iter().cloned())
+                    .collect();
+                Encoding::detect(&all)
+            }
+        };
+        let mut lyrics = Lyrics::new();
+        let mut syllables: Vec<(u64, String)> = Vec::new();
+        for &(tick, ref data) in &self.text_events {
+            let text = encoding.decode(data)?;
+            if text.starts_with('@') {
+                let value = text.chars().skip(2).collect::<String>();
+                match text.chars().nth(1) {
+                    Some('T') => lyrics.title.push(value),
+                    Some('L') => lyrics.language = Some(value),
+                    Some('I') => lyrics.info.push(value),
+                    _ => (),
+                }
+            } else if self.lyric_events.is_empty() {
+                syllables.push((tick, text));
+            }
+        }
+        for &(tick, ref data) in &self.lyric_events {
+            syllables.push((tick, encoding.decode(data)?));
+        }
+        // events of several tracks are gathered in order of tracks
+        syllables.sort_by_key(|&(tick, _)| tick);
+        let mut pending = Break::Paragraph;
+        for (tick, text) in syllables {
+            let mut text = text.as_str();
+            loop {
+                if text.starts_with('\\') || text.starts_with('\n') {
+                    pending = pending.max(Break::Paragraph);
+                } else if text.starts_with('/') || text.starts_with('\r') {
+                    pending = pending.max(Break::Line);
+                } else {
+                    break;
+                }
+                text = &text[1..];
+            }
+            let mut after = Break::Syllable;
+            while text.ends_with('\n') || text.ends_with('\r') {
+                after = after.max(if text.ends_with('\n') {
+                    Break::Paragraph
+                } else {
+                    Break::Line
+                });
+                text = &text[..text.len() - 1];
+            }
+            if !text.is_empty() {
+                lyrics.entries.push(LyricEntry {
+                    tick,
+                    seconds: self.tempo_map.seconds(tick),
+                    text: text.to_string(),
+                    break_before: pending,
+                });
+                pending = Break::Syllable;
+            }
+            pending = pending.max(after);
+        }
+        Ok(lyrics)
+    }
+}
+impl Default for LyricsHandler {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+impl Handler for LyricsHandler {
+    fn header(&mut self, format: u16, track: u16, time_base: u16) {
+        self.tempo_map.header(format, track, time_base);
+    }
+    fn meta_event(&mut self, delta_time: u32, event: &MetaEvent, data: &Vec<u8>) {
+        self.tempo_map.meta_event(delta_time, event, data);
+        self.tick += delta_time as u64;
+        match *event {
+            MetaEvent::Lyric => self.lyric_events.push((self.tick, data.clone())),
+            MetaEvent::TextEvent => self.text_events.push((self.tick, data.clone())),
+            _ => (),
+        }
+    }
+    fn midi_event(&mut self, delta_time: u32, event: &MidiEvent) {
+        self.tempo_map.midi_event(delta_time, event);
+        self.tick += delta_time as u64;
+    }
+    fn sys_ex_event(&mut self, delta_time: u32, event: &SysExEvent, data: &Vec<u8>) {
+        self.tempo_map.sys_ex_event(delta_time, event, data);
+        self.tick += delta_time as u64;
+    }
+    fn track_change(&mut self) {
+        self.tempo_map.track_change();
+        self.tick = 0;
+    }
+}
+#[cfg(test)]
+mod lyrics_tests {
+    use lyrics::*;
+    #[test]
+    fn syllables_of_tracks_in_order_of_tick() {
+        let mut handler = LyricsHandler::new();
+        handler.meta_event(0, &MetaEvent::Lyric, &b"Hel".to_vec());
+        handler.meta_event(480, &MetaEvent::Lyric, &b"World".to_vec());
+        handler.track_change();
+        handler.meta_event(240, &MetaEvent::Lyric, &b"lo\r".to_vec());
+        let lyrics = handler.lyrics().unwrap();
+        let ticks: Vec<u64> = lyrics.entries.iter().map(|entry| entry.tick).collect();
+        assert_eq!(ticks, [0, 240, 480]);
+        assert_eq!(lyrics.text(), "Hello\nWorld");
+    }
+}
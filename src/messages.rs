@@ -1,5 +1,11 @@
+use encoding::*;
 use formats::*;
-use std::fmt;
+use gm;
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+use std::str::FromStr;
+use std::{error, fmt};
+use sysex::ManufacturerId;
 
 /// Common methods among three SMF Events.
 ///
@@ -70,6 +76,8 @@ pub trait MessageTool {
 /// messages.push(Message::TrackChange);
 /// ```
 #[derive(PartialEq, Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "serde", serde(tag = "type"))]
 pub enum Message {
     MetaEvent {
         delta_time: u32,
@@ -129,19 +137,11 @@ impl Message {
                 ref event,
                 ref data,
             } => {
+                // data doesn't contain status byte (0xf0 or 0xf7) as Reader passes it to Handler.
                 binary.append(&mut VLQ::new(delta_time).binary());
                 binary.append(&mut event.binary());
-                use messages::SysExEvent::*;
-                match *event {
-                    F0 => {
-                        binary.append(&mut VLQ::new(data.len() as u32 - 1).binary());
-                        binary.extend_from_slice(&data[1..]);
-                    }
-                    _ => {
-                        binary.append(&mut VLQ::new(data.len() as u32).binary());
-                        binary.extend_from_slice(&data);
-                    }
-                }
+                binary.append(&mut VLQ::new(data.len() as u32).binary());
+                binary.extend_from_slice(&data);
             }
             TrackChange => binary.append(&mut Tag::Track.binary().to_vec()),
         }
@@ -185,21 +185,303 @@ impl Message {
                 ref event,
                 ref data,
             } => {
-                use messages::SysExEvent::*;
                 VLQ::new(delta_time).len()
-                    + VLQ::new(
-                        data.len() as u32
-                            + match *event {
-                                F0 => 1,
-                                _ => 0,
-                            },
-                    )
-                    .len()
+                    + event.len()
+                    + VLQ::new(data.len() as u32).len()
                     + data.len()
             }
             TrackChange => Tag::Track.binary().len(),
         }
     }
+    /// Returns delta time of message (TrackChange returns 0).
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use ghakuf::messages::{Message, MidiEvent};
+    ///
+    /// let message = Message::MidiEvent {
+    ///     delta_time: 120,
+    ///     event: MidiEvent::NoteOn { ch: 0, note: 0x3c, velocity: 0x7f },
+    /// };
+    /// assert_eq!(message.delta_time(), 120);
+    /// assert_eq!(Message::TrackChange.delta_time(), 0);
+    /// ```
+    pub fn delta_time(&self) -> u32 {
+        use messages::Message::*;
+        match *self {
+            MetaEvent { delta_time, .. }
+            | MidiEvent { delta_time, .. }
+            | SysExEvent { delta_time, .. } => delta_time,
+            TrackChange => 0,
+        }
+    }
+    /// Sets delta time of message (TrackChange is left as it is).
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use ghakuf::messages::{Message, MetaEvent};
+    ///
+    /// let mut message = Message::MetaEvent {
+    ///     delta_time: 0,
+    ///     event: MetaEvent::EndOfTrack,
+    ///     data: Vec::new(),
+    /// };
+    /// message.set_delta_time(480);
+    /// assert_eq!(message.delta_time(), 480);
+    /// ```
+    pub fn set_delta_time(&mut self, delta_time: u32) -> &mut Message {
+        use messages::Message::*;
+        match *self {
+            MetaEvent {
+                delta_time: ref mut old,
+                ..
+            }
+            | MidiEvent {
+                delta_time: ref mut old,
+                ..
+            }
+            | SysExEvent {
+                delta_time: ref mut old,
+                ..
+            } => *old = delta_time,
+            TrackChange => (),
+        }
+        self
+    }
+    /// Decodes text of text meta event by encoding (detects it when encoding is None).
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use ghakuf::encoding::Encoding;
+    /// use ghakuf::messages::{Message, MetaEvent};
+    ///
+    /// let message = Message::MetaEvent {
+    ///     delta_time: 0,
+    ///     event: MetaEvent::Lyric,
+    ///     data: vec![0x82, 0xa9, 0x82, 0xa6, 0x82, 0xe9],
+    /// };
+    /// assert_eq!(message.text(None).unwrap(), "かえる");
+    /// assert!(message.text(Some(Encoding::Utf8)).is_err());
+    /// ```
+    pub fn text(&self, encoding: Option<Encoding>) -> Result<String, EncodingError> {
+        match *self {
+            Message::MetaEvent {
+                ref event,
+                ref data,
+                ..
+            } if event.is_text() => encoding
+                .unwrap_or_else(|| Encoding::detect(data))
+                .decode(data),
+            _ => Err(EncodingError::NotText),
+        }
+    }
+    /// Builds text meta event encoding text.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use ghakuf::encoding::{Encoding, EncodingError};
+    /// use ghakuf::messages::{Message, MetaEvent};
+    ///
+    /// let message = Message::text_meta_event(0, MetaEvent::CopyrightNotice, "© 2019", Encoding::Latin1);
+    /// assert_eq!(
+    ///     message.unwrap(),
+    ///     Message::MetaEvent {
+    ///         delta_time: 0,
+    ///         event: MetaEvent::CopyrightNotice,
+    ///         data: b"\xa9 2019".to_vec(),
+    ///     }
+    /// );
+    /// assert_eq!(
+    ///     Message::text_meta_event(0, MetaEvent::SetTempo, "120", Encoding::Utf8),
+    ///     Err(EncodingError::NotText)
+    /// );
+    /// ```
+    pub fn text_meta_event(
+        delta_time: u32,
+        event: MetaEvent,
+        text: &str,
+        encoding: Encoding,
+    ) -> Result<Message, EncodingError> {
+        if !event.is_text() {
+            return Err(EncodingError::NotText);
+        }
+        Ok(Message::MetaEvent {
+            delta_time,
+            event,
+            data: encoding.encode(text)?,
+        })
+    }
+    /// Returns manufacturer ID and the rest of data of sequencer specific meta event.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use ghakuf::messages::{Message, MetaEvent};
+    /// use ghakuf::sysex::ManufacturerId;
+    ///
+    /// let message = Message::MetaEvent {
+    ///     delta_time: 0,
+    ///     event: MetaEvent::SequencerSpecificMetaEvent,
+    ///     data: vec![0x43, 0x7b, 0x00, 0x58],
+    /// };
+    /// assert_eq!(
+    ///     message.sequencer_specific(),
+    ///     Some((ManufacturerId::Standard(0x43), &[0x7b, 0x00, 0x58][..]))
+    /// );
+    /// ```
+    pub fn sequencer_specific(&self) -> Option<(ManufacturerId, &[u8])> {
+        match *self {
+            Message::MetaEvent {
+                event: MetaEvent::SequencerSpecificMetaEvent,
+                ref data,
+                ..
+            } => ManufacturerId::from_data(data),
+            _ => None,
+        }
+    }
+    /// Builds sequencer specific meta event with manufacturer ID and data.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use ghakuf::messages::{Message, MetaEvent};
+    /// use ghakuf::sysex::ManufacturerId;
+    ///
+    /// let message = Message::sequencer_specific_meta_event(0, ManufacturerId::Extended(0x00, 0x7f), &[0x01]);
+    /// assert_eq!(
+    ///     message,
+    ///     Message::MetaEvent {
+    ///         delta_time: 0,
+    ///         event: MetaEvent::SequencerSpecificMetaEvent,
+    ///         data: vec![0x00, 0x00, 0x7f, 0x01],
+    ///     }
+    /// );
+    /// ```
+    pub fn sequencer_specific_meta_event(
+        delta_time: u32,
+        manufacturer: ManufacturerId,
+        data: &[u8],
+    ) -> Message {
+        let mut binary = manufacturer.binary();
+        binary.extend_from_slice(data);
+        Message::MetaEvent {
+            delta_time,
+            event: MetaEvent::SequencerSpecificMetaEvent,
+            data: binary,
+        }
+    }
+    /// Builds TextEvent meta event encoding text.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use ghakuf::encoding::Encoding;
+    /// use ghakuf::messages::Message;
+    ///
+    /// let message = Message::text_event(0, "@KMIDI KARAOKE FILE", Encoding::Utf8).unwrap();
+    /// assert_eq!(message.text(None).unwrap(), "@KMIDI KARAOKE FILE");
+    /// ```
+    pub fn text_event(
+        delta_time: u32,
+        text: &str,
+        encoding: Encoding,
+    ) -> Result<Message, EncodingError> {
+        Message::text_meta_event(delta_time, MetaEvent::TextEvent, text, encoding)
+    }
+    /// Builds Lyric meta event encoding text.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use ghakuf::encoding::Encoding;
+    /// use ghakuf::messages::Message;
+    ///
+    /// let message = Message::lyric(240, "かえる", Encoding::ShiftJis).unwrap();
+    /// assert_eq!(message.len(), 11);
+    /// ```
+    pub fn lyric(
+        delta_time: u32,
+        text: &str,
+        encoding: Encoding,
+    ) -> Result<Message, EncodingError> {
+        Message::text_meta_event(delta_time, MetaEvent::Lyric, text, encoding)
+    }
+    /// Builds SequenceOrTrackName meta event encoding text.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use ghakuf::encoding::Encoding;
+    /// use ghakuf::messages::Message;
+    ///
+    /// let message = Message::track_name(0, "Café", Encoding::Latin1).unwrap();
+    /// assert_eq!(message.text(Some(Encoding::Latin1)).unwrap(), "Café");
+    /// ```
+    pub fn track_name(
+        delta_time: u32,
+        text: &str,
+        encoding: Encoding,
+    ) -> Result<Message, EncodingError> {
+        Message::text_meta_event(delta_time, MetaEvent::SequenceOrTrackName, text, encoding)
+    }
+    /// Builds InstrumentName meta event encoding text.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use ghakuf::encoding::Encoding;
+    /// use ghakuf::messages::Message;
+    ///
+    /// let message = Message::instrument_name(0, "Piano", Encoding::Utf8).unwrap();
+    /// assert_eq!(message.text(None).unwrap(), "Piano");
+    /// ```
+    pub fn instrument_name(
+        delta_time: u32,
+        text: &str,
+        encoding: Encoding,
+    ) -> Result<Message, EncodingError> {
+        Message::text_meta_event(delta_time, MetaEvent::InstrumentName, text, encoding)
+    }
+    /// Builds Marker meta event encoding text.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use ghakuf::encoding::Encoding;
+    /// use ghakuf::messages::Message;
+    ///
+    /// let message = Message::marker(0, "サビ", Encoding::ShiftJis).unwrap();
+    /// assert_eq!(message.text(None).unwrap(), "サビ");
+    /// ```
+    pub fn marker(
+        delta_time: u32,
+        text: &str,
+        encoding: Encoding,
+    ) -> Result<Message, EncodingError> {
+        Message::text_meta_event(delta_time, MetaEvent::Marker, text, encoding)
+    }
+    /// Builds CuePoint meta event encoding text.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use ghakuf::encoding::Encoding;
+    /// use ghakuf::messages::Message;
+    ///
+    /// let message = Message::cue_point(0, "Door slams", Encoding::Utf8).unwrap();
+    /// assert_eq!(message.text(None).unwrap(), "Door slams");
+    /// ```
+    pub fn cue_point(
+        delta_time: u32,
+        text: &str,
+        encoding: Encoding,
+    ) -> Result<Message, EncodingError> {
+        Message::text_meta_event(delta_time, MetaEvent::CuePoint, text, encoding)
+    }
 }
 impl fmt::Display for Message {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
@@ -243,6 +525,8 @@ impl fmt::Display for Message {
 /// assert_eq!(event.binary(), [0xff, 0x51]);
 /// ```
 #[derive(PartialEq, Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "serde", serde(tag = "type"))]
 pub enum MetaEvent {
     SequenceNumber,
     TextEvent,
@@ -253,6 +537,7 @@ pub enum MetaEvent {
     Marker,
     CuePoint,
     MIDIChannelPrefix,
+    MidiPort,
     EndOfTrack,
     SetTempo,
     SMTPEOffset,
@@ -283,6 +568,7 @@ impl MetaEvent {
             0x06 => MetaEvent::Marker,
             0x07 => MetaEvent::CuePoint,
             0x20 => MetaEvent::MIDIChannelPrefix,
+            0x21 => MetaEvent::MidiPort,
             0x2F => MetaEvent::EndOfTrack,
             0x51 => MetaEvent::SetTempo,
             0x54 => MetaEvent::SMTPEOffset,
@@ -292,6 +578,25 @@ impl MetaEvent {
             _ => MetaEvent::Unknown { event_type },
         }
     }
+    /// Returns whether MetaEvent carries text (0x01-0x0f) or not.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use ghakuf::messages::MetaEvent;
+    ///
+    /// assert!(MetaEvent::Lyric.is_text());
+    /// assert!(!MetaEvent::SetTempo.is_text());
+    /// ```
+    pub fn is_text(&self) -> bool {
+        use messages::MetaEvent::*;
+        match *self {
+            TextEvent | CopyrightNotice | SequenceOrTrackName | InstrumentName | Lyric | Marker
+            | CuePoint => true,
+            Unknown { event_type } => (0x01..=0x0f).contains(&event_type),
+            _ => false,
+        }
+    }
 }
 impl MessageTool for MetaEvent {
     fn binary(&self) -> Vec<u8> {
@@ -308,6 +613,7 @@ impl MessageTool for MetaEvent {
                 Marker => 0x06,
                 CuePoint => 0x07,
                 MIDIChannelPrefix => 0x20,
+                MidiPort => 0x21,
                 EndOfTrack => 0x2F,
                 SetTempo => 0x51,
                 SMTPEOffset => 0x54,
@@ -338,6 +644,7 @@ impl fmt::Display for MetaEvent {
             Marker => write!(f, "Marker"),
             CuePoint => write!(f, "CuePoint"),
             MIDIChannelPrefix => write!(f, "MIDIChannelPrefix"),
+            MidiPort => write!(f, "MidiPort"),
             EndOfTrack => write!(f, "EndOfTrack"),
             SetTempo => write!(f, "SetTempo"),
             SMTPEOffset => write!(f, "SMTPEOffset"),
@@ -359,7 +666,28 @@ impl fmt::Display for MetaEvent {
 /// let event: MidiEvent = MidiEvent::NoteOff { ch: 0x04, note: 0x02, velocity: 0x00 };
 /// assert_eq!(event.binary(), [0x84, 0x02, 0x00]);
 /// ```
+///
+/// Alternate format (`{:#}`) shows General MIDI names of program, controller and percussion key.
+///
+/// ```
+/// use ghakuf::messages::MidiEvent;
+///
+/// assert_eq!(
+///     format!("{:#}", MidiEvent::ProgramChange { ch: 0, program: 40 }),
+///     "ProgramChange ch: 0, program: 40 (Violin)"
+/// );
+/// assert_eq!(
+///     format!("{:#}", MidiEvent::NoteOn { ch: 0, note: 61, velocity: 100 }),
+///     "NoteOn ch: 0, note: 61 (C#4), velocity: 100"
+/// );
+/// assert_eq!(
+///     format!("{:#}", MidiEvent::NoteOn { ch: 9, note: 38, velocity: 100 }),
+///     "NoteOn ch: 9, note: 38 (Acoustic Snare), velocity: 100"
+/// );
+/// ```
 #[derive(PartialEq, Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "serde", serde(tag = "type"))]
 pub enum MidiEvent {
     NoteOff { ch: u8, note: u8, velocity: u8 },
     NoteOn { ch: u8, note: u8, velocity: u8 },
@@ -500,11 +828,11 @@ impl MidiEventBuilder {
                 pressure: self.data[0],
             },
             0xe0 => {
-                let lsb = self.data[0] as u16;
-                let msb = (self.data[1] as u16) << 8;
+                let lsb = (self.data[0] & 0x7f) as u16;
+                let msb = ((self.data[1] & 0x7f) as u16) << 7;
                 MidiEvent::PitchBendChange {
                     ch: self.status & 0x0f,
-                    data: (msb & lsb) as i16 - 8192,
+                    data: (msb | lsb) as i16 - 8192,
                 }
             }
             _ => MidiEvent::Unknown {
@@ -526,11 +854,11 @@ impl MessageTool for MidiEvent {
             ProgramChange { program, .. } => vec![self.status_byte(), program],
             ChannelPressure { pressure, .. } => vec![self.status_byte(), pressure],
             MidiEvent::PitchBendChange { data, .. } => {
-                let pitch_bend: u16 = (data + 8192) as u16;
+                let pitch_bend = (data as i32 + 8192).clamp(0, 0x3fff) as u16;
                 vec![
                     self.status_byte(),
-                    (pitch_bend >> 7) as u8,
                     (pitch_bend & 0b1111111) as u8,
+                    (pitch_bend >> 7) as u8,
                 ]
             }
             MidiEvent::Unknown { .. } => vec![self.status_byte()],
@@ -565,6 +893,57 @@ impl MessageTool for MidiEvent {
 impl fmt::Display for MidiEvent {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         use messages::MidiEvent::*;
+        if f.alternate() {
+            return match *self {
+                NoteOff { ch, note, velocity } => write!(
+                    f,
+                    "NoteOff ch: {}, note: {}, velocity: {}",
+                    ch,
+                    key_name(ch, note),
+                    velocity
+                ),
+                NoteOn { ch, note, velocity } => write!(
+                    f,
+                    "NoteOn ch: {}, note: {}, velocity: {}",
+                    ch,
+                    key_name(ch, note),
+                    velocity
+                ),
+                PolyphonicKeyPressure { ch, note, velocity } => write!(
+                    f,
+                    "PolyphonicKeyPressure ch: {}, note: {}, pressure: {}",
+                    ch,
+                    key_name(ch, note),
+                    velocity
+                ),
+                ControlChange { ch, control, data } => match gm::controller_name(control) {
+                    Some(name) => write!(
+                        f,
+                        "ControlChange ch: {}, control: {} ({}), data: {}",
+                        ch, control, name, data
+                    ),
+                    None => write!(
+                        f,
+                        "ControlChange ch: {}, control: {}, data: {}",
+                        ch, control, data
+                    ),
+                },
+                ProgramChange { ch, program } => write!(
+                    f,
+                    "ProgramChange ch: {}, program: {} ({})",
+                    ch,
+                    program,
+                    gm::program_name(program)
+                ),
+                ChannelPressure { ch, pressure } => {
+                    write!(f, "ChannelPressure ch: {}, pressure: {}", ch, pressure)
+                }
+                PitchBendChange { ch, data } => {
+                    write!(f, "PitchBendChange ch: {}, bend: {:+}", ch, data)
+                }
+                Unknown { ch } => write!(f, "Unknown ch: {}", ch),
+            };
+        }
         match *self {
             NoteOff { ch, note, velocity } => write!(
                 f,
@@ -600,6 +979,309 @@ impl fmt::Display for MidiEvent {
     }
 }
 
+fn key_name(ch: u8, note: u8) -> String {
+    match gm::percussion_name(note) {
+        Some(name) if ch == gm::PERCUSSION_CHANNEL => format!("{} ({})", note, name),
+        _ => format!("{} ({})", note, Note::new(note)),
+    }
+}
+
+/// An enum representing octave number convention of middle C (key 60).
+///
+/// Scientific pitch notation uses C4, while some vendors (e.g. Yamaha) use C3 and others use C5.
+#[derive(PartialEq, Clone, Copy, Debug)]
+pub enum MiddleC {
+    C3,
+    C4,
+    C5,
+}
+impl MiddleC {
+    fn octave_offset(&self) -> i32 {
+        match *self {
+            MiddleC::C3 => -2,
+            MiddleC::C4 => -1,
+            MiddleC::C5 => 0,
+        }
+    }
+}
+
+/// An enum representing how black keys are spelled.
+///
+/// # Examples
+///
+/// ```
+/// use ghakuf::messages::Spelling;
+///
+/// // KeySignature meta data of B flat major: [sf, mi] = [-2, 0]
+/// let spelling = Spelling::from_key_signature(&[0xfe, 0x00]);
+/// assert_eq!(spelling, Spelling::Key { sf: -2, minor: false });
+/// ```
+#[derive(PartialEq, Clone, Copy, Debug)]
+pub enum Spelling {
+    /// Spells black keys with sharps (C#, D#, F#, G#, A#)
+    Sharps,
+    /// Spells black keys with flats (Db, Eb, Gb, Ab, Bb)
+    Flats,
+    /// Spells keys diatonically in key signature (`sf`: number of sharps if positive, flats if negative)
+    Key { sf: i8, minor: bool },
+}
+impl Spelling {
+    /// Builds Spelling from data of KeySignature meta event. Invalid data results in `Spelling::Sharps`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use ghakuf::messages::Spelling;
+    ///
+    /// assert_eq!(
+    ///     Spelling::from_key_signature(&[0x03, 0x01]),
+    ///     Spelling::Key { sf: 3, minor: true }
+    /// );
+    /// assert_eq!(Spelling::from_key_signature(&[]), Spelling::Sharps);
+    /// ```
+    pub fn from_key_signature(data: &[u8]) -> Spelling {
+        match data {
+            [sf, mi] if (*sf as i8).abs() <= 7 && *mi <= 1 => Spelling::Key {
+                sf: *sf as i8,
+                minor: *mi == 1,
+            },
+            _ => {
+                warn!("invalid key signature data has found: {:?}", data);
+                Spelling::Sharps
+            }
+        }
+    }
+    // Returns (letter index from C, accidental) of pitch class.
+    fn spell(&self, pitch_class: i32) -> (i32, i32) {
+        const SHARPS: [(i32, i32); 12] = [
+            (0, 0),
+            (0, 1),
+            (1, 0),
+            (1, 1),
+            (2, 0),
+            (3, 0),
+            (3, 1),
+            (4, 0),
+            (4, 1),
+            (5, 0),
+            (5, 1),
+            (6, 0),
+        ];
+        const FLATS: [(i32, i32); 12] = [
+            (0, 0),
+            (1, -1),
+            (1, 0),
+            (2, -1),
+            (2, 0),
+            (3, 0),
+            (4, -1),
+            (4, 0),
+            (5, -1),
+            (5, 0),
+            (6, -1),
+            (6, 0),
+        ];
+        match *self {
+            Spelling::Sharps => SHARPS[pitch_class as usize],
+            Spelling::Flats => FLATS[pitch_class as usize],
+            Spelling::Key { sf, minor } => {
+                let sf = sf.clamp(-7, 7) as i32;
+                let accidental = |letter: i32| -> i32 {
+                    // position of letter in order of sharps (F C G D A E B)
+                    let order = (letter * 2 + 1) % 7;
+                    if sf > 0 && order < sf {
+                        1
+                    } else if sf < 0 && 6 - order < -sf {
+                        -1
+                    } else {
+                        0
+                    }
+                };
+                for letter in 0..7 {
+                    let accidental = accidental(letter);
+                    if (NATURALS[letter as usize] + accidental + 12) % 12 == pitch_class {
+                        return (letter, accidental);
+                    }
+                }
+                if minor {
+                    // raised seventh degree of harmonic minor
+                    let tonic_letter = ((sf * 4 % 7 + 7) % 7 + 5) % 7;
+                    let letter = (tonic_letter + 6) % 7;
+                    let accidental = accidental(letter) + 1;
+                    if (NATURALS[letter as usize] + accidental + 12) % 12 == pitch_class {
+                        return (letter, accidental);
+                    }
+                }
+                if sf < 0 {
+                    FLATS[pitch_class as usize]
+                } else {
+                    SHARPS[pitch_class as usize]
+                }
+            }
+        }
+    }
+}
+
+const NATURALS: [i32; 7] = [0, 2, 4, 5, 7, 9, 11];
+const LETTERS: [char; 7] = ['C', 'D', 'E', 'F', 'G', 'A', 'B'];
+
+/// A struct representing MIDI key number (0-127) with scientific pitch name.
+///
+/// # Examples
+///
+/// ```
+/// use ghakuf::messages::{MiddleC, Note, Spelling};
+///
+/// let note: Note = "C#4".parse().unwrap();
+/// assert_eq!(note.key(), 61);
+/// assert_eq!(note.name(Spelling::Flats, MiddleC::C4), "Db4");
+/// assert_eq!(note.name(Spelling::Sharps, MiddleC::C3), "C#3");
+/// assert_eq!(Note::parse("Bb-1", MiddleC::C4).unwrap().key(), 10);
+/// ```
+#[derive(PartialEq, Eq, PartialOrd, Ord, Hash, Clone, Copy, Debug)]
+pub struct Note {
+    key: u8,
+}
+impl Note {
+    /// Builds Note from MIDI key number.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use ghakuf::messages::Note;
+    ///
+    /// let note = Note::new(60);
+    /// assert_eq!(format!("{}", note), "C4");
+    /// ```
+    pub fn new(key: u8) -> Note {
+        Note { key: key & 0x7f }
+    }
+    /// Returns MIDI key number.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use ghakuf::messages::Note;
+    ///
+    /// assert_eq!(Note::new(69).key(), 69);
+    /// ```
+    pub fn key(&self) -> u8 {
+        self.key
+    }
+    /// Parses pitch name (letter, any number of `#`/`b`, and octave) with middle C convention.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use ghakuf::messages::{MiddleC, Note, NoteError};
+    ///
+    /// assert_eq!(Note::parse("A4", MiddleC::C4).unwrap().key(), 69);
+    /// assert_eq!(Note::parse("A3", MiddleC::C3).unwrap().key(), 69);
+    /// assert_eq!(Note::parse("B#3", MiddleC::C4).unwrap().key(), 60);
+    /// assert!(Note::parse("H2", MiddleC::C4).is_err());
+    /// assert_eq!(
+    ///     Note::parse("G#9", MiddleC::C4),
+    ///     Err(NoteError::OutOfRange { name: "G#9".to_string() })
+    /// );
+    /// assert_eq!(
+    ///     Note::parse("C300000000", MiddleC::C4),
+    ///     Err(NoteError::OutOfRange { name: "C300000000".to_string() })
+    /// );
+    /// ```
+    pub fn parse(name: &str, middle_c: MiddleC) -> Result<Note, NoteError> {
+        let invalid = || NoteError::InvalidName {
+            name: name.to_string(),
+        };
+        let mut chars = name.trim().chars().peekable();
+        let letter = match chars.next() {
+            Some(letter) => LETTERS
+                .iter()
+                .position(|&c| c == letter.to_ascii_uppercase())
+                .ok_or_else(invalid)?,
+            None => return Err(invalid()),
+        };
+        let mut accidental = 0;
+        while let Some(&c) = chars.peek() {
+            match c {
+                '#' | '\u{266f}' => accidental += 1,
+                'b' | '\u{266d}' => accidental -= 1,
+                _ => break,
+            }
+            chars.next();
+        }
+        let octave: String = chars.collect();
+        let octave: i32 = octave.parse().map_err(|_| invalid())?;
+        let key = octave
+            .checked_sub(middle_c.octave_offset())
+            .and_then(|octave| octave.checked_mul(12))
+            .and_then(|key| key.checked_add(NATURALS[letter] + accidental));
+        match key {
+            Some(key) if (0..128).contains(&key) => Ok(Note::new(key as u8)),
+            _ => Err(NoteError::OutOfRange {
+                name: name.to_string(),
+            }),
+        }
+    }
+    /// Makes pitch name with spelling and middle C convention.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use ghakuf::messages::{MiddleC, Note, Spelling};
+    ///
+    /// // F sharp major spells F natural as E sharp.
+    /// let spelling = Spelling::Key { sf: 6, minor: false };
+    /// assert_eq!(Note::new(65).name(spelling, MiddleC::C4), "E#4");
+    /// // D minor spells its leading tone as C sharp.
+    /// let spelling = Spelling::Key { sf: -1, minor: true };
+    /// assert_eq!(Note::new(61).name(spelling, MiddleC::C4), "C#4");
+    /// assert_eq!(Note::new(70).name(spelling, MiddleC::C4), "Bb4");
+    /// ```
+    pub fn name(&self, spelling: Spelling, middle_c: MiddleC) -> String {
+        let key = self.key as i32;
+        let (letter, accidental) = spelling.spell(key % 12);
+        let octave = (key - accidental - NATURALS[letter as usize]).div_euclid(12)
+            + middle_c.octave_offset();
+        let accidental = if accidental < 0 {
+            "b".repeat(-accidental as usize)
+        } else {
+            "#".repeat(accidental as usize)
+        };
+        format!("{}{}{}", LETTERS[letter as usize], accidental, octave)
+    }
+}
+impl FromStr for Note {
+    type Err = NoteError;
+    fn from_str(name: &str) -> Result<Note, NoteError> {
+        Note::parse(name, MiddleC::C4)
+    }
+}
+impl fmt::Display for Note {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.name(Spelling::Sharps, MiddleC::C4))
+    }
+}
+
+/// An enum represents errors of pitch name parsing.
+#[derive(PartialEq, Clone, Debug)]
+pub enum NoteError {
+    /// Pitch name doesn't consist of letter, accidentals and octave.
+    InvalidName { name: String },
+    /// Pitch name is out of MIDI key range (0-127).
+    OutOfRange { name: String },
+}
+impl fmt::Display for NoteError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            NoteError::InvalidName { ref name } => write!(f, "Invalid pitch name: '{}'", name),
+            NoteError::OutOfRange { ref name } => {
+                write!(f, "Pitch name out of MIDI key range: '{}'", name)
+            }
+        }
+    }
+}
+impl error::Error for NoteError {}
+
 /// An enum representing System Exclusive event of SMF.
 ///
 /// # Examples
@@ -611,6 +1293,8 @@ impl fmt::Display for MidiEvent {
 /// assert_eq!(event.status_byte(), 0xf0);
 /// ```
 #[derive(PartialEq, Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "serde", serde(tag = "type"))]
 pub enum SysExEvent {
     F0,
     F7,
@@ -661,3 +1345,58 @@ impl fmt::Display for SysExEvent {
         }
     }
 }
+#[cfg(test)]
+mod midi_event_tests {
+    use messages::*;
+    fn rebuild(event: &MidiEvent) -> MidiEvent {
+        let binary = event.binary();
+        let mut builder = MidiEventBuilder::new(binary[0]);
+        for data in &binary[1..] {
+            builder.push(*data);
+        }
+        builder.build()
+    }
+    #[test]
+    fn pitch_bend_lsb_first() {
+        let event = MidiEvent::PitchBendChange { ch: 2, data: 0 };
+        assert_eq!(event.binary(), [0xe2, 0x00, 0x40]);
+        let event = MidiEvent::PitchBendChange { ch: 0, data: -8192 };
+        assert_eq!(event.binary(), [0xe0, 0x00, 0x00]);
+        let event = MidiEvent::PitchBendChange { ch: 0, data: 8191 };
+        assert_eq!(event.binary(), [0xe0, 0x7f, 0x7f]);
+    }
+    #[test]
+    fn pitch_bend_round_trip() {
+        for data in &[-8192, -1, 0, 1, 100, 8191] {
+            let event = MidiEvent::PitchBendChange { ch: 5, data: *data };
+            assert_eq!(rebuild(&event), event);
+        }
+    }
+}
+#[cfg(test)]
+mod sys_ex_event_tests {
+    use messages::*;
+    #[test]
+    fn binary_excludes_status_from_data() {
+        let message = Message::SysExEvent {
+            delta_time: 0,
+            event: SysExEvent::F0,
+            data: vec![0x7e, 0x7f, 0x09, 0x01, 0xf7],
+        };
+        assert_eq!(
+            message.binary(),
+            [0x00, 0xf0, 0x05, 0x7e, 0x7f, 0x09, 0x01, 0xf7]
+        );
+        assert_eq!(message.len(), message.binary().len());
+    }
+    #[test]
+    fn escape_len() {
+        let message = Message::SysExEvent {
+            delta_time: 200,
+            event: SysExEvent::F7,
+            data: vec![0xf3, 0x01],
+        };
+        assert_eq!(message.binary(), [0x81, 0x48, 0xf7, 0x02, 0xf3, 0x01]);
+        assert_eq!(message.len(), message.binary().len());
+    }
+}
@@ -0,0 +1,922 @@
+use byteorder::{BigEndian, ByteOrder};
+use formats::VLQ;
+use messages::*;
+use smf::Smf;
+use std::str::FromStr;
+use std::{error, fmt, str};
+use text::{Encoding, Text};
+use transform::absolute;
+
+/// Writes SMF into CSV of [midicsv](https://www.fourmilab.ch/webtools/midicsv/) format.
+///
+/// Each line is a record of `track, time, type, parameters...` with absolute time in ticks.
+/// Meta events whose data don't fit their record (e.g. tempo of 2 bytes) are written as
+/// `Unknown_meta_event` with raw data bytes, so they are read back into the same messages.
+/// `Unknown_midi_event_c` and `Unknown_sysex_event` records are only used by this crate for unknown
+/// MIDI and system exclusive events.
+///
+/// Running status isn't a part of CSV records, so use `text_to_csv` to keep it.
+///
+/// # Examples
+///
+/// ```
+/// use ghakuf::messages::*;
+/// use ghakuf::midicsv;
+/// use ghakuf::smf::Smf;
+///
+/// let mut smf = Smf::new(0, 480);
+/// smf.tracks.push(vec![
+///     Message::MetaEvent { delta_time: 0, event: MetaEvent::SetTempo, data: vec![0x07, 0xa1, 0x20] },
+///     Message::MidiEvent { delta_time: 0, event: MidiEvent::NoteOn { ch: 0, note: 60, velocity: 100 } },
+///     Message::MidiEvent { delta_time: 480, event: MidiEvent::NoteOff { ch: 0, note: 60, velocity: 0 } },
+///     Message::MetaEvent { delta_time: 0, event: MetaEvent::EndOfTrack, data: Vec::new() },
+/// ]);
+/// assert_eq!(
+///     midicsv::to_csv(&smf),
+///     "0, 0, Header, 0, 1, 480\n\
+///      1, 0, Start_track\n\
+///      1, 0, Tempo, 500000\n\
+///      1, 0, Note_on_c, 0, 60, 100\n\
+///      1, 480, Note_off_c, 0, 60, 0\n\
+///      1, 480, End_track\n\
+///      0, 0, End_of_file\n"
+/// );
+/// ```
+pub fn to_csv(smf: &Smf) -> String {
+    write_csv(smf, None)
+}
+
+/// Writes Text into CSV of [midicsv](https://www.fourmilab.ch/webtools/midicsv/) format, with its
+/// encoding in `# ghakuf` comment lines.
+///
+/// The comment line before `Header` record has the default running status, and the one before an
+/// event record has the encoding of the event, in the same words as event lines of Text. midicsv
+/// skips them as comments, and `text_from_csv` reads them back, so SMF goes through CSV into the
+/// same bytes.
+///
+/// # Examples
+///
+/// ```
+/// use ghakuf::midicsv;
+/// use ghakuf::text::Text;
+///
+/// let data = [
+///     0x4d, 0x54, 0x68, 0x64, 0x00, 0x00, 0x00, 0x06, 0x00, 0x00, 0x00, 0x01, 0x01, 0xe0,
+///     0x4d, 0x54, 0x72, 0x6b, 0x00, 0x00, 0x00, 0x0f, 0x00, 0x90, 0x3c, 0x64, 0x00, 0x3e,
+///     0x64, 0x00, 0x90, 0x40, 0x64, 0x00, 0xff, 0x2f, 0x00,
+/// ];
+/// let csv = midicsv::text_to_csv(&Text::from_bytes(&data).unwrap());
+/// assert_eq!(
+///     csv,
+///     "# ghakuf running_status=on\n\
+///      0, 0, Header, 0, 1, 480\n\
+///      1, 0, Start_track\n\
+///      1, 0, Note_on_c, 0, 60, 100\n\
+///      1, 0, Note_on_c, 0, 62, 100\n\
+///      ## ghakuf running_status=off\n\
+///      1, 0, Note_on_c, 0, 64, 100\n\
+///      1, 0, End_track\n\
+///      0, 0, End_of_file\n"
+/// );
+/// assert_eq!(midicsv::text_from_csv(&csv).unwrap().to_bytes(), &data[..]);
+/// ```
+pub fn text_to_csv(text: &Text) -> String {
+    write_csv(&text.smf, Some(text))
+}
+
+fn write_csv(smf: &Smf, text: Option<&Text>) -> String {
+    let mut csv = String::new();
+    if let Some(text) = text {
+        let running_status = if text.running_status { "on" } else { "off" };
+        csv.push_str(&format!("# ghakuf running_status={}\n", running_status));
+    }
+    push_record(
+        &mut csv,
+        0,
+        0,
+        "Header",
+        &[
+            smf.format.to_string(),
+            smf.tracks.len().to_string(),
+            smf.time_base.to_string(),
+        ],
+    );
+    for (i, track) in smf.tracks.iter().enumerate() {
+        push_record(&mut csv, i + 1, 0, "Start_track", &[]);
+        for (j, (time, message)) in absolute(track).into_iter().enumerate() {
+            if let Some(encoding) = text.and_then(|text| text.encodings.get(&(i, j))) {
+                csv.push_str(&format!("# ghakuf {}\n", encoding));
+            }
+            if let Some((record, parameters)) = record(&message) {
+                push_record(&mut csv, i + 1, time, record, &parameters);
+            }
+        }
+    }
+    push_record(&mut csv, 0, 0, "End_of_file", &[]);
+    csv
+}
+
+/// Builds SMF from CSV of [midicsv](https://www.fourmilab.ch/webtools/midicsv/) format.
+///
+/// Record types are case insensitive, and lines starting with `#` or `;` are comments.
+///
+/// # Examples
+///
+/// ```
+/// use ghakuf::messages::*;
+/// use ghakuf::midicsv::{self, CsvError};
+///
+/// let smf = midicsv::from_csv(
+///     "0, 0, Header, 1, 1, 96\n\
+///      1, 0, Start_track\n\
+///      1, 0, Title_t, \"Piano \"\"1\"\"\"\n\
+///      1, 0, Program_c, 0, 0\n\
+///      1, 96, Pitch_bend_c, 0, 8192\n\
+///      1, 96, End_track\n\
+///      0, 0, End_of_file\n",
+/// )
+/// .unwrap();
+/// assert_eq!(
+///     smf.tracks[0][0],
+///     Message::MetaEvent { delta_time: 0, event: MetaEvent::SequenceOrTrackName, data: b"Piano \"1\"".to_vec() }
+/// );
+/// assert_eq!(
+///     smf.tracks[0][2],
+///     Message::MidiEvent { delta_time: 96, event: MidiEvent::PitchBendChange { ch: 0, data: 0 } }
+/// );
+/// assert_eq!(
+///     midicsv::from_csv("0, 0, Header, 1, 1, 96\n1, 0, Start_track\n1, 0, Note_on_c, 0, 60\n"),
+///     Err(CsvError::InvalidLine { line: 3, reason: "missing parameter".to_string() })
+/// );
+/// ```
+pub fn from_csv(csv: &str) -> Result<Smf, CsvError> {
+    text_from_csv(csv).map(|text| text.smf)
+}
+
+/// Builds Text from CSV of [midicsv](https://www.fourmilab.ch/webtools/midicsv/) format, with
+/// encoding in `# ghakuf` comment lines written by `text_to_csv`.
+///
+/// Running status is off if CSV doesn't have the comment line before `Header` record.
+///
+/// # Examples
+///
+/// ```
+/// use ghakuf::midicsv;
+/// use ghakuf::text::Encoding;
+///
+/// let text = midicsv::text_from_csv(
+///     "# ghakuf running_status=on\n\
+///      0, 0, Header, 0, 1, 96\n\
+///      1, 0, Start_track\n\
+///      ## ghakuf delta_time_bytes=2\n\
+///      1, 96, End_track\n\
+///      0, 0, End_of_file\n",
+/// )
+/// .unwrap();
+/// assert!(text.running_status);
+/// assert_eq!(text.encodings[&(0, 0)], Encoding { running_status: None, delta_time_bytes: Some(2) });
+/// assert!(midicsv::text_from_csv("# ghakuf delta_time_bytes=2\n0, 0, Header, 0, 0, 96\n").is_err());
+/// ```
+pub fn text_from_csv(csv: &str) -> Result<Text, CsvError> {
+    let mut text: Option<Text> = None;
+    let mut running_status = false;
+    let mut encoding: Option<Encoding> = None;
+    let mut tracks = 0;
+    let mut last_time = 0;
+    let mut end_of_file = false;
+    for (i, line) in csv.lines().enumerate() {
+        let line_number = i + 1;
+        let invalid = |reason: String| CsvError::InvalidLine {
+            line: line_number,
+            reason,
+        };
+        let trimmed = line.trim();
+        if let Some(words) = trimmed.strip_prefix("# ghakuf ") {
+            if encoding.is_some() {
+                return Err(invalid("duplicated encoding".to_string()));
+            }
+            encoding = Some(encoding_comment(words).map_err(&invalid)?);
+            continue;
+        }
+        if trimmed.is_empty() || trimmed.starts_with('#') || trimmed.starts_with(';') {
+            continue;
+        }
+        if end_of_file {
+            return Err(invalid("record after End_of_file".to_string()));
+        }
+        let mut fields = Fields::new(line).map_err(&invalid)?;
+        let track: usize = fields.number().map_err(&invalid)?;
+        let time: u64 = fields.number().map_err(&invalid)?;
+        let record = fields.text().map_err(&invalid)?.to_lowercase();
+        let encoding = encoding.take();
+        if record == "header" {
+            if text.is_some() {
+                return Err(invalid("duplicated Header".to_string()));
+            }
+            let format = fields.number().map_err(&invalid)?;
+            tracks = fields.number().map_err(&invalid)?;
+            let time_base = fields.number().map_err(&invalid)?;
+            fields.finish().map_err(&invalid)?;
+            let mut header = Text::new(Smf::new(format, time_base));
+            match encoding {
+                Some(Encoding {
+                    running_status: Some(running_status),
+                    delta_time_bytes: None,
+                }) => header.running_status = running_status,
+                Some(_) => return Err(invalid("Header needs only running_status".to_string())),
+                None => (),
+            }
+            running_status = header.running_status;
+            text = Some(header);
+            continue;
+        }
+        let text = text.as_mut().ok_or(CsvError::MissingHeader)?;
+        let smf = &mut text.smf;
+        if encoding.is_some() && (record == "start_track" || record == "end_of_file") {
+            return Err(invalid(format!("encoding of {} record", record)));
+        }
+        match record.as_str() {
+            "start_track" => {
+                if track != smf.tracks.len() + 1 {
+                    return Err(invalid(format!("unexpected track number: {}", track)));
+                }
+                fields.finish().map_err(&invalid)?;
+                smf.tracks.push(Vec::new());
+                last_time = 0;
+            }
+            "end_of_file" => {
+                fields.finish().map_err(&invalid)?;
+                end_of_file = true;
+            }
+            _ => {
+                if track == 0 || track != smf.tracks.len() {
+                    return Err(invalid(format!("record outside track {}", track)));
+                }
+                if time < last_time || time - last_time > 0x0fff_ffff {
+                    return Err(invalid(format!("time goes back or jumps: {}", time)));
+                }
+                let mut message = message(&record, &mut fields).map_err(&invalid)?;
+                fields.finish().map_err(&invalid)?;
+                message.set_delta_time((time - last_time) as u32);
+                last_time = time;
+                if let Some(mut encoding) = encoding {
+                    let minimal = VLQ::new(message.delta_time()).len();
+                    match encoding.delta_time_bytes {
+                        Some(bytes) if bytes < minimal || bytes > 4 => {
+                            return Err(invalid(format!(
+                                "delta_time_bytes must be from {} to 4: {}",
+                                minimal, bytes
+                            )))
+                        }
+                        Some(bytes) if bytes == minimal => encoding.delta_time_bytes = None,
+                        _ => (),
+                    }
+                    if encoding.running_status == Some(running_status) {
+                        encoding.running_status = None;
+                    }
+                    if encoding != Encoding::default() {
+                        text.encodings
+                            .insert((track - 1, smf.tracks[track - 1].len()), encoding);
+                    }
+                }
+                smf.tracks[track - 1].push(message);
+            }
+        }
+    }
+    let text = text.ok_or(CsvError::MissingHeader)?;
+    if text.smf.tracks.len() != tracks {
+        return Err(CsvError::TrackCount {
+            expected: tracks,
+            found: text.smf.tracks.len(),
+        });
+    }
+    Ok(text)
+}
+
+// Parses words of `# ghakuf` comment line.
+fn encoding_comment(words: &str) -> Result<Encoding, String> {
+    let mut encoding = Encoding::default();
+    for word in words.split_whitespace() {
+        let mut pair = word.splitn(2, '=');
+        match (pair.next(), pair.next()) {
+            (Some("running_status"), Some("on")) => encoding.running_status = Some(true),
+            (Some("running_status"), Some("off")) => encoding.running_status = Some(false),
+            (Some("delta_time_bytes"), Some(bytes)) => {
+                encoding.delta_time_bytes = Some(
+                    bytes
+                        .parse()
+                        .map_err(|_| format!("invalid value of delta_time_bytes: {}", bytes))?,
+                )
+            }
+            _ => return Err(format!("unknown encoding: {}", word)),
+        }
+    }
+    Ok(encoding)
+}
+
+/// An enum represents errors of building SMF from CSV.
+#[derive(PartialEq, Clone, Debug)]
+pub enum CsvError {
+    /// CSV doesn't start with `Header` record.
+    MissingHeader,
+    /// Line can't be parsed.
+    InvalidLine { line: usize, reason: String },
+    /// Number of tracks in `Header` record doesn't match `Start_track` records.
+    TrackCount { expected: usize, found: usize },
+}
+impl fmt::Display for CsvError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        use midicsv::CsvError::*;
+        match *self {
+            MissingHeader => write!(f, "CSV doesn't start with Header record."),
+            InvalidLine { line, ref reason } => {
+                write!(f, "Invalid line {} has found: {}", line, reason)
+            }
+            TrackCount { expected, found } => write!(
+                f,
+                "Header has {} tracks, but {} tracks have found.",
+                expected, found
+            ),
+        }
+    }
+}
+impl error::Error for CsvError {}
+
+fn push_record(csv: &mut String, track: usize, time: u64, record: &str, parameters: &[String]) {
+    csv.push_str(&format!("{}, {}, {}", track, time, record));
+    for parameter in parameters {
+        csv.push_str(", ");
+        csv.push_str(parameter);
+    }
+    csv.push('\n');
+}
+
+fn record(message: &Message) -> Option<(&'static str, Vec<String>)> {
+    Some(match *message {
+        Message::MetaEvent {
+            ref event,
+            ref data,
+            ..
+        } => meta_record(event, data),
+        Message::MidiEvent { ref event, .. } => midi_record(event),
+        Message::SysExEvent {
+            ref event,
+            ref data,
+            ..
+        } => match *event {
+            SysExEvent::F0 => ("System_exclusive", with_length(data)),
+            SysExEvent::F7 => ("System_exclusive_packet", with_length(data)),
+            SysExEvent::Unknown { status } => {
+                let mut parameters = vec![status.to_string()];
+                parameters.extend(with_length(data));
+                ("Unknown_sysex_event", parameters)
+            }
+        },
+        Message::TrackChange => return None,
+    })
+}
+
+fn meta_record(event: &MetaEvent, data: &[u8]) -> (&'static str, Vec<String>) {
+    use messages::MetaEvent::*;
+    let numbers = || data.iter().map(|byte| byte.to_string()).collect();
+    match (event, data.len()) {
+        (&SequenceNumber, 2) => (
+            "Sequence_number",
+            vec![BigEndian::read_u16(data).to_string()],
+        ),
+        (&TextEvent, _) => ("Text_t", vec![quote(data)]),
+        (&CopyrightNotice, _) => ("Copyright_t", vec![quote(data)]),
+        (&SequenceOrTrackName, _) => ("Title_t", vec![quote(data)]),
+        (&InstrumentName, _) => ("Instrument_name_t", vec![quote(data)]),
+        (&Lyric, _) => ("Lyric_t", vec![quote(data)]),
+        (&Marker, _) => ("Marker_t", vec![quote(data)]),
+        (&CuePoint, _) => ("Cue_point_t", vec![quote(data)]),
+        (&MIDIChannelPrefix, 1) => ("Channel_prefix", numbers()),
+        (&MidiPort, 1) => ("MIDI_port", numbers()),
+        (&EndOfTrack, 0) => ("End_track", Vec::new()),
+        (&SetTempo, 3) => ("Tempo", vec![BigEndian::read_u24(data).to_string()]),
+        (&SMTPEOffset, 5) => ("SMPTE_offset", numbers()),
+        (&TimeSignature, 4) => ("Time_signature", numbers()),
+        (&KeySignature, 2) if data[1] <= 1 => (
+            "Key_signature",
+            vec![
+                (data[0] as i8).to_string(),
+                if data[1] == 0 {
+                    "\"major\""
+                } else {
+                    "\"minor\""
+                }
+                .to_string(),
+            ],
+        ),
+        (&SequencerSpecificMetaEvent, _) => ("Sequencer_specific", with_length(data)),
+        _ => {
+            let mut parameters = vec![event.binary()[1].to_string()];
+            parameters.extend(with_length(data));
+            ("Unknown_meta_event", parameters)
+        }
+    }
+}
+
+fn midi_record(event: &MidiEvent) -> (&'static str, Vec<String>) {
+    use messages::MidiEvent::*;
+    let numbers = |values: &[u8]| values.iter().map(|value| value.to_string()).collect();
+    match *event {
+        NoteOff { ch, note, velocity } => ("Note_off_c", numbers(&[ch, note, velocity])),
+        NoteOn { ch, note, velocity } => ("Note_on_c", numbers(&[ch, note, velocity])),
+        PolyphonicKeyPressure { ch, note, velocity } => {
+            ("Poly_aftertouch_c", numbers(&[ch, note, velocity]))
+        }
+        ControlChange { ch, control, data } => ("Control_c", numbers(&[ch, control, data])),
+        ProgramChange { ch, program } => ("Program_c", numbers(&[ch, program])),
+        ChannelPressure { ch, pressure } => ("Channel_aftertouch_c", numbers(&[ch, pressure])),
+        PitchBendChange { ch, data } => (
+            "Pitch_bend_c",
+            vec![ch.to_string(), (data as i32 + 8192).to_string()],
+        ),
+        Unknown { ch } => ("Unknown_midi_event_c", vec![ch.to_string()]),
+    }
+}
+
+fn with_length(data: &[u8]) -> Vec<String> {
+    let mut parameters = vec![data.len().to_string()];
+    parameters.extend(data.iter().map(|byte| byte.to_string()));
+    parameters
+}
+
+fn message(record: &str, fields: &mut Fields) -> Result<Message, String> {
+    let meta = |event: MetaEvent, data: Vec<u8>| Message::MetaEvent {
+        delta_time: 0,
+        event,
+        data,
+    };
+    let midi = |event: MidiEvent| Message::MidiEvent {
+        delta_time: 0,
+        event,
+    };
+    let sys_ex = |event: SysExEvent, data: Vec<u8>| Message::SysExEvent {
+        delta_time: 0,
+        event,
+        data,
+    };
+    Ok(match record {
+        "sequence_number" => {
+            let mut data = vec![0; 2];
+            BigEndian::write_u16(&mut data, fields.number()?);
+            meta(MetaEvent::SequenceNumber, data)
+        }
+        "text_t" => meta(MetaEvent::TextEvent, fields.string()?),
+        "copyright_t" => meta(MetaEvent::CopyrightNotice, fields.string()?),
+        "title_t" => meta(MetaEvent::SequenceOrTrackName, fields.string()?),
+        "instrument_name_t" => meta(MetaEvent::InstrumentName, fields.string()?),
+        "lyric_t" => meta(MetaEvent::Lyric, fields.string()?),
+        "marker_t" => meta(MetaEvent::Marker, fields.string()?),
+        "cue_point_t" => meta(MetaEvent::CuePoint, fields.string()?),
+        "channel_prefix" => meta(MetaEvent::MIDIChannelPrefix, vec![fields.number()?]),
+        "midi_port" => meta(MetaEvent::MidiPort, vec![fields.number()?]),
+        "end_track" => meta(MetaEvent::EndOfTrack, Vec::new()),
+        "tempo" => {
+            let tempo: u32 = fields.number()?;
+            if tempo > 0xff_ffff {
+                return Err(format!("tempo is out of range: {}", tempo));
+            }
+            let mut data = vec![0; 3];
+            BigEndian::write_u24(&mut data, tempo);
+            meta(MetaEvent::SetTempo, data)
+        }
+        "smpte_offset" => meta(MetaEvent::SMTPEOffset, fields.numbers(5)?),
+        "time_signature" => meta(MetaEvent::TimeSignature, fields.numbers(4)?),
+        "key_signature" => {
+            let sf: i8 = fields.number()?;
+            let mode = fields.string()?;
+            let mi = match str::from_utf8(&mode).map(|mode| mode.to_lowercase()) {
+                Ok(ref mode) if mode == "major" => 0,
+                Ok(ref mode) if mode == "minor" => 1,
+                _ => return Err("key must be major or minor".to_string()),
+            };
+            meta(MetaEvent::KeySignature, vec![sf as u8, mi])
+        }
+        "sequencer_specific" => meta(MetaEvent::SequencerSpecificMetaEvent, fields.data()?),
+        "unknown_meta_event" => {
+            let event = MetaEvent::new(fields.number()?);
+            meta(event, fields.data()?)
+        }
+        "note_off_c" => midi(MidiEvent::NoteOff {
+            ch: fields.number_in(0, 15)?,
+            note: fields.number_in(0, 127)?,
+            velocity: fields.number_in(0, 127)?,
+        }),
+        "note_on_c" => midi(MidiEvent::NoteOn {
+            ch: fields.number_in(0, 15)?,
+            note: fields.number_in(0, 127)?,
+            velocity: fields.number_in(0, 127)?,
+        }),
+        "poly_aftertouch_c" => midi(MidiEvent::PolyphonicKeyPressure {
+            ch: fields.number_in(0, 15)?,
+            note: fields.number_in(0, 127)?,
+            velocity: fields.number_in(0, 127)?,
+        }),
+        "control_c" => midi(MidiEvent::ControlChange {
+            ch: fields.number_in(0, 15)?,
+            control: fields.number_in(0, 127)?,
+            data: fields.number_in(0, 127)?,
+        }),
+        "program_c" => midi(MidiEvent::ProgramChange {
+            ch: fields.number_in(0, 15)?,
+            program: fields.number_in(0, 127)?,
+        }),
+        "channel_aftertouch_c" => midi(MidiEvent::ChannelPressure {
+            ch: fields.number_in(0, 15)?,
+            pressure: fields.number_in(0, 127)?,
+        }),
+        "pitch_bend_c" => {
+            let ch = fields.number_in(0, 15)?;
+            let value: i16 = fields.number_in(0, 16383)?;
+            midi(MidiEvent::PitchBendChange {
+                ch,
+                data: value - 8192,
+            })
+        }
+        "unknown_midi_event_c" => midi(MidiEvent::Unknown {
+            ch: fields.number_in(0, 15)?,
+        }),
+        "system_exclusive" => sys_ex(SysExEvent::F0, fields.data()?),
+        "system_exclusive_packet" => sys_ex(SysExEvent::F7, fields.data()?),
+        "unknown_sysex_event" => {
+            let event = SysExEvent::Unknown {
+                status: fields.number()?,
+            };
+            sys_ex(event, fields.data()?)
+        }
+        _ => return Err(format!("unknown record type: {}", record)),
+    })
+}
+
+enum Field {
+    Plain(String),
+    Quoted(Vec<u8>),
+}
+
+struct Fields {
+    fields: Vec<Field>,
+    position: usize,
+}
+impl Fields {
+    fn new(line: &str) -> Result<Fields, String> {
+        let mut fields = Vec::new();
+        let mut chars = line.chars().peekable();
+        loop {
+            while chars.peek().map(|c| c.is_whitespace()) == Some(true) {
+                chars.next();
+            }
+            if chars.peek() == Some(&'"') {
+                chars.next();
+                let mut data = Vec::new();
+                loop {
+                    match chars.next() {
+                        Some('"') if chars.peek() == Some(&'"') => {
+                            chars.next();
+                            data.push(b'"');
+                        }
+                        Some('"') => break,
+                        Some('\\') if chars.peek() == Some(&'\\') => {
+                            chars.next();
+                            data.push(b'\\');
+                        }
+                        Some('\\') if chars.peek().map(|c| c.is_digit(8)) == Some(true) => {
+                            let digits: String = chars.by_ref().take(3).collect();
+                            data.push(
+                                u8::from_str_radix(&digits, 8)
+                                    .map_err(|_| format!("invalid escape: \\{}", digits))?,
+                            );
+                        }
+                        Some(c) => {
+                            let mut buffer = [0; 4];
+                            data.extend_from_slice(c.encode_utf8(&mut buffer).as_bytes());
+                        }
+                        None => return Err("unterminated string".to_string()),
+                    }
+                }
+                while chars.peek().map(|c| c.is_whitespace()) == Some(true) {
+                    chars.next();
+                }
+                fields.push(Field::Quoted(data));
+                match chars.next() {
+                    Some(',') => continue,
+                    None => break,
+                    Some(c) => return Err(format!("unexpected character after string: {}", c)),
+                }
+            }
+            let mut field = String::new();
+            loop {
+                match chars.next() {
+                    Some(',') => break,
+                    Some(c) => field.push(c),
+                    None => {
+                        fields.push(Field::Plain(field.trim().to_string()));
+                        return Ok(Fields {
+                            fields,
+                            position: 0,
+                        });
+                    }
+                }
+            }
+            fields.push(Field::Plain(field.trim().to_string()));
+        }
+        Ok(Fields {
+            fields,
+            position: 0,
+        })
+    }
+    fn next(&mut self) -> Result<&Field, String> {
+        let field = self
+            .fields
+            .get(self.position)
+            .ok_or_else(|| "missing parameter".to_string())?;
+        self.position += 1;
+        Ok(field)
+    }
+    fn text(&mut self) -> Result<String, String> {
+        match *self.next()? {
+            Field::Plain(ref text) => Ok(text.clone()),
+            Field::Quoted(_) => Err("unexpected string".to_string()),
+        }
+    }
+    fn number<T: FromStr>(&mut self) -> Result<T, String> {
+        let text = self.text()?;
+        text.parse()
+            .map_err(|_| format!("invalid number: {}", text))
+    }
+    fn number_in<T: FromStr + PartialOrd + fmt::Display>(
+        &mut self,
+        min: T,
+        max: T,
+    ) -> Result<T, String> {
+        let value: T = self.number()?;
+        if value < min || value > max {
+            return Err(format!("{} is out of range {}-{}", value, min, max));
+        }
+        Ok(value)
+    }
+    fn numbers(&mut self, len: usize) -> Result<Vec<u8>, String> {
+        (0..len).map(|_| self.number()).collect()
+    }
+    fn data(&mut self) -> Result<Vec<u8>, String> {
+        let len = self.number()?;
+        self.numbers(len)
+    }
+    fn string(&mut self) -> Result<Vec<u8>, String> {
+        match *self.next()? {
+            Field::Quoted(ref data) => Ok(data.clone()),
+            Field::Plain(ref text) => Ok(text.as_bytes().to_vec()),
+        }
+    }
+    fn finish(&self) -> Result<(), String> {
+        if self.position < self.fields.len() {
+            Err("too many parameters".to_string())
+        } else {
+            Ok(())
+        }
+    }
+}
+
+fn quote(data: &[u8]) -> String {
+    let mut quoted = String::from("\"");
+    match str::from_utf8(data) {
+        Ok(text) => {
+            for c in text.chars() {
+                if c.is_ascii() || c.is_control() {
+                    let mut buffer = [0; 4];
+                    for &byte in c.encode_utf8(&mut buffer).as_bytes() {
+                        escape(byte, &mut quoted);
+                    }
+                } else {
+                    quoted.push(c);
+                }
+            }
+        }
+        Err(_) => {
+            for &byte in data {
+                escape(byte, &mut quoted);
+            }
+        }
+    }
+    quoted.push('"');
+    quoted
+}
+
+fn escape(byte: u8, quoted: &mut String) {
+    match byte {
+        b'"' => quoted.push_str("\"\""),
+        b'\\' => quoted.push_str("\\\\"),
+        0x20..=0x7e => quoted.push(byte as char),
+        _ => quoted.push_str(&format!("\\{:03o}", byte)),
+    }
+}
+
+#[cfg(test)]
+mod midicsv_tests {
+    use midicsv::*;
+
+    fn meta(event: MetaEvent, data: &[u8]) -> Message {
+        Message::MetaEvent {
+            delta_time: 0,
+            event,
+            data: data.to_vec(),
+        }
+    }
+    fn midi(delta_time: u32, event: MidiEvent) -> Message {
+        Message::MidiEvent { delta_time, event }
+    }
+    #[test]
+    fn round_trip() {
+        let mut smf = Smf::new(1, 0xe728);
+        smf.tracks.push(vec![
+            meta(MetaEvent::SequenceNumber, &[0x01, 0x02]),
+            meta(MetaEvent::SequenceNumber, &[]),
+            meta(MetaEvent::TextEvent, b"a, b"),
+            meta(MetaEvent::CopyrightNotice, b"\"c\" \\"),
+            meta(MetaEvent::SequenceOrTrackName, "寿限無\n".as_bytes()),
+            meta(MetaEvent::InstrumentName, b""),
+            meta(MetaEvent::Lyric, &[0x82, 0xa0]),
+            meta(MetaEvent::Marker, b"m"),
+            meta(MetaEvent::CuePoint, b"c"),
+            meta(MetaEvent::MIDIChannelPrefix, &[9]),
+            meta(MetaEvent::MidiPort, &[1]),
+            meta(MetaEvent::SetTempo, &[0x07, 0xa1, 0x20]),
+            meta(MetaEvent::SetTempo, &[0x07, 0xa1]),
+            meta(MetaEvent::SMTPEOffset, &[0x60, 0, 3, 4, 5]),
+            meta(MetaEvent::TimeSignature, &[6, 3, 24, 8]),
+            meta(MetaEvent::KeySignature, &[0xfd, 1]),
+            meta(MetaEvent::KeySignature, &[0, 2]),
+            meta(MetaEvent::SequencerSpecificMetaEvent, &[0x43, 0x7b]),
+            meta(MetaEvent::Unknown { event_type: 0x60 }, &[1, 2]),
+            meta(MetaEvent::EndOfTrack, &[]),
+        ]);
+        smf.tracks.push(vec![
+            midi(
+                0,
+                MidiEvent::NoteOn {
+                    ch: 0,
+                    note: 60,
+                    velocity: 100,
+                },
+            ),
+            midi(
+                10,
+                MidiEvent::NoteOff {
+                    ch: 15,
+                    note: 60,
+                    velocity: 127,
+                },
+            ),
+            midi(
+                0,
+                MidiEvent::PolyphonicKeyPressure {
+                    ch: 1,
+                    note: 2,
+                    velocity: 3,
+                },
+            ),
+            midi(
+                0,
+                MidiEvent::ControlChange {
+                    ch: 1,
+                    control: 7,
+                    data: 100,
+                },
+            ),
+            midi(0, MidiEvent::ProgramChange { ch: 1, program: 40 }),
+            midi(
+                0,
+                MidiEvent::ChannelPressure {
+                    ch: 1,
+                    pressure: 64,
+                },
+            ),
+            midi(0, MidiEvent::PitchBendChange { ch: 1, data: -8192 }),
+            midi(0, MidiEvent::PitchBendChange { ch: 1, data: 8191 }),
+            midi(0, MidiEvent::Unknown { ch: 2 }),
+            Message::SysExEvent {
+                delta_time: 0xfff_ffff,
+                event: SysExEvent::F0,
+                data: vec![0x7e, 0x7f, 0x09, 0x01, 0xf7],
+            },
+            Message::SysExEvent {
+                delta_time: 0,
+                event: SysExEvent::F7,
+                data: vec![0xf7],
+            },
+            Message::SysExEvent {
+                delta_time: 0,
+                event: SysExEvent::Unknown { status: 0xf5 },
+                data: Vec::new(),
+            },
+        ]);
+        smf.tracks.push(Vec::new());
+        let csv = to_csv(&smf);
+        assert_eq!(from_csv(&csv), Ok(smf));
+        for record in &[
+            "0, 0, Header, 1, 3, 59176\n",
+            "1, 0, Sequence_number, 258\n",
+            "1, 0, Unknown_meta_event, 0, 0\n",
+            "1, 0, Text_t, \"a, b\"\n",
+            "1, 0, Copyright_t, \"\"\"c\"\" \\\\\"\n",
+            "1, 0, Title_t, \"寿限無\\012\"\n",
+            "1, 0, Lyric_t, \"\\202\\240\"\n",
+            "1, 0, Unknown_meta_event, 81, 2, 7, 161\n",
+            "1, 0, Key_signature, -3, \"minor\"\n",
+            "1, 0, Unknown_meta_event, 89, 2, 0, 2\n",
+            "1, 0, Sequencer_specific, 2, 67, 123\n",
+            "2, 10, Pitch_bend_c, 1, 0\n",
+            "2, 268435465, System_exclusive, 5, 126, 127, 9, 1, 247\n",
+            "3, 0, Start_track\n0, 0, End_of_file\n",
+        ] {
+            assert!(csv.contains(record), "{}", record);
+        }
+    }
+    #[test]
+    fn from_csv_case_and_comments() {
+        let smf = from_csv(
+            "# comment\n\
+             0, 0, header, 0, 1, 96\n\
+             ; comment\n\
+             1, 0, START_TRACK\n\
+             1, 0, Key_signature, 2, major\n\
+             1, 0, Title_t, unquoted\n",
+        )
+        .unwrap();
+        assert_eq!(
+            smf.tracks,
+            vec![vec![
+                meta(MetaEvent::KeySignature, &[2, 0]),
+                meta(MetaEvent::SequenceOrTrackName, b"unquoted"),
+            ]]
+        );
+    }
+    #[test]
+    fn from_csv_errors() {
+        assert_eq!(from_csv(""), Err(CsvError::MissingHeader));
+        assert_eq!(
+            from_csv("1, 0, Start_track\n"),
+            Err(CsvError::MissingHeader)
+        );
+        assert_eq!(
+            from_csv("0, 0, Header, 1, 2, 96\n1, 0, Start_track\n"),
+            Err(CsvError::TrackCount {
+                expected: 2,
+                found: 1
+            })
+        );
+        let header = "0, 0, Header, 1, 1, 96\n1, 0, Start_track\n";
+        for record in &[
+            "0, 0, Header, 1, 1, 96",
+            "3, 0, Start_track",
+            "2, 0, Note_on_c, 0, 60, 100",
+            "1, 0, Note_on_c, 0, 60, 100, 1",
+            "1, 0, Note_on_c, 0, 60, 256",
+            "1, 0, Note_on, 0, 60, 100",
+            "1, 0, Tempo, 16777216",
+            "1, 0, Pitch_bend_c, 0, 40960",
+            "1, 0, Pitch_bend_c, 0, 16384",
+            "1, 0, Pitch_bend_c, 0, -1",
+            "1, 0, Note_on_c, 16, 60, 100",
+            "1, 0, Note_off_c, 0, 128, 0",
+            "1, 0, Poly_aftertouch_c, 0, 60, 128",
+            "1, 0, Control_c, 0, 128, 0",
+            "1, 0, Program_c, 0, 128",
+            "1, 0, Channel_aftertouch_c, 0, 128",
+            "1, 0, Key_signature, 0, \"dorian\"",
+            "1, 0, Title_t, \"a",
+            "1, 0, Title_t, \"a\" b",
+            "1, 0, System_exclusive, 3, 1, 2",
+            "1, -1, End_track",
+        ] {
+            match from_csv(&format!("{}{}\n", header, record)) {
+                Err(CsvError::InvalidLine { line: 3, .. }) => (),
+                other => panic!("{}: {:?}", record, other),
+            }
+        }
+        match from_csv(&format!("{}1, 10, End_track\n1, 5, End_track\n", header)) {
+            Err(CsvError::InvalidLine { line: 4, .. }) => (),
+            other => panic!("{:?}", other),
+        }
+        match from_csv(&format!("{}0, 0, End_of_file\n1, 0, End_track\n", header)) {
+            Err(CsvError::InvalidLine { line: 4, .. }) => (),
+            other => panic!("{:?}", other),
+        }
+        for lines in &[
+            "# ghakuf running_status=maybe\n1, 0, End_track",
+            "# ghakuf running_status=on\n# ghakuf delta_time_bytes=2\n1, 0, End_track",
+            "# ghakuf delta_time_bytes=5\n1, 0, End_track",
+            "# ghakuf delta_time_bytes=1\n1, 128, End_track",
+            "# ghakuf running_status=on\n0, 0, End_of_file",
+        ] {
+            match text_from_csv(&format!("{}{}\n", header, lines)) {
+                Err(CsvError::InvalidLine { line: 3, .. })
+                | Err(CsvError::InvalidLine { line: 4, .. }) => (),
+                other => panic!("{}: {:?}", lines, other),
+            }
+        }
+    }
+}
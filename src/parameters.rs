@@ -0,0 +1,515 @@
+use messages::*;
+use std::collections::HashMap;
+use std::fmt;
+
+/// Registered parameter number of pitch bend sensitivity (value: semitones in MSB, cents in LSB).
+pub const PITCH_BEND_SENSITIVITY: u16 = 0x0000;
+/// Registered parameter number of channel fine tuning.
+pub const FINE_TUNING: u16 = 0x0001;
+/// Registered parameter number of channel coarse tuning.
+pub const COARSE_TUNING: u16 = 0x0002;
+/// Registered parameter number of tuning program change.
+pub const TUNING_PROGRAM: u16 = 0x0003;
+/// Registered parameter number of tuning bank select.
+pub const TUNING_BANK: u16 = 0x0004;
+/// Registered parameter number of modulation depth range.
+pub const MODULATION_DEPTH_RANGE: u16 = 0x0005;
+/// Null parameter number (deselects RPN and NRPN).
+pub const NULL: u16 = 0x3fff;
+
+/// An enum representing kinds of parameter carried by control changes.
+#[derive(PartialEq, Eq, PartialOrd, Ord, Hash, Clone, Copy, Debug)]
+pub enum ParameterKind {
+    /// Registered parameter number (CC 101/100 + data entry)
+    Rpn,
+    /// Non-registered parameter number (CC 99/98 + data entry)
+    Nrpn,
+    /// 14-bit control change (MSB in CC 0-31, LSB in CC 32-63)
+    Cc14,
+}
+impl fmt::Display for ParameterKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        use parameters::ParameterKind::*;
+        write!(
+            f,
+            "{}",
+            match *self {
+                Rpn => "RPN",
+                Nrpn => "NRPN",
+                Cc14 => "CC14",
+            }
+        )
+    }
+}
+
+/// A struct representing one parameter change aggregated from several control changes.
+///
+/// # Examples
+///
+/// ```
+/// use ghakuf::parameters::*;
+///
+/// let change = ParameterChange::pitch_bend_sensitivity(0, 12, 0);
+/// assert_eq!(change.kind, ParameterKind::Rpn);
+/// assert_eq!(change.number, PITCH_BEND_SENSITIVITY);
+/// assert_eq!(change.value14, 12 << 7);
+/// ```
+#[derive(PartialEq, Clone, Copy, Debug)]
+pub struct ParameterChange {
+    pub ch: u8,
+    pub kind: ParameterKind,
+    pub number: u16,
+    pub value14: u16,
+}
+impl ParameterChange {
+    /// Builds RPN change of pitch bend sensitivity.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use ghakuf::parameters::ParameterChange;
+    ///
+    /// let change = ParameterChange::pitch_bend_sensitivity(3, 2, 50);
+    /// assert_eq!(change.msb(), 2);
+    /// assert_eq!(change.lsb(), 50);
+    /// ```
+    pub fn pitch_bend_sensitivity(ch: u8, semitones: u8, cents: u8) -> ParameterChange {
+        ParameterChange {
+            ch,
+            kind: ParameterKind::Rpn,
+            number: PITCH_BEND_SENSITIVITY,
+            value14: (semitones as u16 & 0x7f) << 7 | (cents as u16 & 0x7f),
+        }
+    }
+    /// Returns upper 7 bits of value.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use ghakuf::parameters::{ParameterChange, ParameterKind};
+    ///
+    /// let change = ParameterChange { ch: 0, kind: ParameterKind::Cc14, number: 7, value14: 0x3f81 };
+    /// assert_eq!(change.msb(), 0x7f);
+    /// ```
+    pub fn msb(&self) -> u8 {
+        (self.value14 >> 7) as u8 & 0x7f
+    }
+    /// Returns lower 7 bits of value.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use ghakuf::parameters::{ParameterChange, ParameterKind};
+    ///
+    /// let change = ParameterChange { ch: 0, kind: ParameterKind::Cc14, number: 7, value14: 0x3f81 };
+    /// assert_eq!(change.lsb(), 0x01);
+    /// ```
+    pub fn lsb(&self) -> u8 {
+        self.value14 as u8 & 0x7f
+    }
+}
+impl fmt::Display for ParameterChange {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "({}{{ch: {}, number: {}, value: {}}})",
+            self.kind, self.ch, self.number, self.value14
+        )
+    }
+}
+
+#[derive(Default)]
+struct ChannelParameters {
+    rpn: (u8, u8),
+    nrpn: (u8, u8),
+    selected: Option<(ParameterKind, u16)>,
+    values: HashMap<(ParameterKind, u16), u16>,
+    cc_msb: [u8; 32],
+    // whether LSB (CC 32-63) of the controller has been seen, which makes it 14-bit
+    cc_lsb_seen: [bool; 32],
+}
+
+/// Stateful decoder which aggregates control changes into parameter changes per channel.
+///
+/// Data entry (CC 6/38) and data increment/decrement (CC 96/97) are applied to the RPN or NRPN
+/// selected last on the channel. A parameter change is returned for every control change which
+/// updates a value, so a full MSB + LSB sequence yields two changes and the latter carries the
+/// complete 14-bit value.
+///
+/// Most controllers in CC 0-31 are sent without LSB, so a controller is treated as 14-bit only after
+/// its LSB (CC 32-63) has been seen on the channel. MSB alone (bank select MSB without LSB, or plain
+/// 7-bit controllers such as volume) doesn't yield a parameter change, and MSB of a 14-bit controller
+/// yields a change with LSB 0, as MSB resets LSB.
+///
+/// # Examples
+///
+/// ```
+/// use ghakuf::messages::MidiEvent;
+/// use ghakuf::parameters::*;
+///
+/// let mut decoder = ParameterDecoder::new();
+/// let cc = |control, data| MidiEvent::ControlChange { ch: 0, control, data };
+/// assert_eq!(decoder.push(&cc(101, 0)), None);
+/// assert_eq!(decoder.push(&cc(100, 0)), None);
+/// assert_eq!(
+///     decoder.push(&cc(6, 12)),
+///     Some(ParameterChange::pitch_bend_sensitivity(0, 12, 0))
+/// );
+/// ```
+pub struct ParameterDecoder {
+    channels: Vec<ChannelParameters>,
+}
+impl Default for ParameterDecoder {
+    fn default() -> ParameterDecoder {
+        ParameterDecoder::new()
+    }
+}
+impl ParameterDecoder {
+    /// Builds ParameterDecoder without any selected parameter.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use ghakuf::parameters::ParameterDecoder;
+    ///
+    /// let decoder: ParameterDecoder = ParameterDecoder::new();
+    /// ```
+    pub fn new() -> ParameterDecoder {
+        ParameterDecoder {
+            channels: (0..16).map(|_| ChannelParameters::default()).collect(),
+        }
+    }
+    /// Pushes MIDI event to decoder and returns parameter change if the event completes one.
+    ///
+    /// Events other than control changes are ignored.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use ghakuf::messages::MidiEvent;
+    /// use ghakuf::parameters::*;
+    ///
+    /// let mut decoder = ParameterDecoder::new();
+    /// assert_eq!(decoder.push(&MidiEvent::ControlChange { ch: 2, control: 7, data: 100 }), None);
+    /// assert_eq!(
+    ///     decoder.push(&MidiEvent::ControlChange { ch: 2, control: 39, data: 5 }),
+    ///     Some(ParameterChange { ch: 2, kind: ParameterKind::Cc14, number: 7, value14: 100 << 7 | 5 })
+    /// );
+    /// ```
+    pub fn push(&mut self, event: &MidiEvent) -> Option<ParameterChange> {
+        let (ch, control, data) = match *event {
+            MidiEvent::ControlChange { ch, control, data } => (ch & 0x0f, control, data & 0x7f),
+            _ => return None,
+        };
+        let state = &mut self.channels[ch as usize];
+        match control {
+            101 | 100 => {
+                if control == 101 {
+                    state.rpn.0 = data;
+                } else {
+                    state.rpn.1 = data;
+                }
+                state.select(ParameterKind::Rpn);
+                None
+            }
+            99 | 98 => {
+                if control == 99 {
+                    state.nrpn.0 = data;
+                } else {
+                    state.nrpn.1 = data;
+                }
+                state.select(ParameterKind::Nrpn);
+                None
+            }
+            6 | 38 | 96 | 97 => {
+                let key = state.selected?;
+                let value = state.values.entry(key).or_insert(0);
+                *value = match control {
+                    6 => (data as u16) << 7,
+                    38 => (*value & !0x7f) | data as u16,
+                    96 => (*value + 1).min(0x3fff),
+                    _ => value.saturating_sub(1),
+                };
+                Some(ParameterChange {
+                    ch,
+                    kind: key.0,
+                    number: key.1,
+                    value14: *value,
+                })
+            }
+            0..=31 => {
+                state.cc_msb[control as usize] = data;
+                if !state.cc_lsb_seen[control as usize] {
+                    return None;
+                }
+                Some(ParameterChange {
+                    ch,
+                    kind: ParameterKind::Cc14,
+                    number: control as u16,
+                    value14: (data as u16) << 7,
+                })
+            }
+            32..=63 => {
+                let number = control - 32;
+                state.cc_lsb_seen[number as usize] = true;
+                Some(ParameterChange {
+                    ch,
+                    kind: ParameterKind::Cc14,
+                    number: number as u16,
+                    value14: (state.cc_msb[number as usize] as u16) << 7 | data as u16,
+                })
+            }
+            _ => None,
+        }
+    }
+    /// Returns parameter number selected on channel currently.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use ghakuf::messages::MidiEvent;
+    /// use ghakuf::parameters::*;
+    ///
+    /// let mut decoder = ParameterDecoder::new();
+    /// decoder.push(&MidiEvent::ControlChange { ch: 0, control: 99, data: 0x01 });
+    /// decoder.push(&MidiEvent::ControlChange { ch: 0, control: 98, data: 0x08 });
+    /// assert_eq!(decoder.selected(0), Some((ParameterKind::Nrpn, 0x88)));
+    /// ```
+    pub fn selected(&self, ch: u8) -> Option<(ParameterKind, u16)> {
+        self.channels[(ch & 0x0f) as usize].selected
+    }
+    /// Clears all selected parameters and values.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use ghakuf::messages::MidiEvent;
+    /// use ghakuf::parameters::ParameterDecoder;
+    ///
+    /// let mut decoder = ParameterDecoder::new();
+    /// decoder.push(&MidiEvent::ControlChange { ch: 0, control: 101, data: 0 });
+    /// decoder.reset();
+    /// assert_eq!(decoder.selected(0), None);
+    /// ```
+    pub fn reset(&mut self) {
+        *self = ParameterDecoder::new();
+    }
+}
+impl ChannelParameters {
+    fn select(&mut self, kind: ParameterKind) {
+        let (msb, lsb) = match kind {
+            ParameterKind::Nrpn => self.nrpn,
+            _ => self.rpn,
+        };
+        let number = (msb as u16) << 7 | lsb as u16;
+        self.selected = if number == NULL {
+            None
+        } else {
+            Some((kind, number))
+        };
+    }
+}
+
+/// Encoder which expands parameter changes into control change messages.
+///
+/// # Examples
+///
+/// ```
+/// use ghakuf::messages::{Message, MidiEvent};
+/// use ghakuf::parameters::*;
+///
+/// let messages = ParameterEncoder::new()
+///     .null_terminate(false)
+///     .encode(&ParameterChange::pitch_bend_sensitivity(0, 12, 0), 480);
+/// let controls: Vec<(u8, u8)> = messages
+///     .iter()
+///     .map(|message| match *message {
+///         Message::MidiEvent { event: MidiEvent::ControlChange { control, data, .. }, .. } => {
+///             (control, data)
+///         }
+///         _ => unreachable!(),
+///     })
+///     .collect();
+/// assert_eq!(controls, vec![(101, 0), (100, 0), (6, 12), (38, 0)]);
+/// ```
+pub struct ParameterEncoder {
+    null_terminate: bool,
+}
+impl Default for ParameterEncoder {
+    fn default() -> ParameterEncoder {
+        ParameterEncoder::new()
+    }
+}
+impl ParameterEncoder {
+    /// Builds ParameterEncoder with initial value.
+    ///
+    /// | ParameterEncoder's member | type | initial value |
+    /// |:---|:---|:---|
+    /// | null_terminate | bool | true |
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use ghakuf::parameters::ParameterEncoder;
+    ///
+    /// let encoder: ParameterEncoder = ParameterEncoder::new();
+    /// ```
+    pub fn new() -> ParameterEncoder {
+        ParameterEncoder {
+            null_terminate: true,
+        }
+    }
+    /// Sets bool value whether RPN/NRPN changes are followed by null parameter number or not.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use ghakuf::parameters::ParameterEncoder;
+    ///
+    /// let mut encoder = ParameterEncoder::new();
+    /// encoder.null_terminate(false);
+    /// ```
+    pub fn null_terminate(&mut self, null_terminate: bool) -> &mut ParameterEncoder {
+        self.null_terminate = null_terminate;
+        self
+    }
+    /// Expands parameter change into messages. Only the first message has `delta_time`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use ghakuf::messages::{Message, MidiEvent};
+    /// use ghakuf::parameters::*;
+    ///
+    /// let change = ParameterChange { ch: 1, kind: ParameterKind::Cc14, number: 7, value14: 0x3fff };
+    /// assert_eq!(
+    ///     ParameterEncoder::new().encode(&change, 10),
+    ///     vec![
+    ///         Message::MidiEvent {
+    ///             delta_time: 10,
+    ///             event: MidiEvent::ControlChange { ch: 1, control: 7, data: 0x7f },
+    ///         },
+    ///         Message::MidiEvent {
+    ///             delta_time: 0,
+    ///             event: MidiEvent::ControlChange { ch: 1, control: 39, data: 0x7f },
+    ///         },
+    ///     ]
+    /// );
+    /// ```
+    pub fn encode(&self, change: &ParameterChange, delta_time: u32) -> Vec<Message> {
+        let number_msb = (change.number >> 7) as u8 & 0x7f;
+        let number_lsb = change.number as u8 & 0x7f;
+        let mut controls: Vec<(u8, u8)> = Vec::new();
+        match change.kind {
+            ParameterKind::Rpn | ParameterKind::Nrpn => {
+                let (msb_control, lsb_control) = match change.kind {
+                    ParameterKind::Rpn => (101, 100),
+                    _ => (99, 98),
+                };
+                controls.push((msb_control, number_msb));
+                controls.push((lsb_control, number_lsb));
+                controls.push((6, change.msb()));
+                controls.push((38, change.lsb()));
+                if self.null_terminate {
+                    controls.push((101, 0x7f));
+                    controls.push((100, 0x7f));
+                }
+            }
+            ParameterKind::Cc14 => {
+                controls.push((number_lsb & 0x1f, change.msb()));
+                controls.push(((number_lsb & 0x1f) + 32, change.lsb()));
+            }
+        }
+        controls
+            .into_iter()
+            .enumerate()
+            .map(|(i, (control, data))| Message::MidiEvent {
+                delta_time: if i == 0 { delta_time } else { 0 },
+                event: MidiEvent::ControlChange {
+                    ch: change.ch & 0x0f,
+                    control,
+                    data,
+                },
+            })
+            .collect()
+    }
+}
+#[cfg(test)]
+mod parameters_tests {
+    use parameters::*;
+    fn decode_all(messages: &[Message]) -> Vec<ParameterChange> {
+        let mut decoder = ParameterDecoder::new();
+        messages
+            .iter()
+            .filter_map(|message| match *message {
+                Message::MidiEvent { ref event, .. } => decoder.push(event),
+                _ => None,
+            })
+            .collect()
+    }
+    #[test]
+    fn rpn_round_trip() {
+        let change = ParameterChange::pitch_bend_sensitivity(5, 24, 3);
+        let decoded = decode_all(&ParameterEncoder::new().encode(&change, 0));
+        assert_eq!(decoded.last(), Some(&change));
+    }
+    #[test]
+    fn nrpn_round_trip() {
+        let change = ParameterChange {
+            ch: 9,
+            kind: ParameterKind::Nrpn,
+            number: 0x1a3c,
+            value14: 0x2001,
+        };
+        let decoded = decode_all(&ParameterEncoder::new().encode(&change, 0));
+        assert_eq!(decoded.last(), Some(&change));
+    }
+    #[test]
+    fn null_rpn_ignores_data_entry() {
+        let mut decoder = ParameterDecoder::new();
+        for control in &[101, 100] {
+            decoder.push(&MidiEvent::ControlChange {
+                ch: 0,
+                control: *control,
+                data: 0x7f,
+            });
+        }
+        assert_eq!(
+            decoder.push(&MidiEvent::ControlChange {
+                ch: 0,
+                control: 6,
+                data: 1,
+            }),
+            None
+        );
+    }
+    #[test]
+    fn increment_and_decrement() {
+        let mut decoder = ParameterDecoder::new();
+        let cc = |control, data| MidiEvent::ControlChange {
+            ch: 0,
+            control,
+            data,
+        };
+        decoder.push(&cc(101, 0));
+        decoder.push(&cc(100, 2));
+        decoder.push(&cc(6, 64));
+        assert_eq!(decoder.push(&cc(96, 0)).unwrap().value14, 64 << 7 | 1);
+        decoder.push(&cc(97, 0));
+        assert_eq!(decoder.push(&cc(97, 0)).unwrap().value14, (64 << 7) - 1);
+    }
+    #[test]
+    fn cc14_needs_lsb() {
+        let mut decoder = ParameterDecoder::new();
+        let cc = |control, data| MidiEvent::ControlChange {
+            ch: 0,
+            control,
+            data,
+        };
+        assert_eq!(decoder.push(&cc(0, 1)), None);
+        assert_eq!(decoder.push(&cc(7, 100)), None);
+        assert_eq!(decoder.push(&cc(32, 2)).unwrap().value14, 1 << 7 | 2);
+        assert_eq!(decoder.push(&cc(0, 3)).unwrap().value14, 3 << 7);
+        assert_eq!(decoder.push(&cc(7, 90)), None);
+    }
+}
@@ -1,8 +1,10 @@
 use byteorder::{BigEndian, ReadBytesExt};
 use formats::*;
 use messages::*;
+use std::any::Any;
 use std::io::{Read, Seek, SeekFrom};
 use std::{error, fmt, fs, io, mem, path};
+use sysex::ManufacturerId;
 
 /// `ghakuf`'s SMF parser.
 ///
@@ -39,6 +41,8 @@ pub struct Reader<'a, R = fs::File> {
     file: io::BufReader<R>,
     handlers: Vec<&'a mut dyn Handler>,
     path: &'a path::Path,
+    join_sys_ex: bool,
+    sequencer_specific_parsers: Vec<(ManufacturerId, &'a dyn SequencerSpecificParser)>,
 }
 
 impl<'a> Reader<'a, fs::File> {
@@ -94,6 +98,8 @@ impl<'a, R> Reader<'a, R> where R: Read + Seek {
             file: io::BufReader::new(reader),
             path: path::Path::new(""),
             handlers: handlers,
+            join_sys_ex: false,
+            sequencer_specific_parsers: Vec::new(),
         })
     }
 
@@ -120,6 +126,55 @@ impl<'a, R> Reader<'a, R> where R: Read + Seek {
     pub fn push_handler(&mut self, handler: &'a mut dyn Handler) {
         self.handlers.push(handler);
     }
+    /// Sets bool value whether Reader joins F0 packet and following F7 continuation packets into one system exclusive event or not.
+    ///
+    /// Joined event is fired with delta time of F0 packet, and delta times of continuation packets are added to the next event. F7 event which doesn't follow unterminated F0 packet is an escape sequence and is fired as it is.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use ghakuf::reader::*;
+    /// use std::path;
+    ///
+    /// let path = path::Path::new("tests/test.mid");
+    /// let mut handler = FugaHandler {};
+    /// let mut reader = Reader::new(&mut handler, &path).unwrap();
+    /// reader.join_sys_ex(true);
+    ///
+    /// struct FugaHandler {}
+    /// impl Handler for FugaHandler {}
+    /// ```
+    pub fn join_sys_ex(&mut self, join_sys_ex: bool) -> &mut Self {
+        self.join_sys_ex = join_sys_ex;
+        self
+    }
+    /// Registers parser of sequencer specific meta event for manufacturer ID. Parsed value is sent to handlers by `Handler::sequencer_specific_event`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use ghakuf::reader::*;
+    /// use ghakuf::sysex::ManufacturerId;
+    /// use std::any::Any;
+    /// use std::path;
+    ///
+    /// let parser = |data: &[u8]| -> Option<Box<dyn Any>> { Some(Box::new(data.len())) };
+    /// let path = path::Path::new("tests/test.mid");
+    /// let mut handler = FugaHandler {};
+    /// let mut reader = Reader::new(&mut handler, &path).unwrap();
+    /// reader.register_sequencer_specific(ManufacturerId::Extended(0x00, 0x7f), &parser);
+    ///
+    /// struct FugaHandler {}
+    /// impl Handler for FugaHandler {}
+    /// ```
+    pub fn register_sequencer_specific(
+        &mut self,
+        manufacturer: ManufacturerId,
+        parser: &'a dyn SequencerSpecificParser,
+    ) -> &mut Self {
+        self.sequencer_specific_parsers.push((manufacturer, parser));
+        self
+    }
     /// Parses SMF messages and fires(broadcasts) handlers.
     ///
     /// # Examples
@@ -234,6 +289,9 @@ impl<'a, R> Reader<'a, R> where R: Read + Seek {
     fn read_track_block(&mut self) -> Result<&mut Self, ReadError<'a>> {
         let mut data_size = self.file.read_u32::<BigEndian>()?;
         let mut pre_status: u8 = 0;
+        // unterminated F0 packet (delta time, data) and delta times of its continuation packets
+        let mut pending: Option<(u32, Vec<u8>)> = None;
+        let mut carry: u32 = 0;
         while data_size > 0 {
             let mut skip = true;
             for handler in &mut self.handlers {
@@ -244,8 +302,8 @@ impl<'a, R> Reader<'a, R> where R: Read + Seek {
                 data_size = 0;
                 continue;
             }
-            let delta_time = self.read_vlq()?;
-            data_size -= delta_time.len() as u32;
+            let (delta_time, delta_time_len) = self.read_vlq()?;
+            data_size -= delta_time_len;
             let mut status = self.file.read_u8()?;
             if status < 0b10000000 {
                 debug!(
@@ -257,20 +315,34 @@ impl<'a, R> Reader<'a, R> where R: Read + Seek {
             } else {
                 data_size -= mem::size_of::<u8>() as u32;
             }
+            let mut delta_time = delta_time.val();
+            if pending.is_some() && status == 0xf7 {
+                carry += delta_time;
+            } else {
+                if let Some((pending_delta_time, pending_data)) = pending.take() {
+                    warn!("system exclusive packet isn't terminated by 0xf7");
+                    self.fire_sys_ex_event(pending_delta_time, &SysExEvent::F0, &pending_data);
+                }
+                delta_time += carry;
+                carry = 0;
+            }
             match status {
                 0xff => {
                     // meta event
                     debug!("meta event status has found!");
                     let meta_event = MetaEvent::new(self.file.read_u8()?);
                     data_size -= mem::size_of::<u8>() as u32;
-                    let len = self.read_vlq()?;
+                    let (len, len_len) = self.read_vlq()?;
                     let data = self.read_data(&len)?;
-                    data_size -= len.len() as u32 + len.val();
+                    data_size -= len_len + len.val();
                     for handler in &mut self.handlers {
                         if handler.status() == HandlerStatus::Continue {
-                            handler.meta_event(delta_time.val(), &meta_event, &data);
+                            handler.meta_event(delta_time, &meta_event, &data);
                         }
                     }
+                    if meta_event == MetaEvent::SequencerSpecificMetaEvent {
+                        self.fire_sequencer_specific_event(delta_time, &data);
+                    }
                 }
                 0x80..=0xef => {
                     // midi event
@@ -283,7 +355,7 @@ impl<'a, R> Reader<'a, R> where R: Read + Seek {
                     let midi_event = builder.build();
                     for handler in &mut self.handlers {
                         if handler.status() == HandlerStatus::Continue {
-                            handler.midi_event(delta_time.val(), &midi_event);
+                            handler.midi_event(delta_time, &midi_event);
                         }
                     }
                     pre_status = status;
@@ -291,23 +363,27 @@ impl<'a, R> Reader<'a, R> where R: Read + Seek {
                 0xf0 | 0xf7 => {
                     // system exclusive event
                     debug!("system exclusice event status has found!");
-                    if status == 0xf7 && pre_status == 0xf0 {
-                        pre_status = 0;
-                        data_size -= mem::size_of::<u8>() as u32;
-                        continue;
-                    }
                     let sys_ex_event = SysExEvent::new(status);
-                    let len = self.read_vlq()?;
-                    let data = self.read_data(&len)?;
-                    data_size -= len.len() as u32 + len.val();
-                    for handler in &mut self.handlers {
-                        if handler.status() == HandlerStatus::Continue {
-                            handler.sys_ex_event(delta_time.val(), &sys_ex_event, &data);
+                    let (len, len_len) = self.read_vlq()?;
+                    let mut data = self.read_data(&len)?;
+                    data_size -= len_len + len.val();
+                    if self.join_sys_ex {
+                        if let Some((pending_delta_time, mut pending_data)) = pending.take() {
+                            // continuation packet
+                            pending_data.append(&mut data);
+                            if pending_data.last() == Some(&0xf7) {
+                                self.fire_sys_ex_event(pending_delta_time, &SysExEvent::F0, &pending_data);
+                            } else {
+                                pending = Some((pending_delta_time, pending_data));
+                            }
+                            continue;
+                        }
+                        if status == 0xf0 && data.last() != Some(&0xf7) {
+                            pending = Some((delta_time, data));
+                            continue;
                         }
                     }
-                    if status == 0xf0 {
-                        pre_status = 0xf0;
-                    }
+                    self.fire_sys_ex_event(delta_time, &sys_ex_event, &data);
                 }
                 _ => {
                     error!("unknown status has found: {}", status);
@@ -318,14 +394,46 @@ impl<'a, R> Reader<'a, R> where R: Read + Seek {
                 }
             };
         }
+        if let Some((pending_delta_time, pending_data)) = pending {
+            warn!("system exclusive packet isn't terminated by 0xf7");
+            self.fire_sys_ex_event(pending_delta_time, &SysExEvent::F0, &pending_data);
+        }
         Ok(self)
     }
-    fn read_vlq(&mut self) -> Result<VLQ, ReadError<'a>> {
+    fn fire_sequencer_specific_event(&mut self, delta_time: u32, data: &[u8]) {
+        let (manufacturer, data) = match ManufacturerId::from_data(data) {
+            Some(id_and_data) => id_and_data,
+            None => return,
+        };
+        for &(ref id, parser) in &self.sequencer_specific_parsers {
+            if *id != manufacturer {
+                continue;
+            }
+            if let Some(value) = parser.parse(data) {
+                for handler in &mut self.handlers {
+                    if handler.status() == HandlerStatus::Continue {
+                        handler.sequencer_specific_event(delta_time, &manufacturer, &*value);
+                    }
+                }
+            }
+        }
+    }
+    fn fire_sys_ex_event(&mut self, delta_time: u32, event: &SysExEvent, data: &Vec<u8>) {
+        for handler in &mut self.handlers {
+            if handler.status() == HandlerStatus::Continue {
+                handler.sys_ex_event(delta_time, event, data);
+            }
+        }
+    }
+    // returns VLQ and number of bytes read, which is more than VLQ::len() if VLQ isn't minimal
+    fn read_vlq(&mut self) -> Result<(VLQ, u32), ReadError<'a>> {
         let mut vlq_builder = VLQBuilder::new();
+        let mut len = 0;
         while !vlq_builder.closed() {
             vlq_builder.push(self.file.read_u8()?);
+            len += 1;
         }
-        Ok(vlq_builder.build())
+        Ok((vlq_builder.build(), len))
     }
     fn read_data(&mut self, vlq: &VLQ) -> Result<Vec<u8>, ReadError<'a>> {
         let len = vlq.val();
@@ -393,6 +501,17 @@ pub trait Handler {
     fn sys_ex_event(&mut self, delta_time: u32, event: &SysExEvent, data: &Vec<u8>) {
         let _ = (delta_time, event, data);
     }
+    /// Fired when sequencer specific meta event has been parsed by the parser registered to Reader.
+    ///
+    /// This is fired right after `meta_event` of the same event, so delta time mustn't be counted again.
+    fn sequencer_specific_event(
+        &mut self,
+        delta_time: u32,
+        manufacturer: &ManufacturerId,
+        value: &dyn Any,
+    ) {
+        let _ = (delta_time, manufacturer, value);
+    }
     /// Fired when track has changed.
     fn track_change(&mut self) {}
     /// Send handler status to parser.
@@ -401,6 +520,42 @@ pub trait Handler {
     }
 }
 
+/// Parser of sequencer specific meta event data following manufacturer ID.
+///
+/// Closures taking data and returning `Option<Box<dyn Any>>` are parsers, too.
+///
+/// # Examples
+///
+/// ```
+/// use ghakuf::reader::SequencerSpecificParser;
+/// use std::any::Any;
+///
+/// struct MarkerColorParser {}
+/// impl SequencerSpecificParser for MarkerColorParser {
+///     fn parse(&self, data: &[u8]) -> Option<Box<dyn Any>> {
+///         match data {
+///             [0x01, r, g, b] => Some(Box::new((*r, *g, *b))),
+///             _ => None,
+///         }
+///     }
+/// }
+///
+/// let value = MarkerColorParser {}.parse(&[0x01, 0x7f, 0x00, 0x00]).unwrap();
+/// assert_eq!(value.downcast_ref::<(u8, u8, u8)>(), Some(&(0x7f, 0x00, 0x00)));
+/// ```
+pub trait SequencerSpecificParser {
+    /// Parses data and returns parsed value, or None if data isn't supported.
+    fn parse(&self, data: &[u8]) -> Option<Box<dyn Any>>;
+}
+impl<F> SequencerSpecificParser for F
+where
+    F: Fn(&[u8]) -> Option<Box<dyn Any>>,
+{
+    fn parse(&self, data: &[u8]) -> Option<Box<dyn Any>> {
+        self(data)
+    }
+}
+
 /// An enum represents handler status.
 #[derive(PartialEq, Clone, Debug)]
 pub enum HandlerStatus {
@@ -0,0 +1,99 @@
+use byteorder::{ByteOrder, LittleEndian};
+use std::{error, fmt};
+
+/// Checks whether data is RIFF MIDI (RMID) or not.
+///
+/// # Examples
+///
+/// ```
+/// use ghakuf::rmid;
+///
+/// assert!(rmid::is_rmid(&rmid::wrap(b"MThd")));
+/// assert!(!rmid::is_rmid(b"MThd"));
+/// ```
+pub fn is_rmid(data: &[u8]) -> bool {
+    data.len() >= 12 && &data[0..4] == b"RIFF" && &data[8..12] == b"RMID"
+}
+
+/// Wraps SMF data into RIFF MIDI (RMID) with a `data` chunk.
+///
+/// # Examples
+///
+/// ```
+/// use ghakuf::rmid;
+///
+/// let rmid = rmid::wrap(b"MThd");
+/// assert_eq!(&rmid[0..4], b"RIFF");
+/// assert_eq!(rmid.len(), 12 + 8 + 4);
+/// ```
+pub fn wrap(smf: &[u8]) -> Vec<u8> {
+    let padding = smf.len() % 2;
+    let mut data = Vec::with_capacity(20 + smf.len() + padding);
+    data.extend_from_slice(b"RIFF");
+    data.extend_from_slice(&le32(12 + smf.len() + padding));
+    data.extend_from_slice(b"RMID");
+    data.extend_from_slice(b"data");
+    data.extend_from_slice(&le32(smf.len()));
+    data.extend_from_slice(smf);
+    if padding > 0 {
+        data.push(0);
+    }
+    data
+}
+
+/// Returns SMF data in the `data` chunk of RIFF MIDI (RMID).
+///
+/// # Examples
+///
+/// ```
+/// use ghakuf::rmid::{self, RmidError};
+///
+/// assert_eq!(rmid::unwrap(&rmid::wrap(b"MThd\x00")), Ok(&b"MThd\x00"[..]));
+/// assert_eq!(rmid::unwrap(b"MThd"), Err(RmidError::NotRmid));
+/// ```
+pub fn unwrap(rmid: &[u8]) -> Result<&[u8], RmidError> {
+    if !is_rmid(rmid) {
+        return Err(RmidError::NotRmid);
+    }
+    let mut position = 12;
+    while position + 8 <= rmid.len() {
+        let len = LittleEndian::read_u32(&rmid[position + 4..position + 8]) as usize;
+        let start = position + 8;
+        if &rmid[position..position + 4] == b"data" {
+            return match rmid.get(start..start + len) {
+                Some(smf) => Ok(smf),
+                None => Err(RmidError::Truncated),
+            };
+        }
+        position = start + len + len % 2;
+    }
+    Err(RmidError::NoData)
+}
+
+fn le32(value: usize) -> [u8; 4] {
+    let mut data = [0; 4];
+    LittleEndian::write_u32(&mut data, value as u32);
+    data
+}
+
+/// An enum represents errors of RIFF MIDI (RMID).
+#[derive(PartialEq, Clone, Copy, Debug)]
+pub enum RmidError {
+    /// Data doesn't start with RIFF header of RMID.
+    NotRmid,
+    /// RMID doesn't have `data` chunk.
+    NoData,
+    /// `data` chunk is shorter than its length.
+    Truncated,
+}
+impl fmt::Display for RmidError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        use rmid::RmidError::*;
+        match *self {
+            NotRmid => write!(f, "Data isn't RIFF MIDI."),
+            NoData => write!(f, "RIFF MIDI doesn't have data chunk."),
+            Truncated => write!(f, "Data chunk of RIFF MIDI is truncated."),
+        }
+    }
+}
+impl error::Error for RmidError {}
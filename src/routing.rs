@@ -0,0 +1,197 @@
+use messages::*;
+use reader::{Handler, HandlerStatus};
+
+/// A struct representing MIDI channel prefix and MIDI port in effect at an event.
+///
+/// `None` means no MIDIChannelPrefix (or MidiPort) meta event is in effect.
+///
+/// # Examples
+///
+/// ```
+/// use ghakuf::routing::Route;
+///
+/// let route = Route { channel: Some(9), port: Some(1) };
+/// assert_eq!(route.index(), Some(25));
+/// ```
+#[derive(PartialEq, Eq, Hash, Clone, Copy, Debug, Default)]
+pub struct Route {
+    /// Channel (0-15) of MIDIChannelPrefix meta event
+    pub channel: Option<u8>,
+    /// Port of MidiPort meta event
+    pub port: Option<u8>,
+}
+impl Route {
+    /// Returns channel number across ports (port * 16 + channel), port 0 is assumed if no port is in effect.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use ghakuf::routing::Route;
+    ///
+    /// assert_eq!(Route { channel: Some(3), port: None }.index(), Some(3));
+    /// assert_eq!(Route { channel: None, port: Some(2) }.index(), None);
+    /// ```
+    pub fn index(&self) -> Option<u16> {
+        self.channel
+            .map(|ch| self.port.unwrap_or(0) as u16 * 16 + ch as u16)
+    }
+}
+
+/// Handler receiving events tagged with the route in effect. It is used through Router.
+///
+/// # Examples
+///
+/// ```
+/// use ghakuf::messages::*;
+/// use ghakuf::routing::{Route, RoutedHandler};
+///
+/// struct PortHandler {
+///     ports: Vec<Option<u8>>,
+/// }
+/// impl RoutedHandler for PortHandler {
+///     fn midi_event(&mut self, _: u32, _: &MidiEvent, port: Option<u8>) {
+///         self.ports.push(port);
+///     }
+/// }
+/// ```
+pub trait RoutedHandler {
+    /// Fired when SMF header track has found.
+    fn header(&mut self, format: u16, track: u16, time_base: u16) {
+        let _ = (format, track, time_base);
+    }
+    /// Fired when meta event has found with the route in effect.
+    ///
+    /// MIDIChannelPrefix and MidiPort meta events are tagged with the route they set.
+    fn meta_event(&mut self, delta_time: u32, event: &MetaEvent, data: &Vec<u8>, route: Route) {
+        let _ = (delta_time, event, data, route);
+    }
+    /// Fired when MIDI event has found with the port in effect.
+    fn midi_event(&mut self, delta_time: u32, event: &MidiEvent, port: Option<u8>) {
+        let _ = (delta_time, event, port);
+    }
+    /// Fired when system exclusive event has found with the route in effect.
+    fn sys_ex_event(&mut self, delta_time: u32, event: &SysExEvent, data: &Vec<u8>, route: Route) {
+        let _ = (delta_time, event, data, route);
+    }
+    /// Fired when track has changed.
+    fn track_change(&mut self) {}
+    /// Send handler status to parser.
+    fn status(&mut self) -> HandlerStatus {
+        HandlerStatus::Continue
+    }
+}
+
+/// Handler adapter tracking MIDIChannelPrefix and MidiPort meta events and passing the route in effect
+/// to RoutedHandler.
+///
+/// As SMF specification, channel prefix is in effect until the next MIDI event or MIDIChannelPrefix
+/// meta event, and port is in effect until the next MidiPort meta event. Both are reset at track change.
+///
+/// # Examples
+///
+/// ```
+/// use ghakuf::messages::*;
+/// use ghakuf::reader::Reader;
+/// use ghakuf::routing::{Route, RoutedHandler, Router};
+/// use std::path;
+///
+/// struct NameHandler {
+///     names: Vec<(Route, Vec<u8>)>,
+/// }
+/// impl RoutedHandler for NameHandler {
+///     fn meta_event(&mut self, _: u32, event: &MetaEvent, data: &Vec<u8>, route: Route) {
+///         if *event == MetaEvent::InstrumentName {
+///             self.names.push((route, data.clone()));
+///         }
+///     }
+/// }
+///
+/// let path = path::Path::new("tests/test.mid");
+/// let mut handler = NameHandler { names: Vec::new() };
+/// {
+///     let mut router = Router::new(&mut handler);
+///     let mut reader = Reader::new(&mut router, &path).unwrap();
+///     assert!(reader.read().is_ok());
+/// }
+/// assert!(handler.names.is_empty());
+/// ```
+pub struct Router<'a> {
+    handler: &'a mut dyn RoutedHandler,
+    route: Route,
+}
+impl<'a> Router<'a> {
+    /// Builds Router passing events to handler.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use ghakuf::routing::{RoutedHandler, Router};
+    ///
+    /// struct NopHandler {}
+    /// impl RoutedHandler for NopHandler {}
+    ///
+    /// let mut handler = NopHandler {};
+    /// let router = Router::new(&mut handler);
+    /// assert_eq!(router.route().channel, None);
+    /// ```
+    pub fn new(handler: &'a mut dyn RoutedHandler) -> Router<'a> {
+        Router {
+            handler,
+            route: Route::default(),
+        }
+    }
+    /// Returns the route currently in effect.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use ghakuf::messages::*;
+    /// use ghakuf::reader::Handler;
+    /// use ghakuf::routing::{RoutedHandler, Router};
+    ///
+    /// struct NopHandler {}
+    /// impl RoutedHandler for NopHandler {}
+    ///
+    /// let mut handler = NopHandler {};
+    /// let mut router = Router::new(&mut handler);
+    /// router.meta_event(0, &MetaEvent::MidiPort, &vec![1]);
+    /// router.meta_event(0, &MetaEvent::MIDIChannelPrefix, &vec![9]);
+    /// assert_eq!(router.route().index(), Some(25));
+    /// router.midi_event(0, &MidiEvent::NoteOn { ch: 0, note: 60, velocity: 100 });
+    /// assert_eq!(router.route().channel, None);
+    /// assert_eq!(router.route().port, Some(1));
+    /// ```
+    pub fn route(&self) -> Route {
+        self.route
+    }
+}
+impl<'a> Handler for Router<'a> {
+    fn header(&mut self, format: u16, track: u16, time_base: u16) {
+        self.handler.header(format, track, time_base);
+    }
+    fn meta_event(&mut self, delta_time: u32, event: &MetaEvent, data: &Vec<u8>) {
+        match *event {
+            MetaEvent::MIDIChannelPrefix if !data.is_empty() => {
+                self.route.channel = Some(data[0] & 0x0f)
+            }
+            MetaEvent::MidiPort if !data.is_empty() => self.route.port = Some(data[0]),
+            _ => (),
+        }
+        self.handler.meta_event(delta_time, event, data, self.route);
+    }
+    fn midi_event(&mut self, delta_time: u32, event: &MidiEvent) {
+        self.route.channel = None;
+        self.handler.midi_event(delta_time, event, self.route.port);
+    }
+    fn sys_ex_event(&mut self, delta_time: u32, event: &SysExEvent, data: &Vec<u8>) {
+        self.handler
+            .sys_ex_event(delta_time, event, data, self.route);
+    }
+    fn track_change(&mut self) {
+        self.route = Route::default();
+        self.handler.track_change();
+    }
+    fn status(&mut self) -> HandlerStatus {
+        self.handler.status()
+    }
+}
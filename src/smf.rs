@@ -0,0 +1,1303 @@
+use gm;
+use messages::*;
+use reader::{Handler, ReadError, Reader};
+use rmid;
+use state::ChannelState;
+use std::collections::{BTreeMap, VecDeque};
+use std::{io, path};
+use timing::{Meter, MeterMap, TempoMap, DEFAULT_TEMPO};
+use transform::{absolute, relative, ChannelMap, Normalise, Transform};
+use writer::Writer;
+
+/// A struct representing whole SMF on memory, read by Reader and written by Writer.
+///
+/// # Examples
+///
+/// ```
+/// use ghakuf::smf::Smf;
+/// use ghakuf::transform::Transpose;
+/// use std::path;
+///
+/// let mut smf = Smf::read(path::Path::new("tests/test.mid")).unwrap();
+/// assert_eq!(smf.tracks.len(), 2);
+/// smf.transform(&Transpose::new(2));
+/// assert!(smf.write(path::Path::new("tests/smf_doctest.mid")).is_ok());
+/// ```
+#[derive(PartialEq, Clone, Debug)]
+pub struct Smf {
+    /// SMF format (0, 1 or 2)
+    pub format: u16,
+    /// Time base (ticks per quarter note, or SMPTE format)
+    pub time_base: u16,
+    /// Messages of each track without TrackChange
+    pub tracks: Vec<Vec<Message>>,
+}
+impl Smf {
+    /// Builds Smf without any tracks.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use ghakuf::smf::Smf;
+    ///
+    /// let smf = Smf::new(1, 480);
+    /// assert!(smf.tracks.is_empty());
+    /// ```
+    pub fn new(format: u16, time_base: u16) -> Smf {
+        Smf {
+            format,
+            time_base,
+            tracks: Vec::new(),
+        }
+    }
+    /// Builds Smf from messages whose tracks are separated by TrackChange, in the same way as Writer.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use ghakuf::messages::*;
+    /// use ghakuf::smf::Smf;
+    ///
+    /// let messages = vec![
+    ///     Message::MetaEvent { delta_time: 0, event: MetaEvent::EndOfTrack, data: Vec::new() },
+    ///     Message::TrackChange,
+    ///     Message::MetaEvent { delta_time: 0, event: MetaEvent::EndOfTrack, data: Vec::new() },
+    /// ];
+    /// let smf = Smf::from_messages(1, 480, &messages);
+    /// assert_eq!(smf.tracks.len(), 2);
+    /// assert_eq!(smf.messages(), messages);
+    /// ```
+    pub fn from_messages(format: u16, time_base: u16, messages: &[Message]) -> Smf {
+        let mut smf = Smf::new(format, time_base);
+        let messages = match messages.first() {
+            Some(&Message::TrackChange) => &messages[1..],
+            _ => messages,
+        };
+        if !messages.is_empty() {
+            smf.tracks = messages
+                .split(|message| *message == Message::TrackChange)
+                .map(|track| track.to_vec())
+                .collect();
+        }
+        smf
+    }
+    /// Reads SMF file.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use ghakuf::smf::Smf;
+    /// use std::path;
+    ///
+    /// let smf = Smf::read(path::Path::new("tests/test.mid")).unwrap();
+    /// assert_eq!(smf.time_base, 480);
+    /// ```
+    pub fn read(path: &path::Path) -> Result<Smf, ReadError<'_>> {
+        let mut smf = Smf::new(0, 0);
+        let result = match Reader::new(&mut smf, path) {
+            Ok(mut reader) => reader.read().map_err(|err| rebind(err, path)),
+            Err(err) => Err(rebind(err, path)),
+        };
+        result.map(|_| smf)
+    }
+    /// Reads SMF data on memory. RIFF MIDI (RMID) data is unwrapped.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use ghakuf::rmid;
+    /// use ghakuf::smf::Smf;
+    ///
+    /// let data = include_bytes!("../tests/test.mid");
+    /// let smf = Smf::from_bytes(data).unwrap();
+    /// assert_eq!(Smf::from_bytes(&rmid::wrap(data)).unwrap(), smf);
+    /// ```
+    pub fn from_bytes(data: &[u8]) -> Result<Smf, ReadError<'static>> {
+        let path = path::Path::new("");
+        let data = if rmid::is_rmid(data) {
+            rmid::unwrap(data)
+                .map_err(|err| ReadError::Io(io::Error::new(io::ErrorKind::InvalidData, err)))?
+        } else {
+            data
+        };
+        let mut smf = Smf::new(0, 0);
+        let result = match Reader::from_reader(&mut smf, io::Cursor::new(data)) {
+            Ok(mut reader) => reader.read().map_err(|err| rebind(err, path)),
+            Err(err) => Err(rebind(err, path)),
+        };
+        result.map(|_| smf)
+    }
+    /// Writes SMF file by Writer.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use ghakuf::smf::Smf;
+    /// use std::path;
+    ///
+    /// let smf = Smf::new(0, 480);
+    /// assert!(smf.write(path::Path::new("tests/smf_write_doctest.mid")).is_ok());
+    /// ```
+    pub fn write(&self, path: &path::Path) -> Result<(), io::Error> {
+        let messages = self.messages();
+        let mut writer = Writer::new();
+        writer.format(self.format).time_base(self.time_base);
+        for message in &messages {
+            writer.push(message);
+        }
+        writer.write(path)
+    }
+    /// Returns messages of all tracks separated by TrackChange, in the same way as Writer.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use ghakuf::messages::*;
+    /// use ghakuf::smf::Smf;
+    ///
+    /// let mut smf = Smf::new(1, 480);
+    /// smf.tracks = vec![Vec::new(), Vec::new()];
+    /// assert_eq!(smf.messages(), [Message::TrackChange]);
+    /// ```
+    pub fn messages(&self) -> Vec<Message> {
+        let mut messages = Vec::new();
+        for (i, track) in self.tracks.iter().enumerate() {
+            if i > 0 {
+                messages.push(Message::TrackChange);
+            }
+            messages.extend(track.iter().cloned());
+        }
+        messages
+    }
+    /// Converts tracks to SMF format 0 (all tracks merged into one) or 1 (format 0 track split into a
+    /// conductor track of meta and system exclusive events and a track for each channel).
+    ///
+    /// Tracks aren't changed if the format is already the same, and other formats are only set.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use ghakuf::smf::Smf;
+    /// use std::path;
+    ///
+    /// let mut smf = Smf::read(path::Path::new("tests/test.mid")).unwrap();
+    /// let length = smf.length();
+    /// smf.set_format(0);
+    /// assert_eq!(smf.tracks.len(), 1);
+    /// smf.set_format(1);
+    /// assert_eq!(smf.format, 1);
+    /// assert_eq!(smf.length(), length);
+    /// ```
+    pub fn set_format(&mut self, format: u16) -> &mut Smf {
+        let end = self.length();
+        match (self.format, format) {
+            (old, new) if old == new => (),
+            (_, 0) => {
+                let mut events: Vec<(u64, Message)> = self
+                    .tracks
+                    .iter()
+                    .flat_map(|track| without_end_of_track(track))
+                    .collect();
+                events.sort_by_key(|&(tick, _)| tick);
+                self.tracks = vec![with_end_of_track(events, end)];
+            }
+            (0, 1) => {
+                let mut tracks: Vec<Vec<(u64, Message)>> = vec![Vec::new(); 17];
+                for (tick, message) in self
+                    .tracks
+                    .iter()
+                    .flat_map(|track| without_end_of_track(track))
+                {
+                    let index = match message {
+                        Message::MidiEvent { ref event, .. } => {
+                            (event.status_byte() & 0x0f) as usize + 1
+                        }
+                        _ => 0,
+                    };
+                    tracks[index].push((tick, message));
+                }
+                self.tracks = tracks
+                    .into_iter()
+                    .enumerate()
+                    .filter(|&(i, ref events)| i == 0 || !events.is_empty())
+                    .map(|(_, events)| with_end_of_track(events, end))
+                    .collect();
+            }
+            _ => (),
+        }
+        self.format = format;
+        self
+    }
+    /// Applies Transform to all tracks.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use ghakuf::messages::*;
+    /// use ghakuf::smf::Smf;
+    /// use ghakuf::transform::ChannelMap;
+    ///
+    /// let mut smf = Smf::new(0, 480);
+    /// smf.tracks.push(vec![
+    ///     Message::MidiEvent { delta_time: 0, event: MidiEvent::ProgramChange { ch: 0, program: 1 } },
+    /// ]);
+    /// smf.transform(ChannelMap::new().remap(0, 2));
+    /// assert_eq!(
+    ///     smf.tracks[0][0],
+    ///     Message::MidiEvent { delta_time: 0, event: MidiEvent::ProgramChange { ch: 2, program: 1 } }
+    /// );
+    /// ```
+    pub fn transform(&mut self, transform: &dyn Transform) -> &mut Smf {
+        for track in &mut self.tracks {
+            *track = transform.apply(track);
+        }
+        self
+    }
+    /// Normalises all tracks into canonical form (see `Normalise`).
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use ghakuf::messages::*;
+    /// use ghakuf::smf::Smf;
+    /// use ghakuf::transform::Normalise;
+    ///
+    /// let mut smf = Smf::new(0, 480);
+    /// smf.tracks.push(vec![
+    ///     Message::MidiEvent { delta_time: 0, event: MidiEvent::NoteOn { ch: 0, note: 60, velocity: 100 } },
+    ///     Message::MidiEvent { delta_time: 480, event: MidiEvent::NoteOff { ch: 0, note: 60, velocity: 64 } },
+    /// ]);
+    /// smf.normalise(&Normalise::new());
+    /// assert_eq!(smf.tracks[0].len(), 3);
+    /// assert_eq!(
+    ///     smf.tracks[0][1],
+    ///     Message::MidiEvent { delta_time: 480, event: MidiEvent::NoteOn { ch: 0, note: 60, velocity: 0 } }
+    /// );
+    /// ```
+    pub fn normalise(&mut self, normalise: &Normalise) -> &mut Smf {
+        for track in &mut self.tracks {
+            *track = normalise.apply(track);
+        }
+        self
+    }
+    /// Scales speed by editing SetTempo meta events, so musical length (ticks) is kept.
+    ///
+    /// Factor 2.0 plays twice as fast. SetTempo at tick 0 is added to the first track if there isn't any,
+    /// since the default tempo (120 bpm) is in effect.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use ghakuf::messages::*;
+    /// use ghakuf::smf::Smf;
+    ///
+    /// let mut smf = Smf::new(0, 480);
+    /// smf.tracks.push(vec![
+    ///     Message::MetaEvent { delta_time: 0, event: MetaEvent::EndOfTrack, data: Vec::new() },
+    /// ]);
+    /// smf.scale_tempo(2.0);
+    /// assert_eq!(
+    ///     smf.tracks[0][0],
+    ///     Message::MetaEvent { delta_time: 0, event: MetaEvent::SetTempo, data: vec![0x03, 0xd0, 0x90] }
+    /// );
+    /// ```
+    pub fn scale_tempo(&mut self, factor: f64) -> &mut Smf {
+        let mut found = false;
+        for track in &mut self.tracks {
+            let mut tick = 0;
+            for message in track.iter_mut() {
+                tick += message.delta_time() as u64;
+                if let Message::MetaEvent {
+                    event: MetaEvent::SetTempo,
+                    ref mut data,
+                    ..
+                } = *message
+                {
+                    if data.len() >= 3 {
+                        let tempo = (data[0] as u32) << 16 | (data[1] as u32) << 8 | data[2] as u32;
+                        *data = tempo_data(tempo as f64 / factor);
+                        found |= tick == 0;
+                    }
+                }
+            }
+        }
+        if !found {
+            if self.tracks.is_empty() {
+                self.tracks.push(Vec::new());
+            }
+            self.tracks[0].insert(
+                0,
+                Message::MetaEvent {
+                    delta_time: 0,
+                    event: MetaEvent::SetTempo,
+                    data: tempo_data(DEFAULT_TEMPO as f64 / factor),
+                },
+            );
+        }
+        self
+    }
+    /// Stretches time by rescaling ticks of all tracks, so tempo is kept.
+    ///
+    /// Factor 2.0 makes length twice. Absolute ticks are rounded so that errors don't build up.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use ghakuf::messages::*;
+    /// use ghakuf::smf::Smf;
+    ///
+    /// let mut smf = Smf::new(0, 480);
+    /// smf.tracks.push(vec![
+    ///     Message::MidiEvent { delta_time: 1, event: MidiEvent::NoteOn { ch: 0, note: 60, velocity: 100 } },
+    ///     Message::MidiEvent { delta_time: 1, event: MidiEvent::NoteOn { ch: 0, note: 60, velocity: 0 } },
+    ///     Message::MidiEvent { delta_time: 1, event: MidiEvent::NoteOn { ch: 0, note: 62, velocity: 100 } },
+    /// ]);
+    /// smf.stretch(1.5);
+    /// let delta_times: Vec<u32> = smf.tracks[0].iter().map(|message| message.delta_time()).collect();
+    /// assert_eq!(delta_times, [2, 1, 2]);
+    /// ```
+    pub fn stretch(&mut self, factor: f64) -> &mut Smf {
+        self.rescale(|tick| (tick as f64 * factor).round().max(0.0) as u64)
+    }
+    /// Changes time base (ticks per quarter note) and rescales ticks of all tracks, so playback is kept.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use ghakuf::messages::*;
+    /// use ghakuf::smf::Smf;
+    ///
+    /// let mut smf = Smf::new(0, 96);
+    /// smf.tracks.push(vec![
+    ///     Message::MidiEvent { delta_time: 24, event: MidiEvent::NoteOn { ch: 0, note: 60, velocity: 100 } },
+    /// ]);
+    /// smf.set_time_base(480);
+    /// assert_eq!(smf.time_base, 480);
+    /// assert_eq!(smf.tracks[0][0].delta_time(), 120);
+    /// ```
+    pub fn set_time_base(&mut self, time_base: u16) -> &mut Smf {
+        let (old, new) = (self.time_base.max(1) as u64, time_base as u64);
+        self.time_base = time_base;
+        self.rescale(|tick| (tick * new + old / 2) / old)
+    }
+    /// Returns TempoMap of SetTempo meta events in all tracks.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use ghakuf::smf::Smf;
+    /// use std::path;
+    ///
+    /// let smf = Smf::read(path::Path::new("tests/test.mid")).unwrap();
+    /// assert_eq!(smf.tempo_map().tempo(0), 588_235);
+    /// ```
+    pub fn tempo_map(&self) -> TempoMap {
+        let mut tempo_map = TempoMap::new(self.time_base);
+        self.replay(&mut tempo_map);
+        tempo_map
+    }
+    /// Returns MeterMap of TimeSignature meta events in all tracks.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use ghakuf::smf::Smf;
+    /// use ghakuf::timing::Meter;
+    ///
+    /// assert_eq!(Smf::new(0, 480).meter_map().meter(0), Meter::new(4, 4));
+    /// ```
+    pub fn meter_map(&self) -> MeterMap {
+        let mut meter_map = MeterMap::new(self.time_base);
+        self.replay(&mut meter_map);
+        meter_map
+    }
+    /// Returns length in ticks, the end of the longest track.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use ghakuf::messages::*;
+    /// use ghakuf::smf::Smf;
+    ///
+    /// let mut smf = Smf::new(1, 480);
+    /// smf.tracks.push(vec![
+    ///     Message::MetaEvent { delta_time: 1920, event: MetaEvent::EndOfTrack, data: Vec::new() },
+    /// ]);
+    /// smf.tracks.push(vec![
+    ///     Message::MetaEvent { delta_time: 960, event: MetaEvent::EndOfTrack, data: Vec::new() },
+    /// ]);
+    /// assert_eq!(smf.length(), 1920);
+    /// ```
+    pub fn length(&self) -> u64 {
+        self.tracks
+            .iter()
+            .map(|track| {
+                track
+                    .iter()
+                    .map(|message| message.delta_time() as u64)
+                    .sum()
+            })
+            .max()
+            .unwrap_or(0)
+    }
+    /// Appends other Smf to play after this one, aligned to the next bar line if `align` is true.
+    ///
+    /// Other is rescaled to this time base, and track `n` of other is appended to track `n`. Tempo and
+    /// time signature are reset to the defaults at the junction if other doesn't set them at its head.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use ghakuf::messages::*;
+    /// use ghakuf::smf::Smf;
+    ///
+    /// let mut smf = Smf::new(0, 480);
+    /// smf.tracks.push(vec![
+    ///     Message::MidiEvent { delta_time: 0, event: MidiEvent::NoteOn { ch: 0, note: 60, velocity: 100 } },
+    ///     Message::MidiEvent { delta_time: 480, event: MidiEvent::NoteOn { ch: 0, note: 60, velocity: 0 } },
+    ///     Message::MetaEvent { delta_time: 0, event: MetaEvent::EndOfTrack, data: Vec::new() },
+    /// ]);
+    /// let other = smf.clone();
+    /// smf.append(&other, true);
+    /// assert_eq!(smf.length(), 1920 + 480);
+    /// assert_eq!(smf.tracks[0][2].delta_time(), 1440);
+    /// ```
+    pub fn append(&mut self, other: &Smf, align: bool) -> &mut Smf {
+        let other = self.conform(other);
+        let mut offset = self.length();
+        let meter_map = self.meter_map();
+        if align {
+            let (bar, beat, tick) = meter_map.position(offset);
+            if beat != 1 || tick != 0 {
+                offset = meter_map.tick(bar + 1, 1, 0);
+            }
+        }
+        let mut head = Vec::new();
+        if self.tempo_map().tempo(offset) != DEFAULT_TEMPO && !other.has_head(MetaEvent::SetTempo) {
+            head.push((offset, tempo_message(DEFAULT_TEMPO as f64)));
+        }
+        if meter_map.meter(offset) != Meter::default() && !other.has_head(MetaEvent::TimeSignature)
+        {
+            head.push((
+                offset,
+                Message::MetaEvent {
+                    delta_time: 0,
+                    event: MetaEvent::TimeSignature,
+                    data: vec![0x04, 0x02, 0x18, 0x08],
+                },
+            ));
+        }
+        let end = offset + other.length();
+        let tracks = self.tracks.len().max(other.tracks.len());
+        self.tracks.resize(tracks, Vec::new());
+        for (i, track) in self.tracks.iter_mut().enumerate() {
+            let mut events = without_end_of_track(track);
+            if i == 0 {
+                events.append(&mut head);
+            }
+            if let Some(other) = other.tracks.get(i) {
+                events.extend(
+                    without_end_of_track(other)
+                        .into_iter()
+                        .map(|(tick, message)| (tick + offset, message)),
+                );
+            }
+            *track = with_end_of_track(events, end);
+        }
+        if tracks > 1 && self.format == 0 {
+            self.format = 1;
+        }
+        self
+    }
+    /// Overlays other Smf to play in parallel with this one.
+    ///
+    /// Other is rescaled to this time base and retimed to this tempo map (SetTempo of other is dropped),
+    /// so that its events keep their time in seconds. Channels of other clashing with this one are
+    /// handled by ChannelPolicy, and channels still shared by both (percussion channel, all clashing
+    /// channels with `ChannelPolicy::Keep`, or clashing channels left when no channel is free) are
+    /// returned.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use ghakuf::messages::*;
+    /// use ghakuf::smf::{ChannelPolicy, Overlay, Smf};
+    ///
+    /// let mut smf = Smf::new(0, 480);
+    /// smf.tracks.push(vec![
+    ///     Message::MidiEvent { delta_time: 0, event: MidiEvent::ProgramChange { ch: 0, program: 0 } },
+    ///     Message::MetaEvent { delta_time: 0, event: MetaEvent::EndOfTrack, data: Vec::new() },
+    /// ]);
+    /// let mut other = Smf::new(0, 96);
+    /// other.tracks.push(vec![
+    ///     Message::MidiEvent { delta_time: 96, event: MidiEvent::ProgramChange { ch: 0, program: 40 } },
+    ///     Message::MetaEvent { delta_time: 0, event: MetaEvent::EndOfTrack, data: Vec::new() },
+    /// ]);
+    /// assert!(smf.overlay(&other, Overlay::Tracks, ChannelPolicy::Remap).is_empty());
+    /// assert_eq!(smf.format, 1);
+    /// assert_eq!(
+    ///     smf.tracks[1][0],
+    ///     Message::MidiEvent { delta_time: 480, event: MidiEvent::ProgramChange { ch: 1, program: 40 } }
+    /// );
+    /// ```
+    pub fn overlay(&mut self, other: &Smf, overlay: Overlay, channels: ChannelPolicy) -> Vec<u8> {
+        let mut other = self.conform(other);
+        let (tempo_map, other_tempo_map) = (self.tempo_map(), other.tempo_map());
+        for track in &mut other.tracks {
+            let events: Vec<(u64, Message)> = absolute(track)
+                .into_iter()
+                .filter(|(_, message)| !is_meta(message, MetaEvent::SetTempo))
+                .map(|(tick, message)| {
+                    if tempo_map.tempos() == other_tempo_map.tempos() {
+                        (tick, message)
+                    } else {
+                        (tempo_map.tick(other_tempo_map.seconds(tick)), message)
+                    }
+                })
+                .collect();
+            *track = relative(&events);
+        }
+        let used = self.channels();
+        if channels == ChannelPolicy::Remap {
+            // channels used by other are taken too, or moved events would join another part of other
+            let other_used = other.channels();
+            let mut free = (0..16).filter(|&ch| {
+                ch != gm::PERCUSSION_CHANNEL && !used[ch as usize] && !other_used[ch as usize]
+            });
+            let mut channel_map = ChannelMap::new();
+            for (ch, &clash) in other.channels().iter().enumerate() {
+                if clash && used[ch] && ch as u8 != gm::PERCUSSION_CHANNEL {
+                    if let Some(free) = free.next() {
+                        channel_map.remap(ch as u8, free);
+                    }
+                }
+            }
+            other.transform(&channel_map);
+        }
+        let clashes = other
+            .channels()
+            .iter()
+            .enumerate()
+            .filter(|&(ch, &clash)| clash && used[ch])
+            .map(|(ch, _)| ch as u8)
+            .collect();
+        match overlay {
+            Overlay::Tracks => {
+                self.tracks.append(&mut other.tracks);
+                if self.tracks.len() > 1 && self.format == 0 {
+                    self.format = 1;
+                }
+            }
+            Overlay::Merge => {
+                let end = self.length().max(other.length());
+                let tracks = self.tracks.len().max(other.tracks.len());
+                self.tracks.resize(tracks, Vec::new());
+                for (i, track) in self.tracks.iter_mut().enumerate() {
+                    let mut events = without_end_of_track(track);
+                    if let Some(other) = other.tracks.get(i) {
+                        events.append(&mut without_end_of_track(other));
+                    }
+                    events.sort_by_key(|&(tick, _)| tick);
+                    *track = with_end_of_track(events, end);
+                }
+            }
+        }
+        clashes
+    }
+    /// Cuts out the range [start, end) in ticks into a new Smf starting at tick 0.
+    ///
+    /// Tempo, time signature, key signature, track name and MIDI port in effect at start and controller,
+    /// program, RPN/NRPN, pitch bend and channel pressure state at start (see `ChannelState::chase`) are
+    /// put at tick 0 of each track. Notes crossing start or end are truncated to the range or dropped,
+    /// and EndOfTrack is put at the end of the range.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use ghakuf::messages::*;
+    /// use ghakuf::smf::{Boundary, Smf};
+    ///
+    /// let mut smf = Smf::new(0, 480);
+    /// smf.tracks.push(vec![
+    ///     Message::MidiEvent { delta_time: 0, event: MidiEvent::ProgramChange { ch: 0, program: 5 } },
+    ///     Message::MidiEvent { delta_time: 0, event: MidiEvent::NoteOn { ch: 0, note: 60, velocity: 100 } },
+    ///     Message::MidiEvent { delta_time: 960, event: MidiEvent::NoteOff { ch: 0, note: 60, velocity: 0 } },
+    ///     Message::MetaEvent { delta_time: 0, event: MetaEvent::EndOfTrack, data: Vec::new() },
+    /// ]);
+    /// let slice = smf.slice(480, 1920, Boundary::Truncate);
+    /// assert_eq!(
+    ///     slice.tracks[0],
+    ///     vec![
+    ///         Message::MidiEvent { delta_time: 0, event: MidiEvent::ProgramChange { ch: 0, program: 5 } },
+    ///         Message::MidiEvent { delta_time: 0, event: MidiEvent::NoteOn { ch: 0, note: 60, velocity: 100 } },
+    ///         Message::MidiEvent { delta_time: 480, event: MidiEvent::NoteOff { ch: 0, note: 60, velocity: 0 } },
+    ///         Message::MetaEvent { delta_time: 960, event: MetaEvent::EndOfTrack, data: Vec::new() },
+    ///     ]
+    /// );
+    /// assert_eq!(smf.slice(480, 1920, Boundary::Drop).tracks[0].len(), 2);
+    /// ```
+    pub fn slice(&self, start: u64, end: u64, boundary: Boundary) -> Smf {
+        let mut smf = Smf::new(self.format, self.time_base);
+        smf.tracks = self
+            .tracks
+            .iter()
+            .map(|track| slice_track(track, start, end.max(start), boundary))
+            .collect();
+        smf
+    }
+    /// Cuts out the range [start, end) in seconds into a new Smf starting at tick 0. See `slice`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use ghakuf::smf::{Boundary, Smf};
+    /// use std::path;
+    ///
+    /// let smf = Smf::read(path::Path::new("tests/test.mid")).unwrap();
+    /// let slice = smf.slice_seconds(0.5, 1.0, Boundary::Truncate);
+    /// assert_eq!(slice.tracks.len(), smf.tracks.len());
+    /// ```
+    pub fn slice_seconds(&self, start: f64, end: f64, boundary: Boundary) -> Smf {
+        let tempo_map = self.tempo_map();
+        self.slice(tempo_map.tick(start), tempo_map.tick(end), boundary)
+    }
+    fn push(&mut self, message: Message) {
+        if self.tracks.is_empty() {
+            self.tracks.push(Vec::new());
+        }
+        self.tracks.last_mut().unwrap().push(message);
+    }
+    fn replay(&self, handler: &mut dyn Handler) {
+        handler.header(self.format, self.tracks.len() as u16, self.time_base);
+        for track in &self.tracks {
+            handler.track_change();
+            for message in track {
+                match *message {
+                    Message::MetaEvent {
+                        delta_time,
+                        ref event,
+                        ref data,
+                    } => handler.meta_event(delta_time, event, data),
+                    Message::MidiEvent {
+                        delta_time,
+                        ref event,
+                    } => handler.midi_event(delta_time, event),
+                    Message::SysExEvent {
+                        delta_time,
+                        ref event,
+                        ref data,
+                    } => handler.sys_ex_event(delta_time, event, data),
+                    Message::TrackChange => (),
+                }
+            }
+        }
+    }
+    fn conform(&mut self, other: &Smf) -> Smf {
+        if self.tracks.is_empty() && self.time_base == 0 {
+            self.time_base = other.time_base;
+        }
+        let mut other = other.clone();
+        if other.time_base != self.time_base {
+            other.set_time_base(self.time_base);
+        }
+        other
+    }
+    fn has_head(&self, event: MetaEvent) -> bool {
+        self.tracks.iter().any(|track| {
+            track
+                .iter()
+                .take_while(|message| message.delta_time() == 0)
+                .any(|message| is_meta(message, event.clone()))
+        })
+    }
+    fn channels(&self) -> [bool; 16] {
+        let mut channels = [false; 16];
+        for message in self.tracks.iter().flatten() {
+            match *message {
+                Message::MidiEvent {
+                    event: MidiEvent::Unknown { .. },
+                    ..
+                } => (),
+                Message::MidiEvent { ref event, .. } => {
+                    channels[(event.status_byte() & 0x0f) as usize] = true
+                }
+                _ => (),
+            }
+        }
+        channels
+    }
+    fn rescale<F: Fn(u64) -> u64>(&mut self, scale: F) -> &mut Smf {
+        for track in &mut self.tracks {
+            let events: Vec<(u64, Message)> = absolute(track)
+                .into_iter()
+                .map(|(tick, message)| (scale(tick), message))
+                .collect();
+            *track = relative(&events);
+        }
+        self
+    }
+}
+impl Handler for Smf {
+    fn header(&mut self, format: u16, _: u16, time_base: u16) {
+        self.format = format;
+        self.time_base = time_base;
+    }
+    fn meta_event(&mut self, delta_time: u32, event: &MetaEvent, data: &Vec<u8>) {
+        self.push(Message::MetaEvent {
+            delta_time,
+            event: event.clone(),
+            data: data.clone(),
+        });
+    }
+    fn midi_event(&mut self, delta_time: u32, event: &MidiEvent) {
+        self.push(Message::MidiEvent {
+            delta_time,
+            event: event.clone(),
+        });
+    }
+    fn sys_ex_event(&mut self, delta_time: u32, event: &SysExEvent, data: &Vec<u8>) {
+        self.push(Message::SysExEvent {
+            delta_time,
+            event: event.clone(),
+            data: data.clone(),
+        });
+    }
+    fn track_change(&mut self) {
+        self.tracks.push(Vec::new());
+    }
+}
+
+/// An enum representing how notes crossing boundaries of range are treated.
+#[derive(PartialEq, Eq, Hash, Clone, Copy, Debug)]
+pub enum Boundary {
+    /// Note is truncated to the range.
+    Truncate,
+    /// Note is dropped.
+    Drop,
+}
+
+struct Sounding {
+    // index of note on in the range, or None if it has started before the range
+    index: Option<usize>,
+    velocity: u8,
+}
+
+fn slice_track(track: &[Message], start: u64, end: u64, boundary: Boundary) -> Vec<Message> {
+    let mut state = ChannelState::new();
+    let mut metas: Vec<Message> = Vec::new();
+    let mut sounding: BTreeMap<(u8, u8), VecDeque<Sounding>> = BTreeMap::new();
+    let mut head: Option<Vec<(u64, Message)>> = None;
+    let mut body: Vec<Option<(u64, Message)>> = Vec::new();
+    for (tick, message) in absolute(track) {
+        if tick >= end {
+            break;
+        }
+        if tick < start {
+            match message {
+                Message::MidiEvent { event, .. } => {
+                    match event {
+                        MidiEvent::NoteOn { ch, note, velocity } if velocity > 0 => {
+                            sounding.entry((ch, note)).or_default().push_back(Sounding {
+                                index: None,
+                                velocity,
+                            })
+                        }
+                        MidiEvent::NoteOn { ch, note, .. }
+                        | MidiEvent::NoteOff { ch, note, .. } => {
+                            if let Some(notes) = sounding.get_mut(&(ch, note)) {
+                                notes.pop_front();
+                            }
+                        }
+                        _ => (),
+                    }
+                    state.push(tick, event);
+                }
+                Message::MetaEvent { ref event, .. } if chased(event) => {
+                    metas.retain(|meta| match *meta {
+                        Message::MetaEvent {
+                            event: ref other, ..
+                        } => other != event,
+                        _ => true,
+                    });
+                    let mut message = message.clone();
+                    message.set_delta_time(0);
+                    metas.push(message);
+                }
+                _ => (),
+            }
+            continue;
+        }
+        if head.is_none() {
+            head = Some(slice_head(&metas, &state, &sounding, start, boundary));
+        }
+        let tick = tick - start;
+        match message {
+            Message::MetaEvent {
+                event: MetaEvent::EndOfTrack,
+                ..
+            } => (),
+            Message::MidiEvent {
+                event: MidiEvent::NoteOn { ch, note, velocity },
+                ..
+            } if velocity > 0 => {
+                sounding.entry((ch, note)).or_default().push_back(Sounding {
+                    index: Some(body.len()),
+                    velocity,
+                });
+                body.push(Some((tick, message)));
+            }
+            Message::MidiEvent {
+                event: MidiEvent::NoteOn { ch, note, .. },
+                ..
+            }
+            | Message::MidiEvent {
+                event: MidiEvent::NoteOff { ch, note, .. },
+                ..
+            } => {
+                let on = sounding
+                    .get_mut(&(ch, note))
+                    .and_then(|notes| notes.pop_front());
+                match on {
+                    Some(Sounding { index: None, .. }) if boundary == Boundary::Drop => (),
+                    _ => body.push(Some((tick, message))),
+                }
+            }
+            _ => body.push(Some((tick, message))),
+        }
+    }
+    let mut events = match head {
+        Some(head) => head,
+        None => slice_head(&metas, &state, &sounding, start, boundary),
+    };
+    let length = end - start;
+    let mut offs = Vec::new();
+    for (&(ch, note), notes) in &sounding {
+        for sounding in notes {
+            match (boundary, sounding.index) {
+                (Boundary::Truncate, _) => offs.push((
+                    length,
+                    Message::MidiEvent {
+                        delta_time: 0,
+                        event: MidiEvent::NoteOff {
+                            ch,
+                            note,
+                            velocity: 0,
+                        },
+                    },
+                )),
+                (Boundary::Drop, Some(index)) => body[index] = None,
+                (Boundary::Drop, None) => (),
+            }
+        }
+    }
+    events.extend(body.into_iter().flatten());
+    events.append(&mut offs);
+    events.push((
+        length,
+        Message::MetaEvent {
+            delta_time: 0,
+            event: MetaEvent::EndOfTrack,
+            data: Vec::new(),
+        },
+    ));
+    relative(&events)
+}
+
+fn slice_head(
+    metas: &[Message],
+    state: &ChannelState,
+    sounding: &BTreeMap<(u8, u8), VecDeque<Sounding>>,
+    start: u64,
+    boundary: Boundary,
+) -> Vec<(u64, Message)> {
+    let mut head: Vec<Message> = metas.to_vec();
+    head.append(&mut state.chase(start, false));
+    if boundary == Boundary::Truncate {
+        for (&(ch, note), notes) in sounding {
+            for sounding in notes {
+                head.push(Message::MidiEvent {
+                    delta_time: 0,
+                    event: MidiEvent::NoteOn {
+                        ch,
+                        note,
+                        velocity: sounding.velocity,
+                    },
+                });
+            }
+        }
+    }
+    head.into_iter().map(|message| (0, message)).collect()
+}
+
+fn chased(event: &MetaEvent) -> bool {
+    matches!(
+        *event,
+        MetaEvent::SetTempo
+            | MetaEvent::TimeSignature
+            | MetaEvent::KeySignature
+            | MetaEvent::SequenceOrTrackName
+            | MetaEvent::MidiPort
+    )
+}
+
+/// An enum representing how Smf::overlay puts tracks of other Smf.
+#[derive(PartialEq, Eq, Hash, Clone, Copy, Debug)]
+pub enum Overlay {
+    /// Tracks are added as extra tracks.
+    Tracks,
+    /// Track `n` is merged into track `n`.
+    Merge,
+}
+
+/// An enum representing how Smf::overlay treats channels used by both Smfs.
+#[derive(PartialEq, Eq, Hash, Clone, Copy, Debug)]
+pub enum ChannelPolicy {
+    /// Channels are kept as they are.
+    Keep,
+    /// Clashing channels of other are moved to channels unused by this one (percussion channel is kept).
+    Remap,
+}
+
+fn is_meta(message: &Message, kind: MetaEvent) -> bool {
+    match *message {
+        Message::MetaEvent { ref event, .. } => *event == kind,
+        _ => false,
+    }
+}
+
+fn without_end_of_track(track: &[Message]) -> Vec<(u64, Message)> {
+    absolute(track)
+        .into_iter()
+        .filter(|(_, message)| !is_meta(message, MetaEvent::EndOfTrack))
+        .collect()
+}
+
+fn with_end_of_track(mut events: Vec<(u64, Message)>, end: u64) -> Vec<Message> {
+    events.push((
+        end,
+        Message::MetaEvent {
+            delta_time: 0,
+            event: MetaEvent::EndOfTrack,
+            data: Vec::new(),
+        },
+    ));
+    relative(&events)
+}
+
+fn tempo_message(tempo: f64) -> Message {
+    Message::MetaEvent {
+        delta_time: 0,
+        event: MetaEvent::SetTempo,
+        data: tempo_data(tempo),
+    }
+}
+
+fn tempo_data(tempo: f64) -> Vec<u8> {
+    let tempo = tempo.round().clamp(1.0, 0xff_ffff as f64) as u32;
+    vec![(tempo >> 16) as u8, (tempo >> 8) as u8, tempo as u8]
+}
+
+// ReadError of Reader borrows Reader's path, so it is rebuilt with the caller's path.
+fn rebind<'a>(err: ReadError, path: &'a path::Path) -> ReadError<'a> {
+    match err {
+        ReadError::InvalidHeaderTag { tag, .. } => ReadError::InvalidHeaderTag { tag, path },
+        ReadError::InvalidIdentifyCode { code, .. } => {
+            ReadError::InvalidIdentifyCode { code, path }
+        }
+        ReadError::InvalidTrackTag { tag, .. } => ReadError::InvalidTrackTag { tag, path },
+        ReadError::Io(err) => ReadError::Io(err),
+        ReadError::NoValidHandler => ReadError::NoValidHandler,
+        ReadError::UnknownMessageStatus { status, .. } => {
+            ReadError::UnknownMessageStatus { status, path }
+        }
+    }
+}
+
+#[cfg(test)]
+mod smf_tests {
+    use smf::*;
+    use timing::TempoMap;
+    use transform::absolute;
+    fn seconds(smf: &Smf) -> Vec<f64> {
+        let mut tempo_map = TempoMap::new(smf.time_base);
+        for (tick, message) in absolute(&smf.messages()) {
+            if let Message::MetaEvent {
+                event: MetaEvent::SetTempo,
+                ref data,
+                ..
+            } = message
+            {
+                let tempo = (data[0] as u32) << 16 | (data[1] as u32) << 8 | data[2] as u32;
+                tempo_map.push(tick, tempo);
+            }
+        }
+        absolute(&smf.messages())
+            .iter()
+            .map(|&(tick, _)| tempo_map.seconds(tick))
+            .collect()
+    }
+    fn sample() -> Smf {
+        let mut smf = Smf::new(1, 96);
+        smf.tracks.push(vec![
+            Message::MetaEvent {
+                delta_time: 0,
+                event: MetaEvent::SetTempo,
+                data: vec![0x07, 0xa1, 0x20],
+            },
+            Message::MetaEvent {
+                delta_time: 96 * 4,
+                event: MetaEvent::EndOfTrack,
+                data: Vec::new(),
+            },
+        ]);
+        smf.tracks.push(
+            (0..100)
+                .map(|i| Message::MidiEvent {
+                    delta_time: 7,
+                    event: MidiEvent::NoteOn {
+                        ch: 0,
+                        note: 60,
+                        velocity: if i % 2 == 0 { 100 } else { 0 },
+                    },
+                })
+                .collect(),
+        );
+        smf
+    }
+    #[test]
+    fn time_base_change_keeps_playback() {
+        let original = sample();
+        let mut smf = original.clone();
+        smf.set_time_base(480);
+        assert_eq!(seconds(&smf), seconds(&original));
+        assert_eq!(
+            smf.tracks[1].iter().map(|m| m.delta_time()).sum::<u32>(),
+            3500
+        );
+        smf.set_time_base(96);
+        assert_eq!(smf, original);
+    }
+    #[test]
+    fn scale_tempo_keeps_ticks() {
+        let original = sample();
+        let mut smf = original.clone();
+        smf.scale_tempo(2.0);
+        assert_eq!(smf.tracks[1], original.tracks[1]);
+        let (before, after) = (seconds(&original), seconds(&smf));
+        assert_eq!(
+            after.last(),
+            before.last().map(|seconds| seconds / 2.0).as_ref()
+        );
+        smf.stretch(1.0 / 3.0);
+        // rounding is done with absolute tick, so the last event is at round(700 / 3)
+        assert_eq!(
+            smf.tracks[1].iter().map(|m| m.delta_time()).sum::<u32>(),
+            233
+        );
+    }
+    fn midi(delta_time: u32, event: MidiEvent) -> Message {
+        Message::MidiEvent { delta_time, event }
+    }
+    fn on(ch: u8, note: u8, velocity: u8) -> MidiEvent {
+        MidiEvent::NoteOn { ch, note, velocity }
+    }
+    #[test]
+    fn slice_boundaries() {
+        let mut smf = Smf::new(0, 480);
+        smf.tracks.push(vec![
+            Message::MetaEvent {
+                delta_time: 0,
+                event: MetaEvent::SetTempo,
+                data: vec![0x07, 0xa1, 0x20],
+            },
+            Message::MetaEvent {
+                delta_time: 0,
+                event: MetaEvent::SetTempo,
+                data: vec![0x03, 0xd0, 0x90],
+            },
+            midi(
+                0,
+                MidiEvent::ControlChange {
+                    ch: 1,
+                    control: 7,
+                    data: 80,
+                },
+            ),
+            midi(0, on(1, 40, 90)),   // crosses both boundaries
+            midi(100, on(1, 50, 70)), // ends before the range
+            midi(100, on(1, 50, 0)),
+            midi(400, on(1, 60, 60)), // inside the range
+            midi(100, on(1, 60, 0)),
+            midi(100, on(1, 70, 50)), // crosses end
+            midi(1000, on(1, 70, 0)),
+            midi(0, on(1, 40, 0)),
+            Message::MetaEvent {
+                delta_time: 0,
+                event: MetaEvent::EndOfTrack,
+                data: Vec::new(),
+            },
+        ]);
+        let tempo = Message::MetaEvent {
+            delta_time: 0,
+            event: MetaEvent::SetTempo,
+            data: vec![0x03, 0xd0, 0x90],
+        };
+        let volume = midi(
+            0,
+            MidiEvent::ControlChange {
+                ch: 1,
+                control: 7,
+                data: 80,
+            },
+        );
+        let end_of_track = Message::MetaEvent {
+            delta_time: 300,
+            event: MetaEvent::EndOfTrack,
+            data: Vec::new(),
+        };
+        assert_eq!(
+            smf.slice(500, 1000, Boundary::Truncate).tracks[0],
+            vec![
+                tempo.clone(),
+                volume.clone(),
+                midi(0, on(1, 40, 90)),
+                midi(100, on(1, 60, 60)),
+                midi(100, on(1, 60, 0)),
+                midi(100, on(1, 70, 50)),
+                midi(
+                    200,
+                    MidiEvent::NoteOff {
+                        ch: 1,
+                        note: 40,
+                        velocity: 0
+                    }
+                ),
+                midi(
+                    0,
+                    MidiEvent::NoteOff {
+                        ch: 1,
+                        note: 70,
+                        velocity: 0
+                    }
+                ),
+                Message::MetaEvent {
+                    delta_time: 0,
+                    event: MetaEvent::EndOfTrack,
+                    data: Vec::new(),
+                },
+            ]
+        );
+        assert_eq!(
+            smf.slice(500, 1000, Boundary::Drop).tracks[0],
+            vec![
+                tempo,
+                volume,
+                midi(100, on(1, 60, 60)),
+                midi(100, on(1, 60, 0)),
+                end_of_track,
+            ]
+        );
+    }
+    fn end_of_track(delta_time: u32) -> Message {
+        Message::MetaEvent {
+            delta_time,
+            event: MetaEvent::EndOfTrack,
+            data: Vec::new(),
+        }
+    }
+    #[test]
+    fn append_resets_tempo() {
+        let mut smf = Smf::new(0, 480);
+        smf.tracks.push(vec![
+            tempo_message(1_000_000.0),
+            midi(0, on(0, 60, 100)),
+            midi(100, on(0, 60, 0)),
+            end_of_track(0),
+        ]);
+        let mut other = Smf::new(0, 96);
+        other
+            .tracks
+            .push(vec![midi(96, on(0, 62, 100)), end_of_track(96)]);
+        smf.append(&other, false);
+        assert_eq!(
+            smf.tracks[0],
+            vec![
+                tempo_message(1_000_000.0),
+                midi(0, on(0, 60, 100)),
+                midi(100, on(0, 60, 0)),
+                tempo_message(DEFAULT_TEMPO as f64),
+                midi(480, on(0, 62, 100)),
+                end_of_track(480),
+            ]
+        );
+        assert_eq!(smf.tempo_map().tempo(580), DEFAULT_TEMPO);
+    }
+    #[test]
+    fn overlay_retimes_and_merges() {
+        let mut smf = Smf::new(0, 480);
+        smf.tracks.push(vec![
+            tempo_message(1_000_000.0),
+            midi(0, on(9, 36, 100)),
+            midi(0, on(0, 60, 100)),
+            end_of_track(960),
+        ]);
+        let mut other = Smf::new(0, 480);
+        other.tracks.push(vec![
+            midi(0, on(9, 38, 100)),
+            midi(960, on(0, 64, 100)), // 1 second at 120 bpm
+            end_of_track(0),
+        ]);
+        assert_eq!(
+            smf.overlay(&other, Overlay::Merge, ChannelPolicy::Remap),
+            vec![9]
+        );
+        assert_eq!(smf.format, 0);
+        assert_eq!(
+            smf.tracks[0],
+            vec![
+                tempo_message(1_000_000.0),
+                midi(0, on(9, 36, 100)),
+                midi(0, on(0, 60, 100)),
+                midi(0, on(9, 38, 100)),
+                midi(480, on(1, 64, 100)),
+                end_of_track(480),
+            ]
+        );
+    }
+    #[test]
+    fn overlay_remaps_to_channels_unused_by_both() {
+        let mut smf = Smf::new(1, 480);
+        smf.tracks
+            .push(vec![midi(0, on(0, 60, 100)), end_of_track(0)]);
+        let mut other = Smf::new(1, 480);
+        other.tracks.push(vec![
+            midi(0, on(0, 64, 100)),
+            midi(0, on(1, 67, 100)),
+            end_of_track(0),
+        ]);
+        let mut overlaid = smf.clone();
+        assert!(overlaid
+            .overlay(&other, Overlay::Tracks, ChannelPolicy::Remap)
+            .is_empty());
+        assert_eq!(
+            overlaid.tracks[1],
+            vec![
+                midi(0, on(2, 64, 100)),
+                midi(0, on(1, 67, 100)),
+                end_of_track(0)
+            ]
+        );
+
+        let mut full = Smf::new(1, 480);
+        full.tracks.push(
+            (0..16)
+                .map(|ch| midi(0, on(ch, 60, 100)))
+                .chain(vec![end_of_track(0)])
+                .collect(),
+        );
+        assert_eq!(
+            full.overlay(&other, Overlay::Tracks, ChannelPolicy::Remap),
+            vec![0, 1]
+        );
+    }
+}
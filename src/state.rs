@@ -0,0 +1,367 @@
+use messages::*;
+use parameters::*;
+use reader::Handler;
+use std::collections::BTreeMap;
+
+/// A struct representing state of one MIDI channel at a tick.
+///
+/// `None` means the value has never been set on the channel (or was reset).
+///
+/// # Examples
+///
+/// ```
+/// use ghakuf::state::ChannelSnapshot;
+///
+/// let snapshot = ChannelSnapshot::new();
+/// assert_eq!(snapshot.program, None);
+/// assert!(snapshot.is_empty());
+/// ```
+#[derive(PartialEq, Clone, Debug)]
+pub struct ChannelSnapshot {
+    /// Current program (ProgramChange)
+    pub program: Option<u8>,
+    /// Current controller values indexed by control number, excluding RPN/NRPN and channel mode controls
+    pub controllers: Vec<Option<u8>>,
+    /// Current RPN/NRPN values
+    pub parameters: BTreeMap<(ParameterKind, u16), u16>,
+    /// Current pitch bend
+    pub pitch_bend: Option<i16>,
+    /// Current channel pressure
+    pub channel_pressure: Option<u8>,
+    /// Sounding notes with their note on velocity
+    pub notes: BTreeMap<u8, u8>,
+}
+impl ChannelSnapshot {
+    /// Builds ChannelSnapshot without any state.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use ghakuf::state::ChannelSnapshot;
+    ///
+    /// let snapshot: ChannelSnapshot = ChannelSnapshot::new();
+    /// ```
+    pub fn new() -> ChannelSnapshot {
+        ChannelSnapshot {
+            program: None,
+            controllers: vec![None; 128],
+            parameters: BTreeMap::new(),
+            pitch_bend: None,
+            channel_pressure: None,
+            notes: BTreeMap::new(),
+        }
+    }
+    /// Returns bank select value (CC 0 as MSB and CC 32 as LSB) if bank MSB has been set.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use ghakuf::state::ChannelSnapshot;
+    ///
+    /// let mut snapshot = ChannelSnapshot::new();
+    /// snapshot.controllers[0] = Some(121);
+    /// snapshot.controllers[32] = Some(1);
+    /// assert_eq!(snapshot.bank(), Some((121, 1)));
+    /// ```
+    pub fn bank(&self) -> Option<(u8, u8)> {
+        self.controllers[0].map(|msb| (msb, self.controllers[32].unwrap_or(0)))
+    }
+    /// Checks whether any state has been set on the channel or not.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use ghakuf::state::ChannelSnapshot;
+    ///
+    /// let mut snapshot = ChannelSnapshot::new();
+    /// snapshot.pitch_bend = Some(0);
+    /// assert!(!snapshot.is_empty());
+    /// ```
+    pub fn is_empty(&self) -> bool {
+        *self == ChannelSnapshot::new()
+    }
+    fn apply(&mut self, event: &MidiEvent, change: Option<ParameterChange>) {
+        use messages::MidiEvent::*;
+        match *event {
+            NoteOn { note, velocity, .. } if velocity > 0 => {
+                self.notes.insert(note, velocity);
+            }
+            NoteOn { note, .. } | NoteOff { note, .. } => {
+                self.notes.remove(&note);
+            }
+            ControlChange { control, data, .. } => match control {
+                6 | 38 | 96..=101 => {
+                    if let Some(change) = change {
+                        self.parameters
+                            .insert((change.kind, change.number), change.value14);
+                    }
+                }
+                120 | 123..=127 => self.notes.clear(),
+                121 => {
+                    for (control, value) in self.controllers.iter_mut().enumerate() {
+                        match control {
+                            0 | 7 | 10 | 32 | 91..=95 => (),
+                            _ => *value = None,
+                        }
+                    }
+                    self.pitch_bend = None;
+                    self.channel_pressure = None;
+                }
+                122 => (),
+                _ => self.controllers[control as usize] = Some(data),
+            },
+            ProgramChange { program, .. } => self.program = Some(program),
+            ChannelPressure { pressure, .. } => self.channel_pressure = Some(pressure),
+            PitchBendChange { data, .. } => self.pitch_bend = Some(data),
+            PolyphonicKeyPressure { .. } | Unknown { .. } => (),
+        }
+    }
+    /// Makes messages restoring this state on channel `ch`. All messages have zero delta time.
+    ///
+    /// Bank select is sent before program change, then other controllers, RPN/NRPN values,
+    /// pitch bend and channel pressure. Sounding notes are sent as note on if `notes` is true.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use ghakuf::messages::{Message, MidiEvent};
+    /// use ghakuf::state::ChannelSnapshot;
+    ///
+    /// let mut snapshot = ChannelSnapshot::new();
+    /// snapshot.program = Some(5);
+    /// assert_eq!(
+    ///     snapshot.chase(3, false),
+    ///     vec![Message::MidiEvent {
+    ///         delta_time: 0,
+    ///         event: MidiEvent::ProgramChange { ch: 3, program: 5 },
+    ///     }]
+    /// );
+    /// ```
+    pub fn chase(&self, ch: u8, notes: bool) -> Vec<Message> {
+        let ch = ch & 0x0f;
+        let mut events: Vec<MidiEvent> = Vec::new();
+        for control in &[0, 32] {
+            if let Some(data) = self.controllers[*control as usize] {
+                events.push(MidiEvent::ControlChange {
+                    ch,
+                    control: *control,
+                    data,
+                });
+            }
+        }
+        if let Some(program) = self.program {
+            events.push(MidiEvent::ProgramChange { ch, program });
+        }
+        for (control, data) in self.controllers.iter().enumerate() {
+            match (control, *data) {
+                (0, _) | (32, _) | (_, None) => (),
+                (control, Some(data)) => events.push(MidiEvent::ControlChange {
+                    ch,
+                    control: control as u8,
+                    data,
+                }),
+            }
+        }
+        let mut messages: Vec<Message> = events
+            .into_iter()
+            .map(|event| Message::MidiEvent {
+                delta_time: 0,
+                event,
+            })
+            .collect();
+        let encoder = ParameterEncoder::new();
+        for (&(kind, number), &value14) in &self.parameters {
+            messages.append(&mut encoder.encode(
+                &ParameterChange {
+                    ch,
+                    kind,
+                    number,
+                    value14,
+                },
+                0,
+            ));
+        }
+        let mut events: Vec<MidiEvent> = Vec::new();
+        if let Some(data) = self.pitch_bend {
+            events.push(MidiEvent::PitchBendChange { ch, data });
+        }
+        if let Some(pressure) = self.channel_pressure {
+            events.push(MidiEvent::ChannelPressure { ch, pressure });
+        }
+        if notes {
+            for (&note, &velocity) in &self.notes {
+                events.push(MidiEvent::NoteOn { ch, note, velocity });
+            }
+        }
+        messages.extend(events.into_iter().map(|event| Message::MidiEvent {
+            delta_time: 0,
+            event,
+        }));
+        messages
+    }
+}
+impl Default for ChannelSnapshot {
+    fn default() -> ChannelSnapshot {
+        ChannelSnapshot::new()
+    }
+}
+
+/// Channel state tracker which records MIDI events with absolute ticks.
+///
+/// ChannelState implements Handler, so you can push it to Reader directly. Ticks are counted from
+/// the head of each track, so events of all tracks are merged in absolute time.
+///
+/// # Examples
+///
+/// ```
+/// use ghakuf::messages::{Message, MidiEvent};
+/// use ghakuf::reader::Handler;
+/// use ghakuf::state::ChannelState;
+///
+/// let mut state = ChannelState::new();
+/// state.midi_event(0, &MidiEvent::ProgramChange { ch: 0, program: 24 });
+/// state.midi_event(0, &MidiEvent::NoteOn { ch: 0, note: 60, velocity: 100 });
+/// state.midi_event(480, &MidiEvent::NoteOn { ch: 0, note: 60, velocity: 0 });
+///
+/// let snapshots = state.snapshot(240);
+/// assert_eq!(snapshots[0].program, Some(24));
+/// assert_eq!(snapshots[0].notes.get(&60), Some(&100));
+/// assert!(state.snapshot(481)[0].notes.is_empty());
+/// ```
+pub struct ChannelState {
+    events: Vec<(u64, MidiEvent)>,
+    tick: u64,
+}
+impl ChannelState {
+    /// Builds ChannelState without any event.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use ghakuf::state::ChannelState;
+    ///
+    /// let state: ChannelState = ChannelState::new();
+    /// ```
+    pub fn new() -> ChannelState {
+        ChannelState {
+            events: Vec::new(),
+            tick: 0,
+        }
+    }
+    /// Pushes MIDI event at absolute tick.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use ghakuf::messages::MidiEvent;
+    /// use ghakuf::state::ChannelState;
+    ///
+    /// let mut state = ChannelState::new();
+    /// state.push(960, MidiEvent::ChannelPressure { ch: 1, pressure: 30 });
+    /// assert_eq!(state.snapshot(961)[1].channel_pressure, Some(30));
+    /// ```
+    pub fn push(&mut self, tick: u64, event: MidiEvent) {
+        self.events.push((tick, event));
+    }
+    /// Returns snapshots of 16 channels applying all events before `tick`.
+    ///
+    /// Events exactly at `tick` are not applied, because a player seeking to `tick` sends them by itself.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use ghakuf::messages::MidiEvent;
+    /// use ghakuf::state::ChannelState;
+    ///
+    /// let mut state = ChannelState::new();
+    /// state.push(100, MidiEvent::PitchBendChange { ch: 0, data: 512 });
+    /// assert_eq!(state.snapshot(100)[0].pitch_bend, None);
+    /// assert_eq!(state.snapshot(101)[0].pitch_bend, Some(512));
+    /// ```
+    pub fn snapshot(&self, tick: u64) -> Vec<ChannelSnapshot> {
+        let mut events: Vec<&(u64, MidiEvent)> = self
+            .events
+            .iter()
+            .filter(|&&(event_tick, _)| event_tick < tick)
+            .collect();
+        events.sort_by_key(|&&(event_tick, _)| event_tick);
+        let mut decoder = ParameterDecoder::new();
+        let mut snapshots = vec![ChannelSnapshot::new(); 16];
+        for &(_, event) in &events {
+            let change = decoder
+                .push(event)
+                .filter(|change| change.kind != ParameterKind::Cc14);
+            if let Some(ch) = channel(event) {
+                snapshots[ch as usize].apply(event, change);
+            }
+        }
+        snapshots
+    }
+    /// Makes messages restoring state of all channels at `tick` on a synth.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use ghakuf::messages::{Message, MidiEvent};
+    /// use ghakuf::state::ChannelState;
+    ///
+    /// let mut state = ChannelState::new();
+    /// state.push(0, MidiEvent::ControlChange { ch: 9, control: 7, data: 90 });
+    /// state.push(0, MidiEvent::NoteOn { ch: 9, note: 36, velocity: 127 });
+    /// assert_eq!(
+    ///     state.chase(10, true),
+    ///     vec![
+    ///         Message::MidiEvent {
+    ///             delta_time: 0,
+    ///             event: MidiEvent::ControlChange { ch: 9, control: 7, data: 90 },
+    ///         },
+    ///         Message::MidiEvent {
+    ///             delta_time: 0,
+    ///             event: MidiEvent::NoteOn { ch: 9, note: 36, velocity: 127 },
+    ///         },
+    ///     ]
+    /// );
+    /// ```
+    pub fn chase(&self, tick: u64, notes: bool) -> Vec<Message> {
+        let mut messages: Vec<Message> = Vec::new();
+        for (ch, snapshot) in self.snapshot(tick).iter().enumerate() {
+            messages.append(&mut snapshot.chase(ch as u8, notes));
+        }
+        messages
+    }
+}
+impl Default for ChannelState {
+    fn default() -> ChannelState {
+        ChannelState::new()
+    }
+}
+impl Handler for ChannelState {
+    fn meta_event(&mut self, delta_time: u32, _: &MetaEvent, _: &Vec<u8>) {
+        self.tick += delta_time as u64;
+    }
+    fn midi_event(&mut self, delta_time: u32, event: &MidiEvent) {
+        self.tick += delta_time as u64;
+        let tick = self.tick;
+        self.push(tick, event.clone());
+    }
+    fn sys_ex_event(&mut self, delta_time: u32, _: &SysExEvent, _: &Vec<u8>) {
+        self.tick += delta_time as u64;
+    }
+    fn track_change(&mut self) {
+        self.tick = 0;
+    }
+}
+
+fn channel(event: &MidiEvent) -> Option<u8> {
+    use messages::MidiEvent::*;
+    match *event {
+        NoteOff { ch, .. }
+        | NoteOn { ch, .. }
+        | PolyphonicKeyPressure { ch, .. }
+        | ControlChange { ch, .. }
+        | ProgramChange { ch, .. }
+        | ChannelPressure { ch, .. }
+        | PitchBendChange { ch, .. } => Some(ch & 0x0f),
+        Unknown { .. } => None,
+    }
+}
@@ -0,0 +1,988 @@
+use messages::*;
+use std::{error, fmt};
+
+/// Roland (GS) system exclusive messages
+pub mod roland;
+/// Yamaha (XG) system exclusive messages
+pub mod yamaha;
+
+/// Sub ID of non-commercial (educational/development) system exclusive.
+pub const NON_COMMERCIAL: u8 = 0x7d;
+/// Manufacturer ID of Universal Non-Real Time system exclusive.
+pub const UNIVERSAL_NON_REAL_TIME: u8 = 0x7e;
+/// Manufacturer ID of Universal Real Time system exclusive.
+pub const UNIVERSAL_REAL_TIME: u8 = 0x7f;
+/// Device ID addressing all devices.
+pub const ALL_CALL: u8 = 0x7f;
+
+/// An enum representing manufacturer ID at the head of system exclusive data.
+///
+/// # Examples
+///
+/// ```
+/// use ghakuf::sysex::ManufacturerId;
+///
+/// let data = [0x41, 0x10, 0x42, 0x12];
+/// let (id, rest) = ManufacturerId::from_data(&data).unwrap();
+/// assert_eq!(id, ManufacturerId::Standard(0x41));
+/// assert_eq!(id.name(), Some("Roland"));
+/// assert_eq!(rest, [0x10, 0x42, 0x12]);
+/// ```
+#[derive(PartialEq, Eq, Hash, Clone, Copy, Debug)]
+pub enum ManufacturerId {
+    /// One byte ID (0x01-0x7f)
+    Standard(u8),
+    /// Three bytes ID (0x00 followed by two bytes)
+    Extended(u8, u8),
+}
+impl ManufacturerId {
+    /// Parses manufacturer ID at the head of data and returns it with the rest of data.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use ghakuf::sysex::ManufacturerId;
+    ///
+    /// let data = [0x00, 0x20, 0x29, 0x02];
+    /// let (id, rest) = ManufacturerId::from_data(&data).unwrap();
+    /// assert_eq!(id, ManufacturerId::Extended(0x20, 0x29));
+    /// assert_eq!(rest, [0x02]);
+    /// assert_eq!(ManufacturerId::from_data(&[0x00, 0x20]), None);
+    /// ```
+    pub fn from_data(data: &[u8]) -> Option<(ManufacturerId, &[u8])> {
+        match data {
+            [0x00, first, second, rest @ ..] => {
+                Some((ManufacturerId::Extended(*first, *second), rest))
+            }
+            [id, rest @ ..] if *id != 0x00 && *id < 0x80 => {
+                Some((ManufacturerId::Standard(*id), rest))
+            }
+            _ => None,
+        }
+    }
+    /// Makes binary array of manufacturer ID.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use ghakuf::sysex::ManufacturerId;
+    ///
+    /// assert_eq!(ManufacturerId::Standard(0x43).binary(), [0x43]);
+    /// assert_eq!(ManufacturerId::Extended(0x21, 0x09).binary(), [0x00, 0x21, 0x09]);
+    /// ```
+    pub fn binary(&self) -> Vec<u8> {
+        match *self {
+            ManufacturerId::Standard(id) => vec![id],
+            ManufacturerId::Extended(first, second) => vec![0x00, first, second],
+        }
+    }
+    /// Returns manufacturer name if it is well known.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use ghakuf::sysex::ManufacturerId;
+    ///
+    /// assert_eq!(ManufacturerId::Standard(0x43).name(), Some("Yamaha"));
+    /// assert_eq!(ManufacturerId::Extended(0x21, 0x09).name(), Some("Native Instruments"));
+    /// ```
+    pub fn name(&self) -> Option<&'static str> {
+        Some(match *self {
+            ManufacturerId::Standard(id) => match id {
+                0x01 => "Sequential Circuits",
+                0x04 => "Moog",
+                0x06 => "Lexicon",
+                0x07 => "Kurzweil",
+                0x0f => "Ensoniq",
+                0x10 => "Oberheim",
+                0x11 => "Apple",
+                0x18 => "E-mu",
+                0x24 => "Hohner",
+                0x29 => "PPG",
+                0x33 => "Clavia",
+                0x3e => "Waldorf",
+                0x40 => "Kawai",
+                0x41 => "Roland",
+                0x42 => "Korg",
+                0x43 => "Yamaha",
+                0x44 => "Casio",
+                0x47 => "Akai",
+                0x4c => "Sony",
+                0x51 => "Fostex",
+                0x52 => "Zoom",
+                NON_COMMERCIAL => "Non-Commercial",
+                UNIVERSAL_NON_REAL_TIME => "Universal Non-Real Time",
+                UNIVERSAL_REAL_TIME => "Universal Real Time",
+                _ => return None,
+            },
+            ManufacturerId::Extended(first, second) => match (first, second) {
+                (0x00, 0x0e) => "Alesis",
+                (0x20, 0x29) => "Novation",
+                (0x20, 0x32) => "Behringer",
+                (0x20, 0x33) => "Access",
+                (0x20, 0x3c) => "Elektron",
+                (0x20, 0x6b) => "Arturia",
+                (0x21, 0x09) => "Native Instruments",
+                _ => return None,
+            },
+        })
+    }
+}
+impl fmt::Display for ManufacturerId {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let id = self
+            .binary()
+            .iter()
+            .map(|byte| format!("{:02x}", byte))
+            .collect::<Vec<String>>()
+            .join(" ");
+        match self.name() {
+            Some(name) => write!(f, "{} ({})", name, id),
+            None => write!(f, "({})", id),
+        }
+    }
+}
+
+/// A struct representing tuning of one note by MIDI Tuning Standard.
+///
+/// Frequency is `semitone` (MIDI key number) plus `fraction` / 16384 semitone.
+///
+/// # Examples
+///
+/// ```
+/// use ghakuf::sysex::NoteTuning;
+///
+/// let tuning = NoteTuning::from_semitones(60.5);
+/// assert_eq!(tuning, NoteTuning { semitone: 60, fraction: 0x2000 });
+/// assert_eq!(tuning.semitones(), 60.5);
+/// ```
+#[derive(PartialEq, Clone, Copy, Debug)]
+pub struct NoteTuning {
+    pub semitone: u8,
+    pub fraction: u16,
+}
+impl NoteTuning {
+    /// Tuning data meaning "no change" (0x7f, 0x7f, 0x7f).
+    pub const NO_CHANGE: NoteTuning = NoteTuning {
+        semitone: 0x7f,
+        fraction: 0x3fff,
+    };
+    /// Builds NoteTuning from fractional MIDI key number (e.g. 69.0 is 440 Hz).
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use ghakuf::sysex::NoteTuning;
+    ///
+    /// assert_eq!(NoteTuning::from_semitones(69.0), NoteTuning { semitone: 69, fraction: 0 });
+    /// ```
+    pub fn from_semitones(semitones: f64) -> NoteTuning {
+        let semitones = semitones.clamp(0.0, 127.0 + 16383.0 / 16384.0);
+        let fraction = ((semitones - semitones.floor()) * 16384.0).round() as u16;
+        NoteTuning {
+            semitone: semitones.floor() as u8,
+            fraction: fraction.min(0x3fff),
+        }
+    }
+    /// Returns fractional MIDI key number.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use ghakuf::sysex::NoteTuning;
+    ///
+    /// assert_eq!(NoteTuning { semitone: 61, fraction: 0x1000 }.semitones(), 61.25);
+    /// ```
+    pub fn semitones(&self) -> f64 {
+        self.semitone as f64 + self.fraction as f64 / 16384.0
+    }
+    fn from_binary(data: &[u8]) -> NoteTuning {
+        NoteTuning {
+            semitone: data[0],
+            fraction: (data[1] as u16) << 7 | data[2] as u16,
+        }
+    }
+    fn binary(&self) -> [u8; 3] {
+        [
+            self.semitone & 0x7f,
+            (self.fraction >> 7) as u8 & 0x7f,
+            self.fraction as u8 & 0x7f,
+        ]
+    }
+}
+
+/// An enum representing Universal Non-Real Time and Real Time system exclusive messages.
+#[derive(PartialEq, Clone, Debug)]
+pub enum UniversalMessage {
+    /// GM System On (non-real time 09 01)
+    GmSystemOn,
+    /// GM System Off (non-real time 09 02)
+    GmSystemOff,
+    /// GM2 System On (non-real time 09 03)
+    Gm2SystemOn,
+    /// Master Volume (real time 04 01, 14-bit)
+    MasterVolume { volume: u16 },
+    /// Master Fine Tuning (real time 04 03, -8192 to 8191 as -100 to +100 cents)
+    MasterFineTuning { tuning: i16 },
+    /// Master Coarse Tuning (real time 04 04, -64 to 63 semitones)
+    MasterCoarseTuning { semitones: i8 },
+    /// Identity Request (non-real time 06 01)
+    IdentityRequest,
+    /// Identity Reply (non-real time 06 02)
+    IdentityReply {
+        manufacturer: ManufacturerId,
+        family: u16,
+        member: u16,
+        version: [u8; 4],
+    },
+    /// MTC Full Frame (real time 01 01, rate: 0 = 24, 1 = 25, 2 = 29.97 drop frame, 3 = 30 fps)
+    MtcFullFrame {
+        rate: u8,
+        hours: u8,
+        minutes: u8,
+        seconds: u8,
+        frames: u8,
+    },
+    /// MIDI Tuning Standard bulk tuning dump request (non-real time 08 00)
+    BulkTuningDumpRequest { program: u8 },
+    /// MIDI Tuning Standard bulk tuning dump (non-real time 08 01, 16 characters name and 128 notes)
+    BulkTuningDump {
+        program: u8,
+        name: String,
+        tunings: Vec<NoteTuning>,
+    },
+    /// MIDI Tuning Standard single note tuning change (real time 08 02, pairs of key and tuning)
+    SingleNoteTuningChange {
+        program: u8,
+        changes: Vec<(u8, NoteTuning)>,
+    },
+}
+
+/// A struct representing Universal system exclusive message addressed to a device.
+///
+/// Data of system exclusive message is the same as Reader passes to Handler, that is, bytes after
+/// status byte 0xf0 including the last 0xf7.
+///
+/// # Examples
+///
+/// ```
+/// use ghakuf::messages::{Message, SysExEvent};
+/// use ghakuf::sysex::{Universal, UniversalMessage};
+///
+/// let message = Universal::new(UniversalMessage::GmSystemOn).message(0);
+/// assert_eq!(
+///     message,
+///     Message::SysExEvent {
+///         delta_time: 0,
+///         event: SysExEvent::F0,
+///         data: vec![0x7e, 0x7f, 0x09, 0x01, 0xf7],
+///     }
+/// );
+/// assert_eq!(
+///     Universal::parse(&[0x7f, 0x10, 0x04, 0x01, 0x00, 0x40, 0xf7]).unwrap(),
+///     Universal {
+///         device_id: 0x10,
+///         message: UniversalMessage::MasterVolume { volume: 0x2000 },
+///     }
+/// );
+/// ```
+#[derive(PartialEq, Clone, Debug)]
+pub struct Universal {
+    pub device_id: u8,
+    pub message: UniversalMessage,
+}
+impl Universal {
+    /// Builds Universal message addressed to all devices.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use ghakuf::sysex::{Universal, UniversalMessage, ALL_CALL};
+    ///
+    /// let universal = Universal::new(UniversalMessage::IdentityRequest);
+    /// assert_eq!(universal.device_id, ALL_CALL);
+    /// ```
+    pub fn new(message: UniversalMessage) -> Universal {
+        Universal {
+            device_id: ALL_CALL,
+            message,
+        }
+    }
+    /// Parses system exclusive data (after 0xf0, including the last 0xf7).
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use ghakuf::sysex::{SysExError, Universal, UniversalMessage};
+    ///
+    /// let universal = Universal::parse(&[0x7f, 0x7f, 0x01, 0x01, 0x61, 0x02, 0x03, 0x04, 0xf7]);
+    /// assert_eq!(
+    ///     universal.unwrap().message,
+    ///     UniversalMessage::MtcFullFrame { rate: 3, hours: 1, minutes: 2, seconds: 3, frames: 4 }
+    /// );
+    /// assert!(Universal::parse(&[0x43, 0x10, 0x4c, 0xf7]).is_err());
+    /// ```
+    pub fn parse(data: &[u8]) -> Result<Universal, SysExError> {
+        let body = body(data)?;
+        let (id, device_id, sub_id1, sub_id2, rest) = match body {
+            [id @ UNIVERSAL_NON_REAL_TIME, device_id, sub_id1, sub_id2, rest @ ..]
+            | [id @ UNIVERSAL_REAL_TIME, device_id, sub_id1, sub_id2, rest @ ..] => {
+                (*id, *device_id, *sub_id1, *sub_id2, rest)
+            }
+            _ => {
+                return match ManufacturerId::from_data(body) {
+                    Some((UNIVERSAL_NON_REAL_TIME_ID, _)) | Some((UNIVERSAL_REAL_TIME_ID, _)) => {
+                        Err(SysExError::Truncated {
+                            expected: 4,
+                            found: body.len(),
+                        })
+                    }
+                    Some((id, _)) => Err(SysExError::NotUniversal { id }),
+                    None => Err(SysExError::Truncated {
+                        expected: 4,
+                        found: body.len(),
+                    }),
+                };
+            }
+        };
+        let expect = |len: usize| -> Result<(), SysExError> {
+            if rest.len() < len {
+                Err(SysExError::Truncated {
+                    expected: len + 4,
+                    found: rest.len() + 4,
+                })
+            } else {
+                Ok(())
+            }
+        };
+        use sysex::UniversalMessage::*;
+        let message = match (id, sub_id1, sub_id2) {
+            (UNIVERSAL_NON_REAL_TIME, 0x09, 0x01) => GmSystemOn,
+            (UNIVERSAL_NON_REAL_TIME, 0x09, 0x02) => GmSystemOff,
+            (UNIVERSAL_NON_REAL_TIME, 0x09, 0x03) => Gm2SystemOn,
+            (UNIVERSAL_REAL_TIME, 0x04, 0x01) => {
+                expect(2)?;
+                MasterVolume {
+                    volume: (rest[1] as u16) << 7 | rest[0] as u16,
+                }
+            }
+            (UNIVERSAL_REAL_TIME, 0x04, 0x03) => {
+                expect(2)?;
+                MasterFineTuning {
+                    tuning: ((rest[1] as u16) << 7 | rest[0] as u16) as i16 - 8192,
+                }
+            }
+            (UNIVERSAL_REAL_TIME, 0x04, 0x04) => {
+                expect(2)?;
+                MasterCoarseTuning {
+                    semitones: rest[1] as i8 - 64,
+                }
+            }
+            (UNIVERSAL_NON_REAL_TIME, 0x06, 0x01) => IdentityRequest,
+            (UNIVERSAL_NON_REAL_TIME, 0x06, 0x02) => {
+                let (manufacturer, rest) =
+                    ManufacturerId::from_data(rest).ok_or(SysExError::Truncated {
+                        expected: 5,
+                        found: body.len(),
+                    })?;
+                if rest.len() < 8 {
+                    return Err(SysExError::Truncated {
+                        expected: body.len() - rest.len() + 8,
+                        found: body.len(),
+                    });
+                }
+                IdentityReply {
+                    manufacturer,
+                    family: (rest[1] as u16) << 7 | rest[0] as u16,
+                    member: (rest[3] as u16) << 7 | rest[2] as u16,
+                    version: [rest[4], rest[5], rest[6], rest[7]],
+                }
+            }
+            (UNIVERSAL_REAL_TIME, 0x01, 0x01) => {
+                expect(4)?;
+                MtcFullFrame {
+                    rate: rest[0] >> 5 & 0x03,
+                    hours: rest[0] & 0x1f,
+                    minutes: rest[1],
+                    seconds: rest[2],
+                    frames: rest[3],
+                }
+            }
+            (UNIVERSAL_NON_REAL_TIME, 0x08, 0x00) => {
+                expect(1)?;
+                BulkTuningDumpRequest { program: rest[0] }
+            }
+            (UNIVERSAL_NON_REAL_TIME, 0x08, 0x01) => {
+                expect(1 + 16 + 128 * 3 + 1)?;
+                let expected = checksum_xor(&body[..body.len() - 1]);
+                let found = body[body.len() - 1];
+                if expected != found {
+                    warn!(
+                        "bulk tuning dump checksum mismatch: expected {:x}, found {:x}",
+                        expected, found
+                    );
+                    return Err(SysExError::ChecksumMismatch { expected, found });
+                }
+                BulkTuningDump {
+                    program: rest[0],
+                    name: rest[1..17].iter().map(|&c| c as char).collect(),
+                    tunings: rest[17..17 + 128 * 3]
+                        .chunks(3)
+                        .map(NoteTuning::from_binary)
+                        .collect(),
+                }
+            }
+            (UNIVERSAL_REAL_TIME, 0x08, 0x02) => {
+                expect(2)?;
+                let count = rest[1] as usize;
+                expect(2 + count * 4)?;
+                SingleNoteTuningChange {
+                    program: rest[0],
+                    changes: rest[2..2 + count * 4]
+                        .chunks(4)
+                        .map(|change| (change[0], NoteTuning::from_binary(&change[1..])))
+                        .collect(),
+                }
+            }
+            _ => {
+                return Err(SysExError::Unsupported {
+                    id: ManufacturerId::Standard(id),
+                    sub_id1,
+                    sub_id2,
+                })
+            }
+        };
+        Ok(Universal { device_id, message })
+    }
+    /// Makes system exclusive data (after 0xf0, including the last 0xf7).
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use ghakuf::sysex::{Universal, UniversalMessage};
+    ///
+    /// let universal = Universal {
+    ///     device_id: 0x10,
+    ///     message: UniversalMessage::MasterCoarseTuning { semitones: -2 },
+    /// };
+    /// assert_eq!(universal.binary(), [0x7f, 0x10, 0x04, 0x04, 0x00, 0x3e, 0xf7]);
+    /// ```
+    pub fn binary(&self) -> Vec<u8> {
+        use sysex::UniversalMessage::*;
+        let (id, sub_id1, sub_id2, mut data): (u8, u8, u8, Vec<u8>) = match self.message {
+            GmSystemOn => (UNIVERSAL_NON_REAL_TIME, 0x09, 0x01, vec![]),
+            GmSystemOff => (UNIVERSAL_NON_REAL_TIME, 0x09, 0x02, vec![]),
+            Gm2SystemOn => (UNIVERSAL_NON_REAL_TIME, 0x09, 0x03, vec![]),
+            MasterVolume { volume } => (UNIVERSAL_REAL_TIME, 0x04, 0x01, lsb_msb(volume).to_vec()),
+            MasterFineTuning { tuning } => (
+                UNIVERSAL_REAL_TIME,
+                0x04,
+                0x03,
+                lsb_msb((tuning as i32 + 8192).clamp(0, 0x3fff) as u16).to_vec(),
+            ),
+            MasterCoarseTuning { semitones } => (
+                UNIVERSAL_REAL_TIME,
+                0x04,
+                0x04,
+                vec![0x00, (semitones as i32 + 64).clamp(0, 0x7f) as u8],
+            ),
+            IdentityRequest => (UNIVERSAL_NON_REAL_TIME, 0x06, 0x01, vec![]),
+            IdentityReply {
+                ref manufacturer,
+                family,
+                member,
+                version,
+            } => {
+                let mut data = manufacturer.binary();
+                data.extend_from_slice(&lsb_msb(family));
+                data.extend_from_slice(&lsb_msb(member));
+                data.extend(version.iter().map(|byte| byte & 0x7f));
+                (UNIVERSAL_NON_REAL_TIME, 0x06, 0x02, data)
+            }
+            MtcFullFrame {
+                rate,
+                hours,
+                minutes,
+                seconds,
+                frames,
+            } => (
+                UNIVERSAL_REAL_TIME,
+                0x01,
+                0x01,
+                vec![
+                    (rate & 0x03) << 5 | (hours & 0x1f),
+                    minutes,
+                    seconds,
+                    frames,
+                ],
+            ),
+            BulkTuningDumpRequest { program } => {
+                (UNIVERSAL_NON_REAL_TIME, 0x08, 0x00, vec![program])
+            }
+            BulkTuningDump {
+                program,
+                ref name,
+                ref tunings,
+            } => {
+                let mut data = vec![program];
+                let mut name: Vec<u8> = name
+                    .chars()
+                    .map(|c| if c.is_ascii() { c as u8 } else { b'?' })
+                    .collect();
+                name.resize(16, b' ');
+                data.append(&mut name);
+                for i in 0..128 {
+                    data.extend_from_slice(
+                        &tunings.get(i).unwrap_or(&NoteTuning::NO_CHANGE).binary(),
+                    );
+                }
+                (UNIVERSAL_NON_REAL_TIME, 0x08, 0x01, data)
+            }
+            SingleNoteTuningChange {
+                program,
+                ref changes,
+            } => {
+                let mut data = vec![program, changes.len().min(0x7f) as u8];
+                for &(key, ref tuning) in changes.iter().take(0x7f) {
+                    data.push(key & 0x7f);
+                    data.extend_from_slice(&tuning.binary());
+                }
+                (UNIVERSAL_REAL_TIME, 0x08, 0x02, data)
+            }
+        };
+        let mut binary = vec![id, self.device_id & 0x7f, sub_id1, sub_id2];
+        binary.append(&mut data);
+        if let BulkTuningDump { .. } = self.message {
+            let checksum = checksum_xor(&binary);
+            binary.push(checksum);
+        }
+        binary.push(0xf7);
+        binary
+    }
+    /// Makes Message::SysExEvent of this message.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use ghakuf::messages::{Message, SysExEvent};
+    /// use ghakuf::sysex::{Universal, UniversalMessage};
+    ///
+    /// let message = Universal::new(UniversalMessage::Gm2SystemOn).message(120);
+    /// if let Message::SysExEvent { delta_time, event, data } = message {
+    ///     assert_eq!(delta_time, 120);
+    ///     assert_eq!(event, SysExEvent::F0);
+    ///     assert_eq!(data, [0x7e, 0x7f, 0x09, 0x03, 0xf7]);
+    /// }
+    /// ```
+    pub fn message(&self, delta_time: u32) -> Message {
+        Message::SysExEvent {
+            delta_time,
+            event: SysExEvent::F0,
+            data: self.binary(),
+        }
+    }
+}
+
+/// Splits system exclusive message (F0) into F0 packet and F7 continuation packets.
+///
+/// Each packet has `max_size` bytes of data at most and continuation packets are sent `gap` ticks after
+/// the previous packet. Other messages (including F7 escape sequence) and short data aren't split.
+///
+/// # Examples
+///
+/// ```
+/// use ghakuf::messages::{Message, SysExEvent};
+/// use ghakuf::sysex;
+///
+/// let message = Message::SysExEvent {
+///     delta_time: 10,
+///     event: SysExEvent::F0,
+///     data: vec![0x43, 0x10, 0x4c, 0x00, 0x00, 0x7e, 0x00, 0xf7],
+/// };
+/// assert_eq!(
+///     sysex::split(&message, 4, 2),
+///     vec![
+///         Message::SysExEvent {
+///             delta_time: 10,
+///             event: SysExEvent::F0,
+///             data: vec![0x43, 0x10, 0x4c, 0x00],
+///         },
+///         Message::SysExEvent {
+///             delta_time: 2,
+///             event: SysExEvent::F7,
+///             data: vec![0x00, 0x7e, 0x00, 0xf7],
+///         },
+///     ]
+/// );
+/// ```
+pub fn split(message: &Message, max_size: usize, gap: u32) -> Vec<Message> {
+    match *message {
+        Message::SysExEvent {
+            delta_time,
+            event: SysExEvent::F0,
+            ref data,
+        } if max_size > 0 && data.len() > max_size => data
+            .chunks(max_size)
+            .enumerate()
+            .map(|(i, chunk)| Message::SysExEvent {
+                delta_time: if i == 0 { delta_time } else { gap },
+                event: if i == 0 {
+                    SysExEvent::F0
+                } else {
+                    SysExEvent::F7
+                },
+                data: chunk.to_vec(),
+            })
+            .collect(),
+        _ => vec![message.clone()],
+    }
+}
+
+/// Joins F0 packet and following F7 continuation packets into one system exclusive message.
+///
+/// Joined message keeps timing of F0 packet, and delta times of continuation packets are added to the
+/// next message, so that timing of other messages isn't changed. F7 message which doesn't follow
+/// unterminated F0 packet is an escape sequence and is left as it is.
+///
+/// # Examples
+///
+/// ```
+/// use ghakuf::messages::{Message, MetaEvent, SysExEvent};
+/// use ghakuf::sysex;
+///
+/// let messages = vec![
+///     Message::SysExEvent {
+///         delta_time: 10,
+///         event: SysExEvent::F0,
+///         data: vec![0x7e, 0x7f],
+///     },
+///     Message::SysExEvent {
+///         delta_time: 5,
+///         event: SysExEvent::F7,
+///         data: vec![0x09, 0x01, 0xf7],
+///     },
+///     Message::MetaEvent {
+///         delta_time: 0,
+///         event: MetaEvent::EndOfTrack,
+///         data: Vec::new(),
+///     },
+/// ];
+/// assert_eq!(
+///     sysex::join(&messages),
+///     vec![
+///         Message::SysExEvent {
+///             delta_time: 10,
+///             event: SysExEvent::F0,
+///             data: vec![0x7e, 0x7f, 0x09, 0x01, 0xf7],
+///         },
+///         Message::MetaEvent {
+///             delta_time: 5,
+///             event: MetaEvent::EndOfTrack,
+///             data: Vec::new(),
+///         },
+///     ]
+/// );
+/// ```
+pub fn join(messages: &[Message]) -> Vec<Message> {
+    let mut joined: Vec<Message> = Vec::new();
+    // index of unterminated F0 packet in joined messages
+    let mut pending: Option<usize> = None;
+    let mut carry: u32 = 0;
+    for message in messages {
+        if let Some(index) = pending {
+            if let Message::SysExEvent {
+                delta_time,
+                event: SysExEvent::F7,
+                ref data,
+            } = *message
+            {
+                carry += delta_time;
+                if let Message::SysExEvent {
+                    data: ref mut joined_data,
+                    ..
+                } = joined[index]
+                {
+                    joined_data.extend_from_slice(data);
+                    if joined_data.last() == Some(&0xf7) {
+                        pending = None;
+                    }
+                }
+                continue;
+            }
+            warn!("system exclusive packet isn't terminated by 0xf7");
+            pending = None;
+        }
+        let mut message = message.clone();
+        if message == Message::TrackChange {
+            carry = 0;
+        } else {
+            let delta_time = message.delta_time() + carry;
+            message.set_delta_time(delta_time);
+            carry = 0;
+        }
+        if let Message::SysExEvent {
+            event: SysExEvent::F0,
+            ref data,
+            ..
+        } = message
+        {
+            if data.last() != Some(&0xf7) {
+                pending = Some(joined.len());
+            }
+        }
+        joined.push(message);
+    }
+    joined
+}
+
+const UNIVERSAL_NON_REAL_TIME_ID: ManufacturerId =
+    ManufacturerId::Standard(UNIVERSAL_NON_REAL_TIME);
+const UNIVERSAL_REAL_TIME_ID: ManufacturerId = ManufacturerId::Standard(UNIVERSAL_REAL_TIME);
+
+// Returns data without the last 0xf7.
+fn body(data: &[u8]) -> Result<&[u8], SysExError> {
+    let body = match data.split_last() {
+        Some((&0xf7, body)) => body,
+        _ => return Err(SysExError::Unterminated),
+    };
+    match body.iter().position(|&byte| byte > 0x7f) {
+        Some(index) => Err(SysExError::InvalidDataByte {
+            index,
+            byte: body[index],
+        }),
+        None => Ok(body),
+    }
+}
+
+fn lsb_msb(value: u16) -> [u8; 2] {
+    [value as u8 & 0x7f, (value >> 7) as u8 & 0x7f]
+}
+
+fn checksum_xor(data: &[u8]) -> u8 {
+    data.iter().fold(0, |checksum, byte| checksum ^ byte) & 0x7f
+}
+
+// Checksum used by Roland and Yamaha: the lower 7 bits of the sum of data and checksum are 0.
+fn checksum_sum(data: &[u8]) -> u8 {
+    let sum = data.iter().fold(0u32, |sum, &byte| sum + byte as u32);
+    ((128 - sum % 128) % 128) as u8
+}
+
+// Returns data between manufacturer ID and the last 0xf7 when manufacturer ID is expected one.
+fn manufacturer_body(data: &[u8], expected: ManufacturerId) -> Result<&[u8], SysExError> {
+    match ManufacturerId::from_data(body(data)?) {
+        Some((found, rest)) if found == expected => Ok(rest),
+        Some((found, _)) => Err(SysExError::UnexpectedManufacturer { expected, found }),
+        None => Err(SysExError::Truncated {
+            expected: 1,
+            found: 0,
+        }),
+    }
+}
+
+/// An enum represents errors of system exclusive parsing.
+#[derive(PartialEq, Clone, Debug)]
+pub enum SysExError {
+    /// Data doesn't end with 0xf7 (it may be continued by another packet).
+    Unterminated,
+    /// Data has a byte other than 0xf7 at the end with the most significant bit set.
+    InvalidDataByte { index: usize, byte: u8 },
+    /// Data is shorter than the message requires.
+    Truncated { expected: usize, found: usize },
+    /// Data isn't a Universal system exclusive message.
+    NotUniversal { id: ManufacturerId },
+    /// Message with manufacturer ID and sub IDs isn't supported.
+    Unsupported {
+        id: ManufacturerId,
+        sub_id1: u8,
+        sub_id2: u8,
+    },
+    /// Checksum in data doesn't match with calculated one.
+    ChecksumMismatch { expected: u8, found: u8 },
+    /// Data is sent by another manufacturer.
+    UnexpectedManufacturer {
+        expected: ManufacturerId,
+        found: ManufacturerId,
+    },
+    /// Command (or model) ID of manufacturer specific message isn't supported.
+    UnsupportedCommand { id: ManufacturerId, command: u8 },
+}
+impl fmt::Display for SysExError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        use sysex::SysExError::*;
+        match *self {
+            Unterminated => write!(f, "System exclusive data doesn't end with 0xf7."),
+            InvalidDataByte { index, byte } => write!(
+                f,
+                "System exclusive data has invalid data byte {:02x} at {}",
+                byte, index
+            ),
+            Truncated { expected, found } => write!(
+                f,
+                "System exclusive data is too short: expected {} bytes, found {} bytes",
+                expected, found
+            ),
+            NotUniversal { ref id } => write!(
+                f,
+                "System exclusive data of {} isn't a Universal message.",
+                id
+            ),
+            Unsupported {
+                ref id,
+                sub_id1,
+                sub_id2,
+            } => write!(
+                f,
+                "Unsupported system exclusive message: {}, sub ID: {:02x} {:02x}",
+                id, sub_id1, sub_id2
+            ),
+            ChecksumMismatch { expected, found } => write!(
+                f,
+                "System exclusive checksum mismatch: expected {:02x}, found {:02x}",
+                expected, found
+            ),
+            UnexpectedManufacturer {
+                ref expected,
+                ref found,
+            } => write!(
+                f,
+                "System exclusive data of {} is expected, but found {}.",
+                expected, found
+            ),
+            UnsupportedCommand { ref id, command } => write!(
+                f,
+                "Unsupported system exclusive command of {}: {:02x}",
+                id, command
+            ),
+        }
+    }
+}
+impl error::Error for SysExError {}
+
+#[cfg(test)]
+mod sysex_tests {
+    use sysex::*;
+    fn round_trip(message: UniversalMessage) {
+        let universal = Universal {
+            device_id: 0x12,
+            message,
+        };
+        assert_eq!(Universal::parse(&universal.binary()), Ok(universal));
+    }
+    #[test]
+    fn universal_round_trip() {
+        round_trip(UniversalMessage::GmSystemOff);
+        round_trip(UniversalMessage::MasterFineTuning { tuning: -100 });
+        round_trip(UniversalMessage::IdentityReply {
+            manufacturer: ManufacturerId::Extended(0x20, 0x6b),
+            family: 0x0102,
+            member: 0x0003,
+            version: [1, 2, 3, 4],
+        });
+        round_trip(UniversalMessage::SingleNoteTuningChange {
+            program: 3,
+            changes: vec![
+                (60, NoteTuning::from_semitones(60.25)),
+                (64, NoteTuning::from_semitones(63.86)),
+            ],
+        });
+    }
+    #[test]
+    fn bulk_tuning_dump() {
+        let tunings: Vec<NoteTuning> = (0..128)
+            .map(|key| NoteTuning::from_semitones(key as f64 + 0.5))
+            .collect();
+        let universal = Universal::new(UniversalMessage::BulkTuningDump {
+            program: 1,
+            name: "Quarter tone up ".to_string(),
+            tunings,
+        });
+        let mut binary = universal.binary();
+        assert_eq!(binary.len(), 4 + 1 + 16 + 384 + 1 + 1);
+        assert_eq!(Universal::parse(&binary).as_ref(), Ok(&universal));
+        let len = binary.len();
+        binary[len - 2] ^= 0x01;
+        match Universal::parse(&binary) {
+            Err(SysExError::ChecksumMismatch { .. }) => (),
+            result => panic!("checksum mismatch hasn't reported: {:?}", result),
+        }
+    }
+    #[test]
+    fn errors() {
+        assert_eq!(
+            Universal::parse(&[0x7e, 0x7f, 0x09]),
+            Err(SysExError::Unterminated)
+        );
+        assert_eq!(
+            Universal::parse(&[0x7f, 0x7f, 0x04, 0x01, 0x00, 0xf7]),
+            Err(SysExError::Truncated {
+                expected: 6,
+                found: 5
+            })
+        );
+        assert_eq!(
+            Universal::parse(&[0x7e, 0x7f, 0x06, 0x02, 0x00, 0x21, 0x09, 0x01, 0xf7]),
+            Err(SysExError::Truncated {
+                expected: 15,
+                found: 8
+            })
+        );
+        assert_eq!(
+            Universal::parse(&[0x41, 0x10, 0x42, 0xf7]),
+            Err(SysExError::NotUniversal {
+                id: ManufacturerId::Standard(0x41)
+            })
+        );
+        assert_eq!(
+            Universal::parse(&[0x7f, 0x7f, 0x04, 0x04, 0x00, 0x80, 0xf7]),
+            Err(SysExError::InvalidDataByte {
+                index: 5,
+                byte: 0x80
+            })
+        );
+        assert_eq!(
+            Universal::parse(&[0x7f, 0x7f, 0x04, 0x03, 0xff, 0xff, 0xf7]),
+            Err(SysExError::InvalidDataByte {
+                index: 4,
+                byte: 0xff
+            })
+        );
+    }
+    #[test]
+    fn roland_yamaha_round_trip() {
+        use sysex::roland::*;
+        use sysex::yamaha::*;
+        for ch in 0..16 {
+            let roland = Roland::gs(GsParameter::Part {
+                ch,
+                offset: roland::PART_LEVEL,
+                data: vec![100],
+            });
+            assert_eq!(Roland::parse(&roland.binary()).as_ref(), Ok(&roland));
+            assert_eq!(
+                roland.gs_parameter(),
+                Some(GsParameter::Part {
+                    ch,
+                    offset: roland::PART_LEVEL,
+                    data: vec![100]
+                })
+            );
+        }
+        let yamaha = Yamaha {
+            device_number: 1,
+            model_id: XG_MODEL_ID,
+            command: YamahaCommand::BulkDump {
+                address: [0x08, 0x00, 0x00],
+                data: (0..0x90).map(|i| i & 0x7f).collect(),
+            },
+        };
+        assert_eq!(Yamaha::parse(&yamaha.binary()), Ok(yamaha));
+        assert_eq!(
+            Roland::parse(&Yamaha::xg(XgParameter::SystemOn).binary()),
+            Err(SysExError::UnexpectedManufacturer {
+                expected: ROLAND,
+                found: YAMAHA
+            })
+        );
+    }
+}
@@ -0,0 +1,364 @@
+use sysex::*;
+
+/// Manufacturer ID of Roland.
+pub const ROLAND: ManufacturerId = ManufacturerId::Standard(0x41);
+/// Model ID of GS sound modules.
+pub const GS_MODEL_ID: u8 = 0x42;
+/// Default device ID of Roland sound modules.
+pub const DEFAULT_DEVICE_ID: u8 = 0x10;
+/// Command ID of Data Request 1 (RQ1).
+pub const RQ1: u8 = 0x11;
+/// Command ID of Data Set 1 (DT1).
+pub const DT1: u8 = 0x12;
+
+/// GS part parameter offset of receiving channel.
+pub const PART_RX_CHANNEL: u8 = 0x02;
+/// GS part parameter offset of use for rhythm part (0: off, 1: map 1, 2: map 2).
+pub const PART_USE_FOR_RHYTHM: u8 = 0x15;
+/// GS part parameter offset of pitch key shift (0x40 is center).
+pub const PART_KEY_SHIFT: u8 = 0x16;
+/// GS part parameter offset of part level.
+pub const PART_LEVEL: u8 = 0x19;
+/// GS part parameter offset of part panpot (0x00 is random, 0x40 is center).
+pub const PART_PAN: u8 = 0x1c;
+/// GS part parameter offset of reverb send level.
+pub const PART_REVERB_SEND: u8 = 0x22;
+/// GS part parameter offset of chorus send level.
+pub const PART_CHORUS_SEND: u8 = 0x21;
+
+/// An enum representing Roland command.
+#[derive(PartialEq, Clone, Debug)]
+pub enum RolandCommand {
+    /// Data Set 1 (DT1): writes data from address.
+    DataSet { address: [u8; 3], data: Vec<u8> },
+    /// Data Request 1 (RQ1): requests data of size from address.
+    DataRequest { address: [u8; 3], size: [u8; 3] },
+}
+
+/// A struct representing Roland system exclusive message with checksum.
+///
+/// # Examples
+///
+/// ```
+/// use ghakuf::messages::{Message, SysExEvent};
+/// use ghakuf::sysex::roland::{GsParameter, Roland};
+///
+/// let message = Roland::gs(GsParameter::Reset).message(0);
+/// assert_eq!(
+///     message,
+///     Message::SysExEvent {
+///         delta_time: 0,
+///         event: SysExEvent::F0,
+///         data: vec![0x41, 0x10, 0x42, 0x12, 0x40, 0x00, 0x7f, 0x00, 0x41, 0xf7],
+///     }
+/// );
+/// ```
+#[derive(PartialEq, Clone, Debug)]
+pub struct Roland {
+    pub device_id: u8,
+    pub model_id: u8,
+    pub command: RolandCommand,
+}
+impl Roland {
+    /// Builds DT1 message of GS parameter addressed to default device ID.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use ghakuf::sysex::roland::{GsParameter, Roland, PART_USE_FOR_RHYTHM};
+    ///
+    /// let roland = Roland::gs(GsParameter::Part {
+    ///     ch: 10,
+    ///     offset: PART_USE_FOR_RHYTHM,
+    ///     data: vec![0x02],
+    /// });
+    /// assert_eq!(
+    ///     roland.binary(),
+    ///     [0x41, 0x10, 0x42, 0x12, 0x40, 0x1a, 0x15, 0x02, 0x0f, 0xf7]
+    /// );
+    /// ```
+    pub fn gs(parameter: GsParameter) -> Roland {
+        Roland {
+            device_id: DEFAULT_DEVICE_ID,
+            model_id: GS_MODEL_ID,
+            command: RolandCommand::DataSet {
+                address: parameter.address(),
+                data: parameter.data(),
+            },
+        }
+    }
+    /// Parses system exclusive data (after 0xf0, including the last 0xf7) and verifies checksum.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use ghakuf::sysex::SysExError;
+    /// use ghakuf::sysex::roland::{Roland, RolandCommand};
+    ///
+    /// let roland = Roland::parse(&[0x41, 0x10, 0x42, 0x12, 0x40, 0x00, 0x04, 0x64, 0x58, 0xf7]);
+    /// assert_eq!(
+    ///     roland.unwrap().command,
+    ///     RolandCommand::DataSet { address: [0x40, 0x00, 0x04], data: vec![0x64] }
+    /// );
+    /// assert_eq!(
+    ///     Roland::parse(&[0x41, 0x10, 0x42, 0x12, 0x40, 0x00, 0x04, 0x64, 0x00, 0xf7]),
+    ///     Err(SysExError::ChecksumMismatch { expected: 0x58, found: 0x00 })
+    /// );
+    /// ```
+    pub fn parse(data: &[u8]) -> Result<Roland, SysExError> {
+        let rest = manufacturer_body(data, ROLAND)?;
+        if rest.len() < 3 + 3 + 1 {
+            return Err(SysExError::Truncated {
+                expected: 1 + 3 + 3 + 1,
+                found: 1 + rest.len(),
+            });
+        }
+        let (device_id, model_id, command) = (rest[0], rest[1], rest[2]);
+        let (payload, found) = rest[3..].split_at(rest.len() - 4);
+        let expected = checksum_sum(payload);
+        if expected != found[0] {
+            warn!(
+                "Roland checksum mismatch: expected {:x}, found {:x}",
+                expected, found[0]
+            );
+            return Err(SysExError::ChecksumMismatch {
+                expected,
+                found: found[0],
+            });
+        }
+        let address = [payload[0], payload[1], payload[2]];
+        let command = match command {
+            DT1 => RolandCommand::DataSet {
+                address,
+                data: payload[3..].to_vec(),
+            },
+            RQ1 if payload.len() == 6 => RolandCommand::DataRequest {
+                address,
+                size: [payload[3], payload[4], payload[5]],
+            },
+            RQ1 => {
+                return Err(SysExError::Truncated {
+                    expected: 1 + 3 + 6 + 1,
+                    found: 1 + rest.len(),
+                })
+            }
+            _ => {
+                return Err(SysExError::UnsupportedCommand {
+                    id: ROLAND,
+                    command,
+                })
+            }
+        };
+        Ok(Roland {
+            device_id,
+            model_id,
+            command,
+        })
+    }
+    /// Makes system exclusive data (after 0xf0, including the last 0xf7) with checksum.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use ghakuf::sysex::roland::{Roland, RolandCommand, GS_MODEL_ID};
+    ///
+    /// let roland = Roland {
+    ///     device_id: 0x10,
+    ///     model_id: GS_MODEL_ID,
+    ///     command: RolandCommand::DataRequest {
+    ///         address: [0x40, 0x00, 0x04],
+    ///         size: [0x00, 0x00, 0x01],
+    ///     },
+    /// };
+    /// assert_eq!(
+    ///     roland.binary(),
+    ///     [0x41, 0x10, 0x42, 0x11, 0x40, 0x00, 0x04, 0x00, 0x00, 0x01, 0x3b, 0xf7]
+    /// );
+    /// ```
+    pub fn binary(&self) -> Vec<u8> {
+        let (command, payload) = match self.command {
+            RolandCommand::DataSet { address, ref data } => {
+                let mut payload = address.to_vec();
+                payload.extend(data.iter().map(|byte| byte & 0x7f));
+                (DT1, payload)
+            }
+            RolandCommand::DataRequest { address, size } => {
+                let mut payload = address.to_vec();
+                payload.extend_from_slice(&size);
+                (RQ1, payload)
+            }
+        };
+        let mut binary = ROLAND.binary();
+        binary.extend_from_slice(&[self.device_id & 0x7f, self.model_id & 0x7f, command]);
+        binary.extend_from_slice(&payload);
+        binary.push(checksum_sum(&payload));
+        binary.push(0xf7);
+        binary
+    }
+    /// Makes Message::SysExEvent of this message.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use ghakuf::messages::Message;
+    /// use ghakuf::sysex::roland::{GsParameter, Roland};
+    ///
+    /// let message = Roland::gs(GsParameter::MasterVolume { volume: 100 }).message(10);
+    /// assert_eq!(message.binary(), [0x0a, 0xf0, 0x0a, 0x41, 0x10, 0x42, 0x12, 0x40, 0x00, 0x04, 0x64, 0x58, 0xf7]);
+    /// ```
+    pub fn message(&self, delta_time: u32) -> Message {
+        Message::SysExEvent {
+            delta_time,
+            event: SysExEvent::F0,
+            data: self.binary(),
+        }
+    }
+    /// Returns GS parameter when this message is DT1 for GS.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use ghakuf::sysex::roland::{GsParameter, Roland, PART_PAN};
+    ///
+    /// let roland = Roland::parse(&[0x41, 0x10, 0x42, 0x12, 0x40, 0x11, 0x1c, 0x20, 0x73, 0xf7]).unwrap();
+    /// assert_eq!(
+    ///     roland.gs_parameter(),
+    ///     Some(GsParameter::Part { ch: 0, offset: PART_PAN, data: vec![0x20] })
+    /// );
+    /// ```
+    pub fn gs_parameter(&self) -> Option<GsParameter> {
+        match self.command {
+            RolandCommand::DataSet { address, ref data } if self.model_id == GS_MODEL_ID => {
+                GsParameter::from_data_set(address, data)
+            }
+            _ => None,
+        }
+    }
+}
+
+/// An enum representing GS parameters.
+#[derive(PartialEq, Clone, Debug)]
+pub enum GsParameter {
+    /// GS Reset (40 00 7f)
+    Reset,
+    /// Master volume (40 00 04)
+    MasterVolume { volume: u8 },
+    /// Master key shift (40 00 05, -24 to +24 semitones)
+    MasterKeyShift { semitones: i8 },
+    /// Master panpot (40 00 06, 0x40 is center)
+    MasterPan { pan: u8 },
+    /// Part parameter (40 1x yy) of MIDI channel (0-15) at offset yy
+    Part { ch: u8, offset: u8, data: Vec<u8> },
+}
+impl GsParameter {
+    /// Returns address of this parameter.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use ghakuf::sysex::roland::{GsParameter, PART_LEVEL};
+    ///
+    /// let parameter = GsParameter::Part { ch: 9, offset: PART_LEVEL, data: vec![100] };
+    /// assert_eq!(parameter.address(), [0x40, 0x10, 0x19]);
+    /// ```
+    pub fn address(&self) -> [u8; 3] {
+        match *self {
+            GsParameter::Reset => [0x40, 0x00, 0x7f],
+            GsParameter::MasterVolume { .. } => [0x40, 0x00, 0x04],
+            GsParameter::MasterKeyShift { .. } => [0x40, 0x00, 0x05],
+            GsParameter::MasterPan { .. } => [0x40, 0x00, 0x06],
+            GsParameter::Part { ch, offset, .. } => [0x40, 0x10 | part_block(ch), offset & 0x7f],
+        }
+    }
+    /// Returns data of this parameter.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use ghakuf::sysex::roland::GsParameter;
+    ///
+    /// assert_eq!(GsParameter::MasterKeyShift { semitones: -12 }.data(), [0x34]);
+    /// ```
+    pub fn data(&self) -> Vec<u8> {
+        match *self {
+            GsParameter::Reset => vec![0x00],
+            GsParameter::MasterVolume { volume } => vec![volume & 0x7f],
+            GsParameter::MasterKeyShift { semitones } => {
+                vec![(semitones as i32 + 0x40).clamp(0x28, 0x58) as u8]
+            }
+            GsParameter::MasterPan { pan } => vec![pan & 0x7f],
+            GsParameter::Part { ref data, .. } => data.clone(),
+        }
+    }
+    /// Decodes DT1 address and data to GS parameter.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use ghakuf::sysex::roland::GsParameter;
+    ///
+    /// assert_eq!(
+    ///     GsParameter::from_data_set([0x40, 0x00, 0x7f], &[0x00]),
+    ///     Some(GsParameter::Reset)
+    /// );
+    /// assert_eq!(GsParameter::from_data_set([0x48, 0x00, 0x00], &[0x00]), None);
+    /// ```
+    pub fn from_data_set(address: [u8; 3], data: &[u8]) -> Option<GsParameter> {
+        match (address, data) {
+            ([0x40, 0x00, 0x7f], [0x00]) => Some(GsParameter::Reset),
+            ([0x40, 0x00, 0x04], [volume]) => Some(GsParameter::MasterVolume { volume: *volume }),
+            ([0x40, 0x00, 0x05], [key_shift]) => Some(GsParameter::MasterKeyShift {
+                semitones: *key_shift as i8 - 0x40,
+            }),
+            ([0x40, 0x00, 0x06], [pan]) => Some(GsParameter::MasterPan { pan: *pan }),
+            ([0x40, block, offset], data) if block & 0xf0 == 0x10 && !data.is_empty() => {
+                Some(GsParameter::Part {
+                    ch: block_channel(block & 0x0f),
+                    offset,
+                    data: data.to_vec(),
+                })
+            }
+            _ => None,
+        }
+    }
+}
+
+/// Returns GS part block number (x of address 40 1x yy) of MIDI channel (0-15).
+///
+/// Part 10 (channel 9) is block 0, parts 1-9 are blocks 1-9 and parts 11-16 are blocks 0xa-0xf.
+///
+/// # Examples
+///
+/// ```
+/// use ghakuf::sysex::roland::part_block;
+///
+/// assert_eq!(part_block(0), 1);
+/// assert_eq!(part_block(9), 0);
+/// assert_eq!(part_block(10), 0x0a);
+/// ```
+pub fn part_block(ch: u8) -> u8 {
+    match ch & 0x0f {
+        ch if ch < 9 => ch + 1,
+        9 => 0,
+        ch => ch,
+    }
+}
+
+/// Returns MIDI channel (0-15) of GS part block number.
+///
+/// # Examples
+///
+/// ```
+/// use ghakuf::sysex::roland::{block_channel, part_block};
+///
+/// for ch in 0..16 {
+///     assert_eq!(block_channel(part_block(ch)), ch);
+/// }
+/// ```
+pub fn block_channel(block: u8) -> u8 {
+    match block & 0x0f {
+        0 => 9,
+        block if block < 10 => block - 1,
+        block => block,
+    }
+}
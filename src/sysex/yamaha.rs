@@ -0,0 +1,336 @@
+use sysex::*;
+
+/// Manufacturer ID of Yamaha.
+pub const YAMAHA: ManufacturerId = ManufacturerId::Standard(0x43);
+/// Model ID of XG sound modules.
+pub const XG_MODEL_ID: u8 = 0x4c;
+
+/// XG multi part parameter offset of bank select MSB.
+pub const PART_BANK_MSB: u8 = 0x01;
+/// XG multi part parameter offset of bank select LSB.
+pub const PART_BANK_LSB: u8 = 0x02;
+/// XG multi part parameter offset of program number.
+pub const PART_PROGRAM: u8 = 0x03;
+/// XG multi part parameter offset of receiving channel.
+pub const PART_RX_CHANNEL: u8 = 0x04;
+/// XG multi part parameter offset of part mode (0: normal, 1: drum, 2-5: drum setup 1-4).
+pub const PART_MODE: u8 = 0x07;
+/// XG multi part parameter offset of volume.
+pub const PART_VOLUME: u8 = 0x0b;
+/// XG multi part parameter offset of pan (0x00 is random, 0x40 is center).
+pub const PART_PAN: u8 = 0x0e;
+/// XG multi part parameter offset of chorus send.
+pub const PART_CHORUS_SEND: u8 = 0x12;
+/// XG multi part parameter offset of reverb send.
+pub const PART_REVERB_SEND: u8 = 0x13;
+
+/// An enum representing Yamaha command (upper nibble of the byte after manufacturer ID).
+#[derive(PartialEq, Clone, Debug)]
+pub enum YamahaCommand {
+    /// Bulk dump (0n) of data from address with checksum.
+    BulkDump { address: [u8; 3], data: Vec<u8> },
+    /// Parameter change (1n) of data from address.
+    ParameterChange { address: [u8; 3], data: Vec<u8> },
+    /// Dump request (2n) of address.
+    DumpRequest { address: [u8; 3] },
+    /// Parameter request (3n) of address.
+    ParameterRequest { address: [u8; 3] },
+}
+
+/// A struct representing Yamaha system exclusive message.
+///
+/// # Examples
+///
+/// ```
+/// use ghakuf::messages::{Message, SysExEvent};
+/// use ghakuf::sysex::yamaha::{XgParameter, Yamaha};
+///
+/// let message = Yamaha::xg(XgParameter::SystemOn).message(0);
+/// assert_eq!(
+///     message,
+///     Message::SysExEvent {
+///         delta_time: 0,
+///         event: SysExEvent::F0,
+///         data: vec![0x43, 0x10, 0x4c, 0x00, 0x00, 0x7e, 0x00, 0xf7],
+///     }
+/// );
+/// ```
+#[derive(PartialEq, Clone, Debug)]
+pub struct Yamaha {
+    pub device_number: u8,
+    pub model_id: u8,
+    pub command: YamahaCommand,
+}
+impl Yamaha {
+    /// Builds XG parameter change message of device number 0.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use ghakuf::sysex::yamaha::{XgParameter, Yamaha, PART_MODE};
+    ///
+    /// let yamaha = Yamaha::xg(XgParameter::MultiPart { part: 9, offset: PART_MODE, data: vec![0x02] });
+    /// assert_eq!(yamaha.binary(), [0x43, 0x10, 0x4c, 0x08, 0x09, 0x07, 0x02, 0xf7]);
+    /// ```
+    pub fn xg(parameter: XgParameter) -> Yamaha {
+        Yamaha {
+            device_number: 0,
+            model_id: XG_MODEL_ID,
+            command: YamahaCommand::ParameterChange {
+                address: parameter.address(),
+                data: parameter.data(),
+            },
+        }
+    }
+    /// Parses system exclusive data (after 0xf0, including the last 0xf7) and verifies checksum of bulk dump.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use ghakuf::sysex::SysExError;
+    /// use ghakuf::sysex::yamaha::{Yamaha, YamahaCommand};
+    ///
+    /// let yamaha = Yamaha::parse(&[0x43, 0x10, 0x4c, 0x00, 0x00, 0x04, 0x64, 0xf7]).unwrap();
+    /// assert_eq!(
+    ///     yamaha.command,
+    ///     YamahaCommand::ParameterChange { address: [0x00, 0x00, 0x04], data: vec![0x64] }
+    /// );
+    /// assert_eq!(
+    ///     Yamaha::parse(&[0x43, 0x00, 0x4c, 0x00, 0x01, 0x00, 0x00, 0x04, 0x64, 0x00, 0xf7]),
+    ///     Err(SysExError::ChecksumMismatch { expected: 0x17, found: 0x00 })
+    /// );
+    /// ```
+    pub fn parse(data: &[u8]) -> Result<Yamaha, SysExError> {
+        let rest = manufacturer_body(data, YAMAHA)?;
+        if rest.len() < 2 + 3 {
+            return Err(SysExError::Truncated {
+                expected: 1 + 2 + 3,
+                found: 1 + rest.len(),
+            });
+        }
+        let (device_number, model_id) = (rest[0] & 0x0f, rest[1]);
+        let address = [rest[2], rest[3], rest[4]];
+        let command = match rest[0] >> 4 {
+            0x0 => {
+                // byte count (2 bytes) is placed before address
+                if rest.len() < 2 + 2 + 3 + 1 {
+                    return Err(SysExError::Truncated {
+                        expected: 1 + 2 + 2 + 3 + 1,
+                        found: 1 + rest.len(),
+                    });
+                }
+                let (payload, found) = rest[2..].split_at(rest.len() - 3);
+                let expected = checksum_sum(payload);
+                if expected != found[0] {
+                    warn!(
+                        "Yamaha checksum mismatch: expected {:x}, found {:x}",
+                        expected, found[0]
+                    );
+                    return Err(SysExError::ChecksumMismatch {
+                        expected,
+                        found: found[0],
+                    });
+                }
+                let count = (payload[0] as usize) << 7 | payload[1] as usize;
+                if payload.len() - 5 != count {
+                    warn!(
+                        "Yamaha bulk dump byte count is {}, but {} bytes found",
+                        count,
+                        payload.len() - 5
+                    );
+                }
+                YamahaCommand::BulkDump {
+                    address: [payload[2], payload[3], payload[4]],
+                    data: payload[5..].to_vec(),
+                }
+            }
+            0x1 => YamahaCommand::ParameterChange {
+                address,
+                data: rest[5..].to_vec(),
+            },
+            0x2 => YamahaCommand::DumpRequest { address },
+            0x3 => YamahaCommand::ParameterRequest { address },
+            _ => {
+                return Err(SysExError::UnsupportedCommand {
+                    id: YAMAHA,
+                    command: rest[0],
+                })
+            }
+        };
+        Ok(Yamaha {
+            device_number,
+            model_id,
+            command,
+        })
+    }
+    /// Makes system exclusive data (after 0xf0, including the last 0xf7).
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use ghakuf::sysex::yamaha::{Yamaha, YamahaCommand, XG_MODEL_ID};
+    ///
+    /// let yamaha = Yamaha {
+    ///     device_number: 0,
+    ///     model_id: XG_MODEL_ID,
+    ///     command: YamahaCommand::BulkDump { address: [0x00, 0x00, 0x04], data: vec![0x64] },
+    /// };
+    /// assert_eq!(
+    ///     yamaha.binary(),
+    ///     [0x43, 0x00, 0x4c, 0x00, 0x01, 0x00, 0x00, 0x04, 0x64, 0x17, 0xf7]
+    /// );
+    /// ```
+    pub fn binary(&self) -> Vec<u8> {
+        let device_number = self.device_number & 0x0f;
+        let mut binary = YAMAHA.binary();
+        match self.command {
+            YamahaCommand::BulkDump { address, ref data } => {
+                let mut payload = vec![(data.len() >> 7) as u8 & 0x7f, data.len() as u8 & 0x7f];
+                payload.extend_from_slice(&address);
+                payload.extend(data.iter().map(|byte| byte & 0x7f));
+                binary.extend_from_slice(&[device_number, self.model_id & 0x7f]);
+                binary.extend_from_slice(&payload);
+                binary.push(checksum_sum(&payload));
+            }
+            YamahaCommand::ParameterChange { address, ref data } => {
+                binary.extend_from_slice(&[0x10 | device_number, self.model_id & 0x7f]);
+                binary.extend_from_slice(&address);
+                binary.extend(data.iter().map(|byte| byte & 0x7f));
+            }
+            YamahaCommand::DumpRequest { address } => {
+                binary.extend_from_slice(&[0x20 | device_number, self.model_id & 0x7f]);
+                binary.extend_from_slice(&address);
+            }
+            YamahaCommand::ParameterRequest { address } => {
+                binary.extend_from_slice(&[0x30 | device_number, self.model_id & 0x7f]);
+                binary.extend_from_slice(&address);
+            }
+        }
+        binary.push(0xf7);
+        binary
+    }
+    /// Makes Message::SysExEvent of this message.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use ghakuf::sysex::yamaha::{XgParameter, Yamaha};
+    ///
+    /// let message = Yamaha::xg(XgParameter::MasterVolume { volume: 100 }).message(0);
+    /// assert_eq!(message.binary(), [0x00, 0xf0, 0x08, 0x43, 0x10, 0x4c, 0x00, 0x00, 0x04, 0x64, 0xf7]);
+    /// ```
+    pub fn message(&self, delta_time: u32) -> Message {
+        Message::SysExEvent {
+            delta_time,
+            event: SysExEvent::F0,
+            data: self.binary(),
+        }
+    }
+    /// Returns XG parameter when this message is XG parameter change.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use ghakuf::sysex::yamaha::{XgParameter, Yamaha, PART_PROGRAM};
+    ///
+    /// let yamaha = Yamaha::parse(&[0x43, 0x10, 0x4c, 0x08, 0x00, 0x03, 0x30, 0xf7]).unwrap();
+    /// assert_eq!(
+    ///     yamaha.xg_parameter(),
+    ///     Some(XgParameter::MultiPart { part: 0, offset: PART_PROGRAM, data: vec![0x30] })
+    /// );
+    /// ```
+    pub fn xg_parameter(&self) -> Option<XgParameter> {
+        match self.command {
+            YamahaCommand::ParameterChange { address, ref data }
+                if self.model_id == XG_MODEL_ID =>
+            {
+                XgParameter::from_parameter_change(address, data)
+            }
+            _ => None,
+        }
+    }
+}
+
+/// An enum representing XG parameters.
+#[derive(PartialEq, Clone, Debug)]
+pub enum XgParameter {
+    /// XG System On (00 00 7e)
+    SystemOn,
+    /// Reset all parameters (00 00 7f)
+    AllParameterReset,
+    /// Master volume (00 00 04)
+    MasterVolume { volume: u8 },
+    /// Master transpose (00 00 06, -24 to +24 semitones)
+    MasterTranspose { semitones: i8 },
+    /// Multi part parameter (08 nn pp) of part nn (0-31) at offset pp
+    MultiPart { part: u8, offset: u8, data: Vec<u8> },
+}
+impl XgParameter {
+    /// Returns address of this parameter.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use ghakuf::sysex::yamaha::{XgParameter, PART_VOLUME};
+    ///
+    /// let parameter = XgParameter::MultiPart { part: 3, offset: PART_VOLUME, data: vec![100] };
+    /// assert_eq!(parameter.address(), [0x08, 0x03, 0x0b]);
+    /// ```
+    pub fn address(&self) -> [u8; 3] {
+        match *self {
+            XgParameter::SystemOn => [0x00, 0x00, 0x7e],
+            XgParameter::AllParameterReset => [0x00, 0x00, 0x7f],
+            XgParameter::MasterVolume { .. } => [0x00, 0x00, 0x04],
+            XgParameter::MasterTranspose { .. } => [0x00, 0x00, 0x06],
+            XgParameter::MultiPart { part, offset, .. } => [0x08, part & 0x7f, offset & 0x7f],
+        }
+    }
+    /// Returns data of this parameter.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use ghakuf::sysex::yamaha::XgParameter;
+    ///
+    /// assert_eq!(XgParameter::MasterTranspose { semitones: 2 }.data(), [0x42]);
+    /// ```
+    pub fn data(&self) -> Vec<u8> {
+        match *self {
+            XgParameter::SystemOn | XgParameter::AllParameterReset => vec![0x00],
+            XgParameter::MasterVolume { volume } => vec![volume & 0x7f],
+            XgParameter::MasterTranspose { semitones } => {
+                vec![(semitones as i32 + 0x40).clamp(0x28, 0x58) as u8]
+            }
+            XgParameter::MultiPart { ref data, .. } => data.clone(),
+        }
+    }
+    /// Decodes parameter change address and data to XG parameter.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use ghakuf::sysex::yamaha::XgParameter;
+    ///
+    /// assert_eq!(
+    ///     XgParameter::from_parameter_change([0x00, 0x00, 0x7e], &[0x00]),
+    ///     Some(XgParameter::SystemOn)
+    /// );
+    /// assert_eq!(XgParameter::from_parameter_change([0x02, 0x01, 0x00], &[0x01]), None);
+    /// ```
+    pub fn from_parameter_change(address: [u8; 3], data: &[u8]) -> Option<XgParameter> {
+        match (address, data) {
+            ([0x00, 0x00, 0x7e], [0x00]) => Some(XgParameter::SystemOn),
+            ([0x00, 0x00, 0x7f], [0x00]) => Some(XgParameter::AllParameterReset),
+            ([0x00, 0x00, 0x04], [volume]) => Some(XgParameter::MasterVolume { volume: *volume }),
+            ([0x00, 0x00, 0x06], [transpose]) => Some(XgParameter::MasterTranspose {
+                semitones: *transpose as i8 - 0x40,
+            }),
+            ([0x08, part, offset], data) if !data.is_empty() => Some(XgParameter::MultiPart {
+                part,
+                offset,
+                data: data.to_vec(),
+            }),
+            _ => None,
+        }
+    }
+}
@@ -0,0 +1,1152 @@
+use byteorder::{BigEndian, ByteOrder};
+use formats::{Tag, VLQ};
+use messages::*;
+use reader::ReadError;
+use rmid;
+use smf::Smf;
+use std::collections::HashMap;
+use std::str::FromStr;
+use std::{error, fmt, io, str};
+
+/// Whole SMF with its encoding (running status and delta time length), which is written and parsed as
+/// human readable text.
+///
+/// Text starts with `MThd` header line and has a `MTrk` line followed by event lines for each
+/// track. An event line has delta time, event name and arguments like `480 NoteOn ch=0 key=60 vel=100`.
+/// Text events have a quoted string, and events without dedicated arguments (unknown events, data
+/// of unusual length) have raw data bytes in hex. `#` starts a comment.
+///
+/// `running_status` of the header line is the default: with `on`, status byte of a MIDI event is
+/// omitted when it is the same as the previous one. An event line with `running_status=on` or
+/// `running_status=off` overrides the default for the event, and `delta_time_bytes=N` writes delta
+/// time with N bytes even if it is shorter. So text read by `Text::from_bytes` is rebuilt into exactly
+/// the same messages and bytes.
+///
+/// ```text
+/// MThd format=1 time_base=480 running_status=off
+/// MTrk
+/// 0 SetTempo tempo=500000
+/// 0 SequenceOrTrackName "Conductor"
+/// 0 EndOfTrack
+/// MTrk
+/// 0 NoteOn ch=0 key=60 vel=100 # C4
+/// 480 NoteOff ch=0 key=60 vel=0
+/// 0 NoteOff ch=0 key=62 vel=0 running_status=on
+/// 0 SysEx f0 7e 7f 09 01 f7 delta_time_bytes=2
+/// 0 Meta 60 01 02
+/// 0 EndOfTrack
+/// ```
+///
+/// # Examples
+///
+/// ```
+/// use ghakuf::messages::*;
+/// use ghakuf::smf::Smf;
+/// use ghakuf::text::Text;
+///
+/// let mut smf = Smf::new(0, 480);
+/// smf.tracks.push(vec![
+///     Message::MidiEvent { delta_time: 0, event: MidiEvent::NoteOn { ch: 0, note: 60, velocity: 100 } },
+///     Message::MidiEvent { delta_time: 480, event: MidiEvent::NoteOn { ch: 0, note: 60, velocity: 0 } },
+///     Message::MetaEvent { delta_time: 0, event: MetaEvent::EndOfTrack, data: Vec::new() },
+/// ]);
+/// let text = Text::new(smf).to_string();
+/// assert_eq!(
+///     text,
+///     "MThd format=0 time_base=480 running_status=off\n\
+///      MTrk\n\
+///      0 NoteOn ch=0 key=60 vel=100\n\
+///      480 NoteOn ch=0 key=60 vel=0\n\
+///      0 EndOfTrack\n"
+/// );
+/// assert_eq!(text.parse::<Text>().unwrap().to_string(), text);
+/// ```
+#[derive(PartialEq, Clone, Debug)]
+pub struct Text {
+    pub smf: Smf,
+    /// Default of running status
+    pub running_status: bool,
+    /// Encodings of events different from the default, by index of track and index of message
+    pub encodings: HashMap<(usize, usize), Encoding>,
+}
+
+/// A struct representing how an event is encoded in SMF, where it differs from the default of Text.
+///
+/// # Examples
+///
+/// ```
+/// use ghakuf::text::{Encoding, Text};
+///
+/// let text: Text = "MThd format=0 time_base=480 running_status=off\n\
+///                   MTrk\n\
+///                   0 NoteOn ch=0 key=60 vel=100\n\
+///                   480 NoteOn ch=0 key=60 vel=0 running_status=on delta_time_bytes=3\n".parse().unwrap();
+/// assert_eq!(
+///     text.encodings[&(0, 1)],
+///     Encoding { running_status: Some(true), delta_time_bytes: Some(3) }
+/// );
+/// assert_eq!(&text.to_bytes()[22..], [0x00, 0x90, 0x3c, 0x64, 0x80, 0x83, 0x60, 0x3c, 0x00]);
+/// ```
+#[derive(PartialEq, Eq, Hash, Clone, Copy, Debug, Default)]
+pub struct Encoding {
+    /// Whether status byte of MIDI event is omitted, instead of the default of Text. Status byte is
+    /// written anyway if it isn't the same as the previous one.
+    pub running_status: Option<bool>,
+    /// Number of bytes of delta time, if it is longer than necessary (up to 4).
+    pub delta_time_bytes: Option<usize>,
+}
+impl Text {
+    /// Builds Text of SMF written without running status.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use ghakuf::smf::Smf;
+    /// use ghakuf::text::Text;
+    ///
+    /// let text = Text::new(Smf::new(1, 480));
+    /// assert!(!text.running_status);
+    /// ```
+    pub fn new(smf: Smf) -> Text {
+        Text {
+            smf,
+            running_status: false,
+            encodings: HashMap::new(),
+        }
+    }
+    /// Reads SMF (or RIFF MIDI) data into Text, which is written into exactly the same data.
+    ///
+    /// Running status is on by default when more MIDI events omit status byte than repeat it, and
+    /// the other events and delta times longer than necessary are recorded in `encodings`. Data which
+    /// can't be reproduced (such as unknown chunks, or data after the last track) is an error.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use ghakuf::text::Text;
+    /// use std::fs;
+    ///
+    /// let data = fs::read("tests/test.mid").unwrap();
+    /// let text = Text::from_bytes(&data).unwrap();
+    /// assert_eq!(text.to_bytes(), data);
+    /// ```
+    pub fn from_bytes(data: &[u8]) -> Result<Text, ReadError<'static>> {
+        let mut text = Text::new(Smf::from_bytes(data)?);
+        let data = if rmid::is_rmid(data) {
+            rmid::unwrap(data).unwrap_or(data)
+        } else {
+            data
+        };
+        let unreproducible = || {
+            ReadError::Io(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "SMF data can't be reproduced byte for byte",
+            ))
+        };
+        let events = scan(&text.smf, data).ok_or_else(unreproducible)?;
+        let running = events
+            .iter()
+            .flatten()
+            .filter(|&&(_, running)| running == Some(true))
+            .count();
+        let explicit = events
+            .iter()
+            .flatten()
+            .filter(|&&(_, running)| running == Some(false))
+            .count();
+        text.running_status = running > 0 && running >= explicit;
+        for (i, track) in events.iter().enumerate() {
+            for (j, &(delta_time_bytes, running)) in track.iter().enumerate() {
+                let minimal = VLQ::new(text.smf.tracks[i][j].delta_time()).len();
+                let encoding = Encoding {
+                    running_status: running.filter(|&running| running != text.running_status),
+                    delta_time_bytes: Some(delta_time_bytes).filter(|&bytes| bytes != minimal),
+                };
+                if encoding != Encoding::default() {
+                    text.encodings.insert((i, j), encoding);
+                }
+            }
+        }
+        if text.to_bytes() != data {
+            return Err(unreproducible());
+        }
+        Ok(text)
+    }
+    /// Writes Text into SMF data.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use ghakuf::text::Text;
+    ///
+    /// let text: Text = "MThd format=0 time_base=480 running_status=on\n\
+    ///                   MTrk\n\
+    ///                   0 NoteOn ch=0 key=60 vel=100\n\
+    ///                   480 NoteOn ch=0 key=60 vel=0\n\
+    ///                   0 EndOfTrack\n".parse().unwrap();
+    /// let data = text.to_bytes();
+    /// assert_eq!(&data[0..4], b"MThd");
+    /// assert_eq!(&data[22..], [0x00, 0x90, 0x3c, 0x64, 0x83, 0x60, 0x3c, 0x00, 0x00, 0xff, 0x2f, 0x00]);
+    /// ```
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut data = Tag::Header.binary().to_vec();
+        let mut buffer = [0; 4];
+        for &(value, len) in &[
+            (6, 4),
+            (self.smf.format as u32, 2),
+            (self.smf.tracks.len() as u32, 2),
+            (self.smf.time_base as u32, 2),
+        ] {
+            BigEndian::write_u32(&mut buffer, value);
+            data.extend_from_slice(&buffer[4 - len..]);
+        }
+        for i in 0..self.smf.tracks.len() {
+            let track = self.track_bytes(i);
+            data.extend_from_slice(Tag::Track.binary());
+            BigEndian::write_u32(&mut buffer, track.len() as u32);
+            data.extend_from_slice(&buffer);
+            data.extend(track);
+        }
+        data
+    }
+    fn track_bytes(&self, track: usize) -> Vec<u8> {
+        let mut data = Vec::new();
+        let mut pre_status_byte: Option<u8> = None;
+        for (i, message) in self.smf.tracks[track].iter().enumerate() {
+            let encoding = self.encodings.get(&(track, i)).cloned().unwrap_or_default();
+            let mut binary = message.binary();
+            let minimal = VLQ::new(message.delta_time()).len();
+            if let Message::MidiEvent { ref event, .. } = *message {
+                let status_byte = event.status_byte();
+                if pre_status_byte == Some(status_byte)
+                    && encoding.running_status.unwrap_or(self.running_status)
+                {
+                    binary.remove(minimal);
+                }
+                pre_status_byte = Some(status_byte);
+            }
+            match encoding.delta_time_bytes {
+                Some(bytes) if bytes > minimal => {
+                    data.extend(padded_vlq(message.delta_time(), bytes));
+                    data.extend_from_slice(&binary[minimal..]);
+                }
+                _ => data.extend(binary),
+            }
+        }
+        data
+    }
+}
+
+// Walks SMF data along with messages and returns length of delta time and whether status byte is
+// omitted (None if the previous status byte is different) of each event, or None if data doesn't match.
+#[allow(clippy::type_complexity)]
+fn scan(smf: &Smf, data: &[u8]) -> Option<Vec<Vec<(usize, Option<bool>)>>> {
+    if data.len() < 14 || BigEndian::read_u32(&data[4..8]) != 6 {
+        return None;
+    }
+    let mut position = 14;
+    let mut events = Vec::with_capacity(smf.tracks.len());
+    for track in &smf.tracks {
+        if data.get(position..position + 4)? != Tag::Track.binary() {
+            return None;
+        }
+        let end =
+            position + 8 + BigEndian::read_u32(data.get(position + 4..position + 8)?) as usize;
+        position += 8;
+        let mut pre_status_byte: Option<u8> = None;
+        let mut track_events = Vec::with_capacity(track.len());
+        for message in track {
+            let mut delta_time_bytes = 1;
+            while data.get(position + delta_time_bytes - 1)? & 0x80 != 0 {
+                delta_time_bytes += 1;
+            }
+            let binary = message.binary();
+            let body = &binary[VLQ::new(message.delta_time()).len()..];
+            let rest = data.get(position + delta_time_bytes..end)?;
+            let status_byte = status_byte(message);
+            let running = if rest.starts_with(body) {
+                false
+            } else if status_byte.is_some()
+                && pre_status_byte == status_byte
+                && rest.starts_with(&body[1..])
+            {
+                true
+            } else {
+                return None;
+            };
+            if status_byte.is_some() {
+                track_events.push((
+                    delta_time_bytes,
+                    Some(running).filter(|_| pre_status_byte == status_byte),
+                ));
+                pre_status_byte = status_byte;
+            } else {
+                track_events.push((delta_time_bytes, None));
+            }
+            position += delta_time_bytes + body.len() - running as usize;
+        }
+        if position != end {
+            return None;
+        }
+        events.push(track_events);
+    }
+    if position == data.len() {
+        Some(events)
+    } else {
+        None
+    }
+}
+
+fn status_byte(message: &Message) -> Option<u8> {
+    match *message {
+        Message::MidiEvent { ref event, .. } => Some(event.status_byte()),
+        _ => None,
+    }
+}
+
+fn padded_vlq(value: u32, bytes: usize) -> Vec<u8> {
+    (0..bytes)
+        .rev()
+        .map(|i| {
+            let septet = (value >> (7 * i)) as u8 & 0x7f;
+            if i > 0 {
+                septet | 0x80
+            } else {
+                septet
+            }
+        })
+        .collect()
+}
+impl fmt::Display for Text {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(
+            f,
+            "MThd format={} time_base={} running_status={}",
+            self.smf.format,
+            self.smf.time_base,
+            if self.running_status { "on" } else { "off" }
+        )?;
+        for (i, track) in self.smf.tracks.iter().enumerate() {
+            writeln!(f, "MTrk")?;
+            for (j, message) in track.iter().enumerate() {
+                match *message {
+                    Message::MetaEvent {
+                        delta_time,
+                        ref event,
+                        ref data,
+                    } => write!(f, "{} {}", delta_time, meta_event(event, data))?,
+                    Message::MidiEvent {
+                        delta_time,
+                        ref event,
+                    } => write!(f, "{} {}", delta_time, midi_event(event))?,
+                    Message::SysExEvent {
+                        delta_time,
+                        ref event,
+                        ref data,
+                    } => write!(
+                        f,
+                        "{} SysEx {:02x}{}",
+                        delta_time,
+                        event.status_byte(),
+                        hex(data)
+                    )?,
+                    Message::TrackChange => continue,
+                }
+                if let Some(encoding) = self.encodings.get(&(i, j)) {
+                    write!(f, " {}", encoding)?;
+                }
+                writeln!(f)?;
+            }
+        }
+        Ok(())
+    }
+}
+impl fmt::Display for Encoding {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let mut words = Vec::new();
+        if let Some(running_status) = self.running_status {
+            let running_status = if running_status { "on" } else { "off" };
+            words.push(format!("running_status={}", running_status));
+        }
+        if let Some(delta_time_bytes) = self.delta_time_bytes {
+            words.push(format!("delta_time_bytes={}", delta_time_bytes));
+        }
+        write!(f, "{}", words.join(" "))
+    }
+}
+impl FromStr for Text {
+    type Err = TextError;
+    fn from_str(text: &str) -> Result<Text, TextError> {
+        let mut parsed: Option<Text> = None;
+        for (i, line) in text.lines().enumerate() {
+            let line_number = i + 1;
+            let invalid = |reason: String| TextError::InvalidLine {
+                line: line_number,
+                reason,
+            };
+            let tokens = tokenize(line).map_err(&invalid)?;
+            if tokens.is_empty() {
+                continue;
+            }
+            match parsed {
+                None => parsed = Some(header(&tokens).map_err(&invalid)?),
+                Some(ref mut text) => match tokens[0] {
+                    Token::Word(ref word) if word == "MTrk" && tokens.len() == 1 => {
+                        text.smf.tracks.push(Vec::new())
+                    }
+                    _ => {
+                        let (tokens, encoding) = encoding(tokens).map_err(&invalid)?;
+                        let message = message(&tokens).map_err(&invalid)?;
+                        let default = text.running_status;
+                        let i = match text.smf.tracks.len() {
+                            0 => return Err(TextError::EventOutsideTrack { line: line_number }),
+                            len => len - 1,
+                        };
+                        let track = &mut text.smf.tracks[i];
+                        let encoding =
+                            check_encoding(encoding, &message, track, default).map_err(&invalid)?;
+                        if encoding != Encoding::default() {
+                            text.encodings.insert((i, track.len()), encoding);
+                        }
+                        track.push(message);
+                    }
+                },
+            }
+        }
+        parsed.ok_or(TextError::MissingHeader)
+    }
+}
+
+/// An enum represents errors of parsing Text.
+#[derive(PartialEq, Clone, Debug)]
+pub enum TextError {
+    /// Text doesn't have `MThd` header line.
+    MissingHeader,
+    /// Event line is found before the first `MTrk` line.
+    EventOutsideTrack { line: usize },
+    /// Line can't be parsed.
+    InvalidLine { line: usize, reason: String },
+}
+impl fmt::Display for TextError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        use text::TextError::*;
+        match *self {
+            MissingHeader => write!(f, "Text doesn't have MThd header line."),
+            EventOutsideTrack { line } => {
+                write!(f, "Event is found before MTrk line at line {}.", line)
+            }
+            InvalidLine { line, ref reason } => {
+                write!(f, "Invalid line {} has found: {}", line, reason)
+            }
+        }
+    }
+}
+impl error::Error for TextError {}
+
+#[derive(PartialEq, Debug)]
+enum Token {
+    Word(String),
+    Pair(String, String),
+    Quoted(Vec<u8>),
+}
+
+fn tokenize(line: &str) -> Result<Vec<Token>, String> {
+    let mut tokens = Vec::new();
+    let mut chars = line.chars().peekable();
+    while let Some(&c) = chars.peek() {
+        if c.is_whitespace() {
+            chars.next();
+        } else if c == '#' {
+            break;
+        } else if c == '"' {
+            chars.next();
+            let mut data = Vec::new();
+            loop {
+                match chars.next() {
+                    Some('"') => break,
+                    Some('\\') => match chars.next() {
+                        Some('"') => data.push(b'"'),
+                        Some('\\') => data.push(b'\\'),
+                        Some('n') => data.push(b'\n'),
+                        Some('r') => data.push(b'\r'),
+                        Some('t') => data.push(b'\t'),
+                        Some('x') => {
+                            let digits: String = chars.by_ref().take(2).collect();
+                            data.push(byte(&digits)?);
+                        }
+                        other => return Err(format!("invalid escape {:?}", other)),
+                    },
+                    Some(c) => {
+                        let mut buffer = [0; 4];
+                        data.extend_from_slice(c.encode_utf8(&mut buffer).as_bytes());
+                    }
+                    None => return Err("unterminated string".to_string()),
+                }
+            }
+            tokens.push(Token::Quoted(data));
+        } else {
+            let mut word = String::new();
+            while let Some(&c) = chars.peek() {
+                if c.is_whitespace() || c == '#' {
+                    break;
+                }
+                word.push(c);
+                chars.next();
+            }
+            tokens.push(match word.find('=') {
+                Some(index) => {
+                    Token::Pair(word[..index].to_string(), word[index + 1..].to_string())
+                }
+                None => Token::Word(word),
+            });
+        }
+    }
+    Ok(tokens)
+}
+
+fn header(tokens: &[Token]) -> Result<Text, String> {
+    if tokens[0] != Token::Word("MThd".to_string()) {
+        return Err("text must start with MThd line".to_string());
+    }
+    let mut arguments = Arguments::new(&tokens[1..])?;
+    let format = arguments.number("format")?;
+    let time_base = arguments.number("time_base")?;
+    let running_status = match arguments.value("running_status")?.as_str() {
+        "on" => true,
+        "off" => false,
+        other => return Err(format!("running_status must be on or off: {}", other)),
+    };
+    arguments.finish()?;
+    Ok(Text {
+        smf: Smf::new(format, time_base),
+        running_status,
+        encodings: HashMap::new(),
+    })
+}
+
+// Takes `running_status` and `delta_time_bytes` out of tokens of an event line.
+fn encoding(tokens: Vec<Token>) -> Result<(Vec<Token>, Encoding), String> {
+    let mut encoding = Encoding::default();
+    let mut rest = Vec::with_capacity(tokens.len());
+    for token in tokens {
+        match token {
+            Token::Pair(ref key, ref value) if key == "running_status" => {
+                encoding.running_status = Some(match value.as_str() {
+                    "on" => true,
+                    "off" => false,
+                    other => return Err(format!("running_status must be on or off: {}", other)),
+                });
+            }
+            Token::Pair(ref key, ref value) if key == "delta_time_bytes" => {
+                encoding.delta_time_bytes = Some(
+                    value
+                        .parse()
+                        .map_err(|_| format!("invalid value of {}: {}", key, value))?,
+                );
+            }
+            token => rest.push(token),
+        }
+    }
+    Ok((rest, encoding))
+}
+
+// Checks encoding of a message pushed to the track, and drops what is the same as the default.
+fn check_encoding(
+    encoding: Encoding,
+    message: &Message,
+    track: &[Message],
+    default: bool,
+) -> Result<Encoding, String> {
+    let running_status = match (encoding.running_status, status_byte(message)) {
+        (None, _) => None,
+        (Some(running_status), Some(status)) => {
+            let pre_status_byte = track.iter().rev().find_map(status_byte);
+            if running_status && pre_status_byte != Some(status) {
+                return Err(
+                    "running status needs the same status byte as the previous MIDI event"
+                        .to_string(),
+                );
+            }
+            Some(running_status).filter(|&running_status| running_status != default)
+        }
+        (Some(_), None) => return Err("running status is only for MIDI events".to_string()),
+    };
+    let minimal = VLQ::new(message.delta_time()).len();
+    let delta_time_bytes = match encoding.delta_time_bytes {
+        Some(bytes) if bytes < minimal || bytes > 4 => {
+            return Err(format!(
+                "delta_time_bytes must be from {} to 4: {}",
+                minimal, bytes
+            ))
+        }
+        bytes => bytes.filter(|&bytes| bytes != minimal),
+    };
+    Ok(Encoding {
+        running_status,
+        delta_time_bytes,
+    })
+}
+
+fn message(tokens: &[Token]) -> Result<Message, String> {
+    let (delta_time, name) = match (tokens.first(), tokens.get(1)) {
+        (Some(Token::Word(delta_time)), Some(Token::Word(name))) => (
+            delta_time
+                .parse::<u32>()
+                .map_err(|_| format!("invalid delta time: {}", delta_time))?,
+            name.as_str(),
+        ),
+        _ => return Err("event line must start with delta time and event name".to_string()),
+    };
+    let arguments = &tokens[2..];
+    if let Some(event) = midi_event_name(name) {
+        let mut arguments = Arguments::new(arguments)?;
+        let event = parse_midi_event(event, &mut arguments)?;
+        arguments.finish()?;
+        return Ok(Message::MidiEvent { delta_time, event });
+    }
+    let (event, data) = match name {
+        "SysEx" => {
+            let mut data = bytes(arguments)?;
+            if data.is_empty() {
+                return Err("SysEx needs status byte".to_string());
+            }
+            let status = data.remove(0);
+            return Ok(Message::SysExEvent {
+                delta_time,
+                event: SysExEvent::new(status),
+                data,
+            });
+        }
+        "Meta" => {
+            let mut data = bytes(arguments)?;
+            if data.is_empty() {
+                return Err("Meta needs event type byte".to_string());
+            }
+            let event_type = data.remove(0);
+            (MetaEvent::new(event_type), data)
+        }
+        _ => match meta_event_name(name) {
+            Some(event) => {
+                let data = parse_meta_data(&event, arguments)?;
+                (event, data)
+            }
+            None => return Err(format!("unknown event name: {}", name)),
+        },
+    };
+    Ok(Message::MetaEvent {
+        delta_time,
+        event,
+        data,
+    })
+}
+
+fn midi_event_name(name: &str) -> Option<u8> {
+    Some(match name {
+        "NoteOff" => 0x80,
+        "NoteOn" => 0x90,
+        "PolyphonicKeyPressure" => 0xa0,
+        "ControlChange" => 0xb0,
+        "ProgramChange" => 0xc0,
+        "ChannelPressure" => 0xd0,
+        "PitchBendChange" => 0xe0,
+        "UnknownMidiEvent" => 0x00,
+        _ => return None,
+    })
+}
+
+fn parse_midi_event(status: u8, arguments: &mut Arguments) -> Result<MidiEvent, String> {
+    let ch = arguments.number_in("ch", 0, 15)?;
+    Ok(match status {
+        0x80 => MidiEvent::NoteOff {
+            ch,
+            note: arguments.number_in("key", 0, 127)?,
+            velocity: arguments.number_in("vel", 0, 127)?,
+        },
+        0x90 => MidiEvent::NoteOn {
+            ch,
+            note: arguments.number_in("key", 0, 127)?,
+            velocity: arguments.number_in("vel", 0, 127)?,
+        },
+        0xa0 => MidiEvent::PolyphonicKeyPressure {
+            ch,
+            note: arguments.number_in("key", 0, 127)?,
+            velocity: arguments.number_in("pressure", 0, 127)?,
+        },
+        0xb0 => MidiEvent::ControlChange {
+            ch,
+            control: arguments.number_in("control", 0, 127)?,
+            data: arguments.number_in("value", 0, 127)?,
+        },
+        0xc0 => MidiEvent::ProgramChange {
+            ch,
+            program: arguments.number_in("program", 0, 127)?,
+        },
+        0xd0 => MidiEvent::ChannelPressure {
+            ch,
+            pressure: arguments.number_in("pressure", 0, 127)?,
+        },
+        0xe0 => MidiEvent::PitchBendChange {
+            ch,
+            data: arguments.number_in("value", -8192, 8191)?,
+        },
+        _ => MidiEvent::Unknown { ch },
+    })
+}
+
+fn midi_event(event: &MidiEvent) -> String {
+    use messages::MidiEvent::*;
+    match *event {
+        NoteOff { ch, note, velocity } => {
+            format!("NoteOff ch={} key={} vel={}", ch, note, velocity)
+        }
+        NoteOn { ch, note, velocity } => format!("NoteOn ch={} key={} vel={}", ch, note, velocity),
+        PolyphonicKeyPressure { ch, note, velocity } => format!(
+            "PolyphonicKeyPressure ch={} key={} pressure={}",
+            ch, note, velocity
+        ),
+        ControlChange { ch, control, data } => {
+            format!("ControlChange ch={} control={} value={}", ch, control, data)
+        }
+        ProgramChange { ch, program } => format!("ProgramChange ch={} program={}", ch, program),
+        ChannelPressure { ch, pressure } => {
+            format!("ChannelPressure ch={} pressure={}", ch, pressure)
+        }
+        PitchBendChange { ch, data } => format!("PitchBendChange ch={} value={}", ch, data),
+        Unknown { ch } => format!("UnknownMidiEvent ch={}", ch),
+    }
+}
+
+fn meta_event_name(name: &str) -> Option<MetaEvent> {
+    use messages::MetaEvent::*;
+    [
+        SequenceNumber,
+        TextEvent,
+        CopyrightNotice,
+        SequenceOrTrackName,
+        InstrumentName,
+        Lyric,
+        Marker,
+        CuePoint,
+        MIDIChannelPrefix,
+        MidiPort,
+        EndOfTrack,
+        SetTempo,
+        SMTPEOffset,
+        TimeSignature,
+        KeySignature,
+        SequencerSpecificMetaEvent,
+    ]
+    .iter()
+    .find(|event| event.to_string() == name)
+    .cloned()
+}
+
+// Argument names of meta events written with named arguments instead of raw data bytes.
+fn meta_fields(event: &MetaEvent) -> &'static [&'static str] {
+    use messages::MetaEvent::*;
+    match *event {
+        SequenceNumber => &["number"],
+        MIDIChannelPrefix => &["ch"],
+        MidiPort => &["port"],
+        SetTempo => &["tempo"],
+        SMTPEOffset => &["hr", "mn", "se", "fr", "ff"],
+        TimeSignature => &["nn", "dd", "cc", "bb"],
+        KeySignature => &["sf", "mi"],
+        _ => &[],
+    }
+}
+
+fn meta_len(event: &MetaEvent) -> usize {
+    match *event {
+        MetaEvent::SequenceNumber | MetaEvent::KeySignature => 2,
+        MetaEvent::SetTempo => 3,
+        _ => meta_fields(event).len(),
+    }
+}
+
+fn meta_event(event: &MetaEvent, data: &[u8]) -> String {
+    use messages::MetaEvent::*;
+    if let Unknown { event_type } = *event {
+        return format!("Meta {:02x}{}", event_type, hex(data));
+    }
+    if event.is_text() {
+        return format!("{} {}", event, quote(data));
+    }
+    let fields = meta_fields(event);
+    if fields.is_empty() || data.len() != meta_len(event) {
+        return format!("{}{}", event, hex(data));
+    }
+    let values: Vec<String> = match *event {
+        SequenceNumber => vec![BigEndian::read_u16(data).to_string()],
+        SetTempo => vec![BigEndian::read_u24(data).to_string()],
+        KeySignature => vec![(data[0] as i8).to_string(), data[1].to_string()],
+        _ => data.iter().map(|value| value.to_string()).collect(),
+    };
+    let arguments: Vec<String> = fields
+        .iter()
+        .zip(values.iter())
+        .map(|(field, value)| format!(" {}={}", field, value))
+        .collect();
+    format!("{}{}", event, arguments.concat())
+}
+
+fn parse_meta_data(event: &MetaEvent, tokens: &[Token]) -> Result<Vec<u8>, String> {
+    match tokens.first() {
+        Some(Token::Quoted(data)) if tokens.len() == 1 => return Ok(data.clone()),
+        Some(Token::Pair(..)) => (),
+        _ => return bytes(tokens),
+    }
+    let fields = meta_fields(event);
+    if fields.is_empty() {
+        return Err(format!("{} doesn't have named arguments", event));
+    }
+    let mut arguments = Arguments::new(tokens)?;
+    let mut data = Vec::with_capacity(meta_len(event));
+    match *event {
+        MetaEvent::SequenceNumber => {
+            let mut buffer = [0; 2];
+            BigEndian::write_u16(&mut buffer, arguments.number("number")?);
+            data.extend_from_slice(&buffer);
+        }
+        MetaEvent::SetTempo => {
+            let tempo: u32 = arguments.number("tempo")?;
+            if tempo > 0xff_ffff {
+                return Err(format!("tempo is out of range: {}", tempo));
+            }
+            let mut buffer = [0; 3];
+            BigEndian::write_u24(&mut buffer, tempo);
+            data.extend_from_slice(&buffer);
+        }
+        MetaEvent::KeySignature => {
+            data.push(arguments.number::<i8>("sf")? as u8);
+            data.push(arguments.number("mi")?);
+        }
+        _ => {
+            for field in fields {
+                data.push(arguments.number(field)?);
+            }
+        }
+    }
+    arguments.finish()?;
+    Ok(data)
+}
+
+struct Arguments {
+    pairs: HashMap<String, String>,
+}
+impl Arguments {
+    fn new(tokens: &[Token]) -> Result<Arguments, String> {
+        let mut pairs = HashMap::new();
+        for token in tokens {
+            match *token {
+                Token::Pair(ref key, ref value) => {
+                    if pairs.insert(key.clone(), value.clone()).is_some() {
+                        return Err(format!("duplicated argument: {}", key));
+                    }
+                }
+                _ => return Err(format!("unexpected argument: {:?}", token)),
+            }
+        }
+        Ok(Arguments { pairs })
+    }
+    fn value(&mut self, key: &str) -> Result<String, String> {
+        self.pairs
+            .remove(key)
+            .ok_or_else(|| format!("missing argument: {}", key))
+    }
+    fn number<T: FromStr>(&mut self, key: &str) -> Result<T, String> {
+        let value = self.value(key)?;
+        value
+            .parse()
+            .map_err(|_| format!("invalid value of {}: {}", key, value))
+    }
+    fn number_in<T: FromStr + PartialOrd + fmt::Display>(
+        &mut self,
+        key: &str,
+        min: T,
+        max: T,
+    ) -> Result<T, String> {
+        let value: T = self.number(key)?;
+        if value < min || value > max {
+            return Err(format!(
+                "{} must be from {} to {}: {}",
+                key, min, max, value
+            ));
+        }
+        Ok(value)
+    }
+    fn finish(self) -> Result<(), String> {
+        match self.pairs.keys().next() {
+            Some(key) => Err(format!("unknown argument: {}", key)),
+            None => Ok(()),
+        }
+    }
+}
+
+fn bytes(tokens: &[Token]) -> Result<Vec<u8>, String> {
+    tokens
+        .iter()
+        .map(|token| match *token {
+            Token::Word(ref word) => byte(word),
+            _ => Err(format!("expected hex byte: {:?}", token)),
+        })
+        .collect()
+}
+
+fn byte(digits: &str) -> Result<u8, String> {
+    if digits.len() != 2 {
+        return Err(format!("invalid hex byte: {}", digits));
+    }
+    u8::from_str_radix(digits, 16).map_err(|_| format!("invalid hex byte: {}", digits))
+}
+
+fn hex(data: &[u8]) -> String {
+    data.iter().map(|byte| format!(" {:02x}", byte)).collect()
+}
+
+fn quote(data: &[u8]) -> String {
+    let mut quoted = String::from("\"");
+    match str::from_utf8(data) {
+        Ok(text) => {
+            for c in text.chars() {
+                if c.is_ascii() || c.is_control() {
+                    let mut buffer = [0; 4];
+                    for &byte in c.encode_utf8(&mut buffer).as_bytes() {
+                        escape(byte, &mut quoted);
+                    }
+                } else {
+                    quoted.push(c);
+                }
+            }
+        }
+        Err(_) => {
+            for &byte in data {
+                escape(byte, &mut quoted);
+            }
+        }
+    }
+    quoted.push('"');
+    quoted
+}
+
+fn escape(byte: u8, quoted: &mut String) {
+    match byte {
+        b'"' => quoted.push_str("\\\""),
+        b'\\' => quoted.push_str("\\\\"),
+        b'\n' => quoted.push_str("\\n"),
+        b'\r' => quoted.push_str("\\r"),
+        b'\t' => quoted.push_str("\\t"),
+        0x20..=0x7e => quoted.push(byte as char),
+        _ => quoted.push_str(&format!("\\x{:02x}", byte)),
+    }
+}
+
+#[cfg(test)]
+mod text_tests {
+    use smf::Smf;
+    use text::*;
+
+    fn meta(event: MetaEvent, data: &[u8]) -> Message {
+        Message::MetaEvent {
+            delta_time: 0,
+            event,
+            data: data.to_vec(),
+        }
+    }
+    fn midi(delta_time: u32, event: MidiEvent) -> Message {
+        Message::MidiEvent { delta_time, event }
+    }
+    #[test]
+    fn round_trip() {
+        let mut smf = Smf::new(1, 0xe728);
+        smf.tracks.push(vec![
+            meta(MetaEvent::SequenceNumber, &[0x01, 0x02]),
+            meta(MetaEvent::SequenceNumber, &[]),
+            meta(
+                MetaEvent::SequenceOrTrackName,
+                "寿限無 \"x\"\\\n".as_bytes(),
+            ),
+            meta(MetaEvent::Lyric, &[0x82, 0xa0, 0x00]),
+            meta(MetaEvent::SetTempo, &[0x07, 0xa1, 0x20]),
+            meta(MetaEvent::SetTempo, &[0x07, 0xa1]),
+            meta(MetaEvent::SMTPEOffset, &[0x60, 0, 3, 4, 5]),
+            meta(MetaEvent::TimeSignature, &[6, 3, 24, 8]),
+            meta(MetaEvent::KeySignature, &[0xfd, 1]),
+            meta(MetaEvent::MIDIChannelPrefix, &[9]),
+            meta(MetaEvent::MidiPort, &[1]),
+            meta(MetaEvent::SequencerSpecificMetaEvent, &[0x43, 0x7b]),
+            meta(MetaEvent::Unknown { event_type: 0x60 }, &[1, 2]),
+            meta(MetaEvent::EndOfTrack, &[]),
+        ]);
+        smf.tracks.push(vec![
+            midi(
+                0,
+                MidiEvent::NoteOn {
+                    ch: 0,
+                    note: 60,
+                    velocity: 100,
+                },
+            ),
+            midi(
+                10,
+                MidiEvent::NoteOff {
+                    ch: 15,
+                    note: 60,
+                    velocity: 127,
+                },
+            ),
+            midi(
+                0,
+                MidiEvent::PolyphonicKeyPressure {
+                    ch: 1,
+                    note: 2,
+                    velocity: 3,
+                },
+            ),
+            midi(
+                0,
+                MidiEvent::ControlChange {
+                    ch: 1,
+                    control: 7,
+                    data: 100,
+                },
+            ),
+            midi(0, MidiEvent::ProgramChange { ch: 1, program: 40 }),
+            midi(
+                0,
+                MidiEvent::ChannelPressure {
+                    ch: 1,
+                    pressure: 64,
+                },
+            ),
+            midi(0, MidiEvent::PitchBendChange { ch: 1, data: -8192 }),
+            midi(0, MidiEvent::Unknown { ch: 2 }),
+            Message::SysExEvent {
+                delta_time: 0xfff_ffff,
+                event: SysExEvent::F0,
+                data: vec![0x7e, 0x7f, 0x09, 0x01, 0xf7],
+            },
+            Message::SysExEvent {
+                delta_time: 0,
+                event: SysExEvent::Unknown { status: 0xf5 },
+                data: Vec::new(),
+            },
+        ]);
+        smf.tracks.push(Vec::new());
+        for &running_status in &[false, true] {
+            let mut text = Text::new(smf.clone());
+            text.running_status = running_status;
+            let parsed: Text = text.to_string().parse().unwrap();
+            assert_eq!(parsed, text);
+            assert_eq!(parsed.to_bytes(), text.to_bytes());
+        }
+        let text = Text::new(smf).to_string();
+        assert!(text.contains("0 SequenceNumber number=258\n"));
+        assert!(text.contains("0 SequenceNumber\n"));
+        assert!(text.contains("0 SequenceOrTrackName \"寿限無 \\\"x\\\"\\\\\\n\"\n"));
+        assert!(text.contains("0 Lyric \"\\x82\\xa0\\x00\"\n"));
+        assert!(text.contains("0 SetTempo tempo=500000\n"));
+        assert!(text.contains("0 SetTempo 07 a1\n"));
+        assert!(text.contains("0 KeySignature sf=-3 mi=1\n"));
+        assert!(text.contains("0 Meta 60 01 02\n"));
+        assert!(text.contains("268435455 SysEx f0 7e 7f 09 01 f7\n"));
+        assert!(text.ends_with("0 SysEx f5\nMTrk\n"));
+    }
+    #[test]
+    fn partial_running_status_and_long_delta_time() {
+        let data = [
+            0x4d, 0x54, 0x68, 0x64, 0x00, 0x00, 0x00, 0x06, 0x00, 0x00, 0x00, 0x01, 0x01, 0xe0,
+            0x4d, 0x54, 0x72, 0x6b, 0x00, 0x00, 0x00, 0x10, 0x00, 0x90, 0x3c, 0x64, 0x00, 0x3e,
+            0x64, 0x00, 0x90, 0x40, 0x64, 0x80, 0x00, 0xff, 0x2f, 0x00,
+        ];
+        let text = Text::from_bytes(&data).unwrap();
+        assert!(text.running_status);
+        assert_eq!(text.encodings.len(), 2);
+        assert_eq!(
+            text.encodings[&(0, 2)],
+            Encoding {
+                running_status: Some(false),
+                delta_time_bytes: None,
+            }
+        );
+        assert_eq!(
+            text.encodings[&(0, 3)],
+            Encoding {
+                running_status: None,
+                delta_time_bytes: Some(2),
+            }
+        );
+        assert_eq!(text.to_bytes(), &data[..]);
+        let string = text.to_string();
+        assert!(string.contains("0 NoteOn ch=0 key=64 vel=100 running_status=off\n"));
+        assert!(string.contains("0 EndOfTrack delta_time_bytes=2\n"));
+        let parsed: Text = string.parse().unwrap();
+        assert_eq!(parsed, text);
+        assert_eq!(parsed.to_bytes(), &data[..]);
+
+        let mut unknown_chunk = data.to_vec();
+        unknown_chunk.extend_from_slice(&[0x58, 0x46, 0x49, 0x48, 0x00, 0x00, 0x00, 0x00]);
+        assert!(Text::from_bytes(&unknown_chunk).is_err());
+    }
+    #[test]
+    fn parse_comments_and_blank_lines() {
+        let text: Text = "# fixture\n\
+                          MThd format=0 time_base=96 running_status=on  # header\n\
+                          \n\
+                          MTrk\n  \
+                          0 Marker \"# not a comment\" # comment\n  \
+                          0 EndOfTrack\n"
+            .parse()
+            .unwrap();
+        assert!(text.running_status);
+        assert_eq!(
+            text.smf.tracks,
+            vec![vec![
+                meta(MetaEvent::Marker, b"# not a comment"),
+                meta(MetaEvent::EndOfTrack, &[]),
+            ]]
+        );
+    }
+    #[test]
+    fn parse_errors() {
+        assert_eq!("".parse::<Text>(), Err(TextError::MissingHeader));
+        assert_eq!(
+            "MThd format=1 time_base=480 running_status=off\n0 EndOfTrack".parse::<Text>(),
+            Err(TextError::EventOutsideTrack { line: 2 })
+        );
+        for (text, line) in &[
+            ("MTrk", 1),
+            ("MThd format=1 time_base=480", 1),
+            ("MThd format=1 time_base=480 running_status=off foo=1", 1),
+            ("MThd format=1 time_base=480 running_status=off\nMTrk\n0 NoteOn ch=0 key=60", 3),
+            ("MThd format=1 time_base=480 running_status=off\nMTrk\n0 NoteOn ch=0 key=60 vel=256", 3),
+            ("MThd format=1 time_base=480 running_status=off\nMTrk\n0 NoteOn ch=16 key=60 vel=100", 3),
+            ("MThd format=1 time_base=480 running_status=off\nMTrk\n0 NoteOn ch=0 key=200 vel=100", 3),
+            ("MThd format=1 time_base=480 running_status=off\nMTrk\n0 NoteOff ch=0 key=60 vel=128", 3),
+            ("MThd format=1 time_base=480 running_status=off\nMTrk\n0 ControlChange ch=0 control=128 value=0", 3),
+            ("MThd format=1 time_base=480 running_status=off\nMTrk\n0 ProgramChange ch=0 program=128", 3),
+            ("MThd format=1 time_base=480 running_status=off\nMTrk\n0 ChannelPressure ch=0 pressure=128", 3),
+            ("MThd format=1 time_base=480 running_status=off\nMTrk\n0 PitchBendChange ch=0 value=9000", 3),
+            ("MThd format=1 time_base=480 running_status=off\nMTrk\n0 PitchBendChange ch=0 value=-8193", 3),
+            ("MThd format=1 time_base=480 running_status=off\nMTrk\n0 NoteOnn ch=0", 3),
+            ("MThd format=1 time_base=480 running_status=off\nMTrk\n0 Lyric \"a", 3),
+            ("MThd format=1 time_base=480 running_status=off\nMTrk\n0 Lyric a=1", 3),
+            ("MThd format=1 time_base=480 running_status=off\nMTrk\n0 SetTempo tempo=16777216", 3),
+            ("MThd format=1 time_base=480 running_status=off\nMTrk\n0 Meta", 3),
+            ("MThd format=1 time_base=480 running_status=off\nMTrk\n0 SysEx f", 3),
+            ("MThd format=1 time_base=480 running_status=off\nMTrk\n-1 EndOfTrack", 3),
+            ("MThd format=1 time_base=480 running_status=off\nMTrk\n0 EndOfTrack running_status=on", 3),
+            ("MThd format=1 time_base=480 running_status=off\nMTrk\n0 NoteOn ch=0 key=60 vel=1 running_status=on", 3),
+            ("MThd format=1 time_base=480 running_status=off\nMTrk\n0 NoteOn ch=0 key=60 vel=1\n0 NoteOn ch=1 key=60 vel=1 running_status=on", 4),
+            ("MThd format=1 time_base=480 running_status=off\nMTrk\n0 EndOfTrack delta_time_bytes=5", 3),
+            ("MThd format=1 time_base=480 running_status=off\nMTrk\n128 EndOfTrack delta_time_bytes=1", 3),
+        ] {
+            match text.parse::<Text>() {
+                Err(TextError::InvalidLine { line: error_line, .. }) => {
+                    assert_eq!(error_line, *line, "{}", text)
+                }
+                other => panic!("{}: {:?}", text, other),
+            }
+        }
+    }
+}
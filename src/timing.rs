@@ -0,0 +1,626 @@
+use messages::*;
+use reader::Handler;
+use std::fmt;
+
+/// Default tempo (microseconds per quarter note) of SMF, 120 bpm.
+pub const DEFAULT_TEMPO: u32 = 500_000;
+
+/// A struct representing tempo changes of SMF to convert between ticks and seconds.
+///
+/// It works as Handler to collect SetTempo meta events from all tracks.
+///
+/// # Examples
+///
+/// ```
+/// use ghakuf::reader::Reader;
+/// use ghakuf::timing::TempoMap;
+/// use std::path;
+///
+/// let path = path::Path::new("tests/test.mid");
+/// let mut tempo_map = TempoMap::new(480);
+/// {
+///     let mut reader = Reader::new(&mut tempo_map, &path).unwrap();
+///     assert!(reader.read().is_ok());
+/// }
+/// assert_eq!(tempo_map.tempo(0), 588_235);
+/// assert_eq!(tempo_map.tick(tempo_map.seconds(960)), 960);
+/// ```
+#[derive(PartialEq, Clone, Debug)]
+pub struct TempoMap {
+    time_base: u16,
+    tempos: Vec<(u64, u32)>,
+    tick: u64,
+}
+impl TempoMap {
+    /// Builds TempoMap with time base (ticks per quarter note, or SMPTE format) and default tempo.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use ghakuf::timing::TempoMap;
+    ///
+    /// let tempo_map = TempoMap::new(480);
+    /// assert_eq!(tempo_map.seconds(480), 0.5);
+    /// ```
+    pub fn new(time_base: u16) -> TempoMap {
+        TempoMap {
+            time_base,
+            tempos: vec![(0, DEFAULT_TEMPO)],
+            tick: 0,
+        }
+    }
+    /// Returns time base.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use ghakuf::timing::TempoMap;
+    ///
+    /// assert_eq!(TempoMap::new(960).time_base(), 960);
+    /// ```
+    pub fn time_base(&self) -> u16 {
+        self.time_base
+    }
+    /// Sets tempo (microseconds per quarter note) from tick. Tempo at the same tick is replaced.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use ghakuf::timing::TempoMap;
+    ///
+    /// let mut tempo_map = TempoMap::new(480);
+    /// tempo_map.push(960, 1_000_000).push(0, 250_000);
+    /// assert_eq!(tempo_map.tempo(959), 250_000);
+    /// assert_eq!(tempo_map.tempo(960), 1_000_000);
+    /// ```
+    pub fn push(&mut self, tick: u64, tempo: u32) -> &mut TempoMap {
+        match self.tempos.binary_search_by_key(&tick, |&(tick, _)| tick) {
+            Ok(index) => self.tempos[index].1 = tempo,
+            Err(index) => self.tempos.insert(index, (tick, tempo)),
+        }
+        self
+    }
+    /// Returns tempo changes as pairs of tick and tempo in order of tick.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use ghakuf::timing::{TempoMap, DEFAULT_TEMPO};
+    ///
+    /// assert_eq!(TempoMap::new(480).tempos(), [(0, DEFAULT_TEMPO)]);
+    /// ```
+    pub fn tempos(&self) -> &[(u64, u32)] {
+        &self.tempos
+    }
+    /// Returns tempo (microseconds per quarter note) at tick.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use ghakuf::timing::{TempoMap, DEFAULT_TEMPO};
+    ///
+    /// assert_eq!(TempoMap::new(480).tempo(12345), DEFAULT_TEMPO);
+    /// ```
+    pub fn tempo(&self, tick: u64) -> u32 {
+        self.tempos[self.segment(tick)].1
+    }
+    /// Returns time in seconds at tick.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use ghakuf::timing::TempoMap;
+    ///
+    /// let mut tempo_map = TempoMap::new(480);
+    /// tempo_map.push(480, 1_000_000);
+    /// assert_eq!(tempo_map.seconds(960), 1.5);
+    ///
+    /// // 25 fps and 40 ticks per frame (millisecond resolution)
+    /// let tempo_map = TempoMap::new(0xe728);
+    /// assert_eq!(tempo_map.seconds(1500), 1.5);
+    /// ```
+    pub fn seconds(&self, tick: u64) -> f64 {
+        if let Some(ticks_per_second) = self.smpte_ticks_per_second() {
+            return tick as f64 / ticks_per_second;
+        }
+        let mut seconds = 0.0;
+        for (i, &(start, tempo)) in self.tempos.iter().enumerate() {
+            if start >= tick {
+                break;
+            }
+            let end = match self.tempos.get(i + 1) {
+                Some(&(next, _)) if next < tick => next,
+                _ => tick,
+            };
+            seconds += self.segment_seconds(end - start, tempo);
+        }
+        seconds
+    }
+    /// Returns tick at time in seconds (rounded down).
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use ghakuf::timing::TempoMap;
+    ///
+    /// let mut tempo_map = TempoMap::new(480);
+    /// tempo_map.push(480, 1_000_000);
+    /// assert_eq!(tempo_map.tick(1.5), 960);
+    /// assert_eq!(tempo_map.tick(0.25), 240);
+    /// ```
+    pub fn tick(&self, seconds: f64) -> u64 {
+        if seconds <= 0.0 {
+            return 0;
+        }
+        if let Some(ticks_per_second) = self.smpte_ticks_per_second() {
+            return (seconds * ticks_per_second + 1e-9) as u64;
+        }
+        let mut elapsed = 0.0;
+        for (i, &(start, tempo)) in self.tempos.iter().enumerate() {
+            let ticks_per_second = self.time_base as f64 * 1_000_000.0 / tempo as f64;
+            if let Some(&(next, _)) = self.tempos.get(i + 1) {
+                let duration = self.segment_seconds(next - start, tempo);
+                if elapsed + duration <= seconds {
+                    elapsed += duration;
+                    continue;
+                }
+            }
+            return start + ((seconds - elapsed) * ticks_per_second + 1e-6) as u64;
+        }
+        0
+    }
+    fn segment(&self, tick: u64) -> usize {
+        match self.tempos.binary_search_by_key(&tick, |&(tick, _)| tick) {
+            Ok(index) => index,
+            Err(index) => index.saturating_sub(1),
+        }
+    }
+    fn segment_seconds(&self, ticks: u64, tempo: u32) -> f64 {
+        ticks as f64 * tempo as f64 / (self.time_base as f64 * 1_000_000.0)
+    }
+    fn smpte_ticks_per_second(&self) -> Option<f64> {
+        if self.time_base & 0x8000 == 0 {
+            return None;
+        }
+        let fps = match -((self.time_base >> 8) as u8 as i8) {
+            29 => 29.97,
+            fps => fps as f64,
+        };
+        Some(fps * (self.time_base & 0xff) as f64)
+    }
+}
+impl Handler for TempoMap {
+    fn header(&mut self, _: u16, _: u16, time_base: u16) {
+        self.time_base = time_base;
+    }
+    fn meta_event(&mut self, delta_time: u32, event: &MetaEvent, data: &Vec<u8>) {
+        self.tick += delta_time as u64;
+        if *event == MetaEvent::SetTempo && data.len() >= 3 {
+            let tempo = (data[0] as u32) << 16 | (data[1] as u32) << 8 | data[2] as u32;
+            let tick = self.tick;
+            self.push(tick, tempo);
+        }
+    }
+    fn midi_event(&mut self, delta_time: u32, _: &MidiEvent) {
+        self.tick += delta_time as u64;
+    }
+    fn sys_ex_event(&mut self, delta_time: u32, _: &SysExEvent, _: &Vec<u8>) {
+        self.tick += delta_time as u64;
+    }
+    fn track_change(&mut self) {
+        self.tick = 0;
+    }
+}
+
+/// A struct representing time signature (meter) of SMF.
+///
+/// Meters whose numerator is a multiple of 3 greater than 3 (6/8, 9/8, 12/8, 6/4...) are compound meters,
+/// and their beat is a dotted note of three denominator notes.
+///
+/// # Examples
+///
+/// ```
+/// use ghakuf::timing::Meter;
+///
+/// let meter = Meter::new(6, 8);
+/// assert!(meter.is_compound());
+/// assert_eq!(meter.beats(), 2);
+/// assert_eq!(meter.beat_ticks(480), 720);
+/// assert_eq!(meter.bar_ticks(480), 1440);
+/// ```
+#[derive(PartialEq, Eq, Hash, Clone, Copy, Debug)]
+pub struct Meter {
+    /// Numerator of time signature
+    pub numerator: u8,
+    /// Denominator of time signature (note value, not the power of 2 in SMF)
+    pub denominator: u8,
+}
+impl Meter {
+    /// Builds Meter from numerator and denominator (note value).
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use ghakuf::timing::Meter;
+    ///
+    /// let meter: Meter = Meter::new(3, 4);
+    /// ```
+    pub fn new(numerator: u8, denominator: u8) -> Meter {
+        Meter {
+            numerator,
+            denominator,
+        }
+    }
+    /// Builds Meter from TimeSignature meta event data, or returns None if data is too short.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use ghakuf::timing::Meter;
+    ///
+    /// assert_eq!(Meter::from_data(&[0x06, 0x03, 0x24, 0x08]), Some(Meter::new(6, 8)));
+    /// assert_eq!(Meter::from_data(&[0x06]), None);
+    /// ```
+    pub fn from_data(data: &[u8]) -> Option<Meter> {
+        if data.len() < 2 {
+            return None;
+        }
+        Some(Meter::new(data[0], 1 << data[1].min(7)))
+    }
+    /// Checks whether the meter is compound or not.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use ghakuf::timing::Meter;
+    ///
+    /// assert!(Meter::new(12, 8).is_compound());
+    /// assert!(!Meter::new(3, 8).is_compound());
+    /// ```
+    // u8::is_multiple_of needs Rust 1.87
+    #[allow(clippy::manual_is_multiple_of)]
+    pub fn is_compound(&self) -> bool {
+        self.numerator > 3 && self.numerator % 3 == 0
+    }
+    /// Returns number of beats in a bar.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use ghakuf::timing::Meter;
+    ///
+    /// assert_eq!(Meter::new(9, 8).beats(), 3);
+    /// assert_eq!(Meter::new(5, 4).beats(), 5);
+    /// ```
+    pub fn beats(&self) -> u32 {
+        if self.is_compound() {
+            self.numerator as u32 / 3
+        } else {
+            self.numerator.max(1) as u32
+        }
+    }
+    /// Returns length of a beat in ticks at time base (ticks per quarter note).
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use ghakuf::timing::Meter;
+    ///
+    /// assert_eq!(Meter::new(3, 4).beat_ticks(480), 480);
+    /// assert_eq!(Meter::new(2, 2).beat_ticks(480), 960);
+    /// ```
+    pub fn beat_ticks(&self, time_base: u16) -> u64 {
+        let note = (time_base as u64 * 4 / self.denominator.max(1) as u64).max(1);
+        if self.is_compound() {
+            note * 3
+        } else {
+            note
+        }
+    }
+    /// Returns length of a bar in ticks at time base (ticks per quarter note).
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use ghakuf::timing::Meter;
+    ///
+    /// assert_eq!(Meter::new(3, 4).bar_ticks(480), 1440);
+    /// ```
+    pub fn bar_ticks(&self, time_base: u16) -> u64 {
+        self.beat_ticks(time_base) * self.beats() as u64
+    }
+}
+impl Default for Meter {
+    fn default() -> Self {
+        Meter::new(4, 4)
+    }
+}
+impl fmt::Display for Meter {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}/{}", self.numerator, self.denominator)
+    }
+}
+
+/// A struct representing time signature changes of SMF to convert between ticks and musical time
+/// (1-based bar, 1-based beat and tick in the beat).
+///
+/// It works as Handler to collect TimeSignature meta events from all tracks. Meter change in the middle
+/// of a bar cuts the bar short and starts a new bar at the change.
+///
+/// # Examples
+///
+/// ```
+/// use ghakuf::timing::{Meter, MeterMap};
+///
+/// let mut meter_map = MeterMap::new(480);
+/// meter_map.push(3840, Meter::new(6, 8)); // 6/8 from bar 3
+/// assert_eq!(meter_map.position(0), (1, 1, 0));
+/// assert_eq!(meter_map.position(2400), (2, 2, 0));
+/// assert_eq!(meter_map.position(3840 + 720 + 10), (3, 2, 10));
+/// assert_eq!(meter_map.tick(3, 2, 10), 3840 + 720 + 10);
+/// ```
+#[derive(PartialEq, Clone, Debug)]
+pub struct MeterMap {
+    time_base: u16,
+    meters: Vec<(u64, Meter)>,
+    tick: u64,
+}
+impl MeterMap {
+    /// Builds MeterMap with time base (ticks per quarter note) and 4/4 meter.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use ghakuf::timing::{Meter, MeterMap};
+    ///
+    /// let meter_map = MeterMap::new(480);
+    /// assert_eq!(meter_map.meter(0), Meter::new(4, 4));
+    /// ```
+    pub fn new(time_base: u16) -> MeterMap {
+        MeterMap {
+            time_base,
+            meters: vec![(0, Meter::default())],
+            tick: 0,
+        }
+    }
+    /// Returns time base.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use ghakuf::timing::MeterMap;
+    ///
+    /// assert_eq!(MeterMap::new(960).time_base(), 960);
+    /// ```
+    pub fn time_base(&self) -> u16 {
+        self.time_base
+    }
+    /// Sets meter from tick. Meter at the same tick is replaced.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use ghakuf::timing::{Meter, MeterMap};
+    ///
+    /// let mut meter_map = MeterMap::new(480);
+    /// meter_map.push(1920, Meter::new(3, 4)).push(0, Meter::new(2, 4));
+    /// assert_eq!(meter_map.meter(1919), Meter::new(2, 4));
+    /// assert_eq!(meter_map.meter(1920), Meter::new(3, 4));
+    /// ```
+    pub fn push(&mut self, tick: u64, meter: Meter) -> &mut MeterMap {
+        match self.meters.binary_search_by_key(&tick, |&(tick, _)| tick) {
+            Ok(index) => self.meters[index].1 = meter,
+            Err(index) => self.meters.insert(index, (tick, meter)),
+        }
+        self
+    }
+    /// Returns meter changes as pairs of tick and meter in order of tick.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use ghakuf::timing::{Meter, MeterMap};
+    ///
+    /// assert_eq!(MeterMap::new(480).meters(), [(0, Meter::new(4, 4))]);
+    /// ```
+    pub fn meters(&self) -> &[(u64, Meter)] {
+        &self.meters
+    }
+    /// Returns meter at tick.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use ghakuf::timing::{Meter, MeterMap};
+    ///
+    /// assert_eq!(MeterMap::new(480).meter(12345), Meter::new(4, 4));
+    /// ```
+    pub fn meter(&self, tick: u64) -> Meter {
+        match self.meters.binary_search_by_key(&tick, |&(tick, _)| tick) {
+            Ok(index) => self.meters[index].1,
+            Err(index) => self.meters[index.saturating_sub(1)].1,
+        }
+    }
+    /// Returns musical time (1-based bar, 1-based beat and tick in the beat) at tick.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use ghakuf::timing::{Meter, MeterMap};
+    ///
+    /// let mut meter_map = MeterMap::new(480);
+    /// meter_map.push(0, Meter::new(3, 4));
+    /// assert_eq!(meter_map.position(1440 * 2 + 500), (3, 2, 20));
+    /// ```
+    pub fn position(&self, tick: u64) -> (u32, u32, u32) {
+        let (start, bar, meter) = self.segment(tick);
+        let bar_ticks = meter.bar_ticks(self.time_base);
+        let beat_ticks = meter.beat_ticks(self.time_base);
+        let offset = (tick - start) % bar_ticks;
+        (
+            bar + ((tick - start) / bar_ticks) as u32,
+            (offset / beat_ticks) as u32 + 1,
+            (offset % beat_ticks) as u32,
+        )
+    }
+    /// Returns tick at musical time (1-based bar, 1-based beat and tick in the beat).
+    ///
+    /// Beat and tick beyond the bar are counted over to the following bars in the same meter.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use ghakuf::timing::{Meter, MeterMap};
+    ///
+    /// let mut meter_map = MeterMap::new(480);
+    /// meter_map.push(1920, Meter::new(6, 8));
+    /// assert_eq!(meter_map.tick(2, 1, 0), 1920);
+    /// assert_eq!(meter_map.tick(3, 2, 0), 1920 + 1440 + 720);
+    /// ```
+    pub fn tick(&self, bar: u32, beat: u32, tick: u32) -> u64 {
+        let mut start_bar = 1;
+        for (i, &(start, meter)) in self.meters.iter().enumerate() {
+            if let Some(&(next, _)) = self.meters.get(i + 1) {
+                let bars = self.bars(next - start, meter);
+                if bar >= start_bar + bars {
+                    start_bar += bars;
+                    continue;
+                }
+            }
+            return start
+                + bar.saturating_sub(start_bar) as u64 * meter.bar_ticks(self.time_base)
+                + beat.saturating_sub(1) as u64 * meter.beat_ticks(self.time_base)
+                + tick as u64;
+        }
+        unreachable!()
+    }
+    /// Returns ticks of bar lines in range [start, end).
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use ghakuf::timing::{Meter, MeterMap};
+    ///
+    /// let mut meter_map = MeterMap::new(480);
+    /// meter_map.push(1920, Meter::new(3, 4));
+    /// assert_eq!(meter_map.bar_lines(0, 5000), [0, 1920, 3360, 4800]);
+    /// ```
+    pub fn bar_lines(&self, start: u64, end: u64) -> Vec<u64> {
+        self.grid(start, end, |meter, time_base| meter.bar_ticks(time_base))
+    }
+    /// Returns ticks of beats in range [start, end).
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use ghakuf::timing::{Meter, MeterMap};
+    ///
+    /// let mut meter_map = MeterMap::new(480);
+    /// meter_map.push(0, Meter::new(6, 8));
+    /// assert_eq!(meter_map.beat_lines(700, 3000), [720, 1440, 2160, 2880]);
+    /// ```
+    pub fn beat_lines(&self, start: u64, end: u64) -> Vec<u64> {
+        self.grid(start, end, |meter, time_base| meter.beat_ticks(time_base))
+    }
+    // u64::div_ceil needs Rust 1.73
+    #[allow(clippy::manual_div_ceil)]
+    fn bars(&self, ticks: u64, meter: Meter) -> u32 {
+        let bar_ticks = meter.bar_ticks(self.time_base);
+        ((ticks + bar_ticks - 1) / bar_ticks) as u32
+    }
+    fn segment(&self, tick: u64) -> (u64, u32, Meter) {
+        let mut bar = 1;
+        for (i, &(start, meter)) in self.meters.iter().enumerate() {
+            match self.meters.get(i + 1) {
+                Some(&(next, _)) if next <= tick => bar += self.bars(next - start, meter),
+                _ => return (start, bar, meter),
+            }
+        }
+        unreachable!()
+    }
+    #[allow(clippy::manual_div_ceil)]
+    fn grid<F: Fn(&Meter, u16) -> u64>(&self, start: u64, end: u64, step: F) -> Vec<u64> {
+        let mut ticks = Vec::new();
+        for (i, &(segment_start, meter)) in self.meters.iter().enumerate() {
+            let segment_end = match self.meters.get(i + 1) {
+                Some(&(next, _)) => next.min(end),
+                None => end,
+            };
+            let step = step(&meter, self.time_base);
+            let mut tick = if start > segment_start {
+                segment_start + (start - segment_start + step - 1) / step * step
+            } else {
+                segment_start
+            };
+            while tick < segment_end {
+                ticks.push(tick);
+                tick += step;
+            }
+        }
+        ticks
+    }
+}
+impl Handler for MeterMap {
+    fn header(&mut self, _: u16, _: u16, time_base: u16) {
+        self.time_base = time_base;
+    }
+    fn meta_event(&mut self, delta_time: u32, event: &MetaEvent, data: &Vec<u8>) {
+        self.tick += delta_time as u64;
+        if *event == MetaEvent::TimeSignature {
+            if let Some(meter) = Meter::from_data(data) {
+                let tick = self.tick;
+                self.push(tick, meter);
+            }
+        }
+    }
+    fn midi_event(&mut self, delta_time: u32, _: &MidiEvent) {
+        self.tick += delta_time as u64;
+    }
+    fn sys_ex_event(&mut self, delta_time: u32, _: &SysExEvent, _: &Vec<u8>) {
+        self.tick += delta_time as u64;
+    }
+    fn track_change(&mut self) {
+        self.tick = 0;
+    }
+}
+
+#[cfg(test)]
+mod timing_tests {
+    use reader::Handler;
+    use timing::*;
+    #[test]
+    fn meter_change_in_the_middle_of_bar() {
+        let mut meter_map = MeterMap::new(480);
+        // 3/4 from the third beat of bar 2 cuts bar 2 short
+        meter_map.push(1920 + 960, Meter::new(3, 4));
+        assert_eq!(meter_map.position(1920 + 959), (2, 2, 479));
+        assert_eq!(meter_map.position(1920 + 960), (3, 1, 0));
+        assert_eq!(meter_map.tick(4, 1, 0), 1920 + 960 + 1440);
+        assert_eq!(meter_map.bar_lines(0, 4400), [0, 1920, 2880, 4320]);
+        for tick in (0..10_000).step_by(7) {
+            let (bar, beat, offset) = meter_map.position(tick);
+            assert_eq!(meter_map.tick(bar, beat, offset), tick);
+        }
+    }
+    #[test]
+    fn meter_map_handler() {
+        let mut meter_map = MeterMap::new(0);
+        meter_map.header(1, 2, 96);
+        meter_map.meta_event(0, &MetaEvent::TimeSignature, &vec![0x03, 0x02, 0x18, 0x08]);
+        meter_map.track_change();
+        meter_map.midi_event(
+            288,
+            &MidiEvent::NoteOn {
+                ch: 0,
+                note: 60,
+                velocity: 100,
+            },
+        );
+        meter_map.meta_event(0, &MetaEvent::TimeSignature, &vec![0x0c, 0x03, 0x24, 0x08]);
+        assert_eq!(
+            meter_map.meters(),
+            [(0, Meter::new(3, 4)), (288, Meter::new(12, 8))]
+        );
+        assert_eq!(meter_map.position(288 + 144 * 3), (2, 4, 0));
+    }
+}
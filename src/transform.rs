@@ -0,0 +1,1363 @@
+use gm;
+use messages::*;
+use std::collections::{HashMap, VecDeque};
+use sysex;
+
+/// Converts messages with delta time into pairs of absolute tick and message.
+///
+/// Tick is counted from the start of each track, and TrackChange message is at tick 0.
+///
+/// # Examples
+///
+/// ```
+/// use ghakuf::messages::*;
+/// use ghakuf::transform::absolute;
+///
+/// let messages = vec![
+///     Message::MidiEvent { delta_time: 10, event: MidiEvent::NoteOn { ch: 0, note: 60, velocity: 100 } },
+///     Message::MidiEvent { delta_time: 20, event: MidiEvent::NoteOff { ch: 0, note: 60, velocity: 0 } },
+/// ];
+/// let ticks: Vec<u64> = absolute(&messages).iter().map(|&(tick, _)| tick).collect();
+/// assert_eq!(ticks, [10, 30]);
+/// ```
+pub fn absolute(messages: &[Message]) -> Vec<(u64, Message)> {
+    let mut tick = 0;
+    messages
+        .iter()
+        .map(|message| {
+            if *message == Message::TrackChange {
+                tick = 0;
+            }
+            tick += message.delta_time() as u64;
+            (tick, message.clone())
+        })
+        .collect()
+}
+
+/// Converts pairs of absolute tick and message into messages with delta time.
+///
+/// Events must be in order of tick in each track.
+///
+/// # Examples
+///
+/// ```
+/// use ghakuf::messages::*;
+/// use ghakuf::transform::{absolute, relative};
+///
+/// let messages = vec![
+///     Message::MidiEvent { delta_time: 10, event: MidiEvent::NoteOn { ch: 0, note: 60, velocity: 100 } },
+///     Message::TrackChange,
+///     Message::MidiEvent { delta_time: 20, event: MidiEvent::NoteOff { ch: 0, note: 60, velocity: 0 } },
+/// ];
+/// assert_eq!(relative(&absolute(&messages)), messages);
+/// ```
+pub fn relative(events: &[(u64, Message)]) -> Vec<Message> {
+    let mut last = 0;
+    events
+        .iter()
+        .map(|&(tick, ref message)| {
+            let mut message = message.clone();
+            if message == Message::TrackChange {
+                last = 0;
+            } else {
+                message.set_delta_time(tick.saturating_sub(last) as u32);
+                last = last.max(tick);
+            }
+            message
+        })
+        .collect()
+}
+
+/// An enum representing how Quantise treats note durations.
+#[derive(PartialEq, Eq, Hash, Clone, Copy, Debug)]
+pub enum DurationMode {
+    /// Note off moves together with its note on, so duration is preserved.
+    Preserve,
+    /// Note off is quantised to grid independently (at least one grid step after its note on).
+    Snap,
+}
+
+/// Quantisation of note on/off ticks to grid.
+///
+/// Grid is aligned to tick 0 in each track. Only NoteOn and NoteOff (including NoteOn with velocity 0)
+/// are moved, and the result never puts a note off before its note on. EndOfTrack is kept at the end.
+///
+/// # Examples
+///
+/// ```
+/// use ghakuf::messages::*;
+/// use ghakuf::transform::Quantise;
+///
+/// let messages = vec![
+///     Message::MidiEvent { delta_time: 115, event: MidiEvent::NoteOn { ch: 0, note: 60, velocity: 100 } },
+///     Message::MidiEvent { delta_time: 100, event: MidiEvent::NoteOff { ch: 0, note: 60, velocity: 0 } },
+///     Message::MetaEvent { delta_time: 0, event: MetaEvent::EndOfTrack, data: Vec::new() },
+/// ];
+/// let quantised = Quantise::new(480, 16).apply(&messages);
+/// assert_eq!(quantised[0].delta_time(), 120);
+/// assert_eq!(quantised[1].delta_time(), 100);
+/// ```
+#[derive(PartialEq, Clone, Debug)]
+pub struct Quantise {
+    time_base: u16,
+    note_value: u16,
+    triplet: bool,
+    dotted: bool,
+    strength: f64,
+    swing: f64,
+    window: Option<u32>,
+    duration: DurationMode,
+}
+impl Quantise {
+    /// Builds Quantise with time base (ticks per quarter note) and grid of note value (4 is quarter note,
+    /// 16 is sixteenth note). Strength is 1.0, without swing and window, and duration is preserved.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use ghakuf::transform::Quantise;
+    ///
+    /// assert_eq!(Quantise::new(480, 16).step(), 120.0);
+    /// ```
+    pub fn new(time_base: u16, note_value: u16) -> Quantise {
+        Quantise {
+            time_base,
+            note_value,
+            triplet: false,
+            dotted: false,
+            strength: 1.0,
+            swing: 0.5,
+            window: None,
+            duration: DurationMode::Preserve,
+        }
+    }
+    /// Sets whether grid is triplet (2/3 of note value) or not.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use ghakuf::transform::Quantise;
+    ///
+    /// assert_eq!(Quantise::new(480, 8).triplet(true).step(), 160.0);
+    /// ```
+    pub fn triplet(&mut self, triplet: bool) -> &mut Quantise {
+        self.triplet = triplet;
+        self
+    }
+    /// Sets whether grid is dotted (3/2 of note value) or not.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use ghakuf::transform::Quantise;
+    ///
+    /// assert_eq!(Quantise::new(480, 8).dotted(true).step(), 360.0);
+    /// ```
+    pub fn dotted(&mut self, dotted: bool) -> &mut Quantise {
+        self.dotted = dotted;
+        self
+    }
+    /// Sets strength (0.0-1.0), the ratio of the distance to grid which events are moved.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use ghakuf::messages::*;
+    /// use ghakuf::transform::Quantise;
+    ///
+    /// let messages = vec![
+    ///     Message::MidiEvent { delta_time: 100, event: MidiEvent::NoteOn { ch: 0, note: 60, velocity: 100 } },
+    /// ];
+    /// assert_eq!(Quantise::new(480, 16).strength(0.5).apply(&messages)[0].delta_time(), 110);
+    /// ```
+    pub fn strength(&mut self, strength: f64) -> &mut Quantise {
+        self.strength = strength.clamp(0.0, 1.0);
+        self
+    }
+    /// Sets swing (0.5-1.0), the position of every second grid point in a pair of grid steps.
+    ///
+    /// 0.5 is straight, and 2/3 is triplet feel.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use ghakuf::messages::*;
+    /// use ghakuf::transform::Quantise;
+    ///
+    /// let messages = vec![
+    ///     Message::MidiEvent { delta_time: 250, event: MidiEvent::NoteOn { ch: 0, note: 60, velocity: 100 } },
+    /// ];
+    /// assert_eq!(Quantise::new(480, 8).swing(2.0 / 3.0).apply(&messages)[0].delta_time(), 320);
+    /// ```
+    pub fn swing(&mut self, swing: f64) -> &mut Quantise {
+        self.swing = swing.clamp(0.5, 1.0);
+        self
+    }
+    /// Sets window in ticks. Only events within window from grid are moved if it is set.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use ghakuf::messages::*;
+    /// use ghakuf::transform::Quantise;
+    ///
+    /// let messages = vec![
+    ///     Message::MidiEvent { delta_time: 60, event: MidiEvent::NoteOn { ch: 0, note: 60, velocity: 100 } },
+    /// ];
+    /// assert_eq!(Quantise::new(480, 4).window(Some(30)).apply(&messages)[0].delta_time(), 60);
+    /// ```
+    pub fn window(&mut self, window: Option<u32>) -> &mut Quantise {
+        self.window = window;
+        self
+    }
+    /// Sets how note durations are treated.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use ghakuf::messages::*;
+    /// use ghakuf::transform::{DurationMode, Quantise};
+    ///
+    /// let messages = vec![
+    ///     Message::MidiEvent { delta_time: 0, event: MidiEvent::NoteOn { ch: 0, note: 60, velocity: 100 } },
+    ///     Message::MidiEvent { delta_time: 20, event: MidiEvent::NoteOff { ch: 0, note: 60, velocity: 0 } },
+    /// ];
+    /// let quantised = Quantise::new(480, 16).duration(DurationMode::Snap).apply(&messages);
+    /// assert_eq!(quantised[1].delta_time(), 120);
+    /// ```
+    pub fn duration(&mut self, duration: DurationMode) -> &mut Quantise {
+        self.duration = duration;
+        self
+    }
+    /// Returns grid step in ticks.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use ghakuf::transform::Quantise;
+    ///
+    /// assert_eq!(Quantise::new(96, 4).step(), 96.0);
+    /// ```
+    pub fn step(&self) -> f64 {
+        let mut step = self.time_base as f64 * 4.0 / self.note_value.max(1) as f64;
+        if self.triplet {
+            step = step * 2.0 / 3.0;
+        }
+        if self.dotted {
+            step = step * 3.0 / 2.0;
+        }
+        step
+    }
+    /// Returns quantised tick.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use ghakuf::transform::Quantise;
+    ///
+    /// let quantise = Quantise::new(480, 8);
+    /// assert_eq!(quantise.tick(1000), 960);
+    /// assert_eq!(quantise.tick(1200), 1200);
+    /// ```
+    pub fn tick(&self, tick: u64) -> u64 {
+        let step = self.step();
+        let pair_start = (tick as f64 / (step * 2.0)).floor() * step * 2.0;
+        let grid = [
+            pair_start,
+            pair_start + step * 2.0 * self.swing,
+            pair_start + step * 2.0,
+        ]
+        .iter()
+        .map(|&grid| grid.round())
+        .min_by_key(|&grid| (grid - tick as f64).abs() as u64)
+        .unwrap_or(tick as f64);
+        let offset = grid - tick as f64;
+        match self.window {
+            Some(window) if offset.abs() > window as f64 => tick,
+            _ => (tick as f64 + offset * self.strength).round().max(0.0) as u64,
+        }
+    }
+    /// Quantises messages (which may contain several tracks separated by TrackChange) and returns
+    /// messages with recomputed delta time.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use ghakuf::messages::*;
+    /// use ghakuf::transform::Quantise;
+    ///
+    /// let messages = vec![
+    ///     Message::MidiEvent { delta_time: 0, event: MidiEvent::NoteOn { ch: 0, note: 60, velocity: 100 } },
+    ///     Message::MidiEvent { delta_time: 250, event: MidiEvent::NoteOn { ch: 0, note: 62, velocity: 100 } },
+    ///     Message::MidiEvent { delta_time: 0, event: MidiEvent::NoteOn { ch: 0, note: 60, velocity: 0 } },
+    /// ];
+    /// let quantised = Quantise::new(480, 8).apply(&messages);
+    /// assert_eq!(quantised[1].delta_time(), 240);
+    /// assert_eq!(quantised[2].delta_time(), 10);
+    /// ```
+    pub fn apply(&self, messages: &[Message]) -> Vec<Message> {
+        let mut events = Vec::new();
+        for (i, track) in absolute(messages)
+            .split(|(_, message)| *message == Message::TrackChange)
+            .enumerate()
+        {
+            if i > 0 {
+                events.push((0, Message::TrackChange));
+            }
+            events.append(&mut self.apply_track(track));
+        }
+        relative(&events)
+    }
+    fn apply_track(&self, track: &[(u64, Message)]) -> Vec<(u64, Message)> {
+        let mut ticks: Vec<u64> = track.iter().map(|&(tick, _)| tick).collect();
+        let mut starts: Vec<Option<u64>> = vec![None; track.len()];
+        let mut sounding: HashMap<(u8, u8), VecDeque<usize>> = HashMap::new();
+        for (i, &(tick, ref message)) in track.iter().enumerate() {
+            match *message {
+                Message::MidiEvent {
+                    event: MidiEvent::NoteOn { ch, note, velocity },
+                    ..
+                } if velocity > 0 => {
+                    ticks[i] = self.tick(tick);
+                    sounding.entry((ch, note)).or_default().push_back(i);
+                }
+                Message::MidiEvent {
+                    event: MidiEvent::NoteOn { ch, note, .. },
+                    ..
+                }
+                | Message::MidiEvent {
+                    event: MidiEvent::NoteOff { ch, note, .. },
+                    ..
+                } => {
+                    let on = sounding
+                        .get_mut(&(ch, note))
+                        .and_then(|indexes| indexes.pop_front());
+                    ticks[i] = match on {
+                        Some(on) => {
+                            let start = ticks[on];
+                            starts[i] = Some(start);
+                            match self.duration {
+                                DurationMode::Preserve => {
+                                    (tick + start).saturating_sub(track[on].0)
+                                }
+                                DurationMode::Snap => {
+                                    let end = self.tick(tick);
+                                    if end > start {
+                                        end
+                                    } else {
+                                        start + (self.step().round() as u64).max(1)
+                                    }
+                                }
+                            }
+                        }
+                        None => self.tick(tick),
+                    };
+                }
+                _ => (),
+            }
+        }
+        let last = ticks.iter().cloned().max().unwrap_or(0);
+        let mut events: Vec<(u64, u8, Message)> = track
+            .iter()
+            .enumerate()
+            .map(|(i, (_, message))| match *message {
+                Message::MetaEvent {
+                    event: MetaEvent::EndOfTrack,
+                    ..
+                } => (last, 2, message.clone()),
+                _ => match starts[i] {
+                    Some(start) if start < ticks[i] => (ticks[i], 0, message.clone()),
+                    _ => (ticks[i], 1, message.clone()),
+                },
+            })
+            .collect();
+        events.sort_by_key(|&(tick, rank, _)| (tick, rank));
+        events
+            .into_iter()
+            .map(|(tick, _, message)| (tick, message))
+            .collect()
+    }
+}
+
+/// An enum representing how Normalise writes note off.
+#[derive(PartialEq, Eq, Hash, Clone, Copy, Debug)]
+pub enum NoteOffStyle {
+    /// NoteOn with velocity 0, which shares running status with note on. Release velocity is dropped.
+    NoteOnZero,
+    /// NoteOff with the given release velocity.
+    NoteOff(u8),
+}
+
+/// Normalisation of message streams into one canonical form.
+///
+/// Each track is rewritten as follows, so that files differing only in cosmetic details give the same
+/// messages (and the same bytes when written).
+///
+/// - Split system exclusive events are joined (see `sysex::join`).
+/// - Every note off is written in one style (NoteOn with velocity 0 by default).
+/// - Simultaneous events are ordered as meta events, other channel messages and system exclusive
+///   events, note offs and note ons. Note offs and note ons are sorted by channel, note and velocity,
+///   except that a note off after a note on of the same key stays after it (zero length notes), and
+///   the others keep their order (channel prefix, bank select, RPN and resets depend on it).
+/// - ControlChange setting the same value as the previous one of the channel is removed. Data entry,
+///   data increment/decrement, RPN/NRPN parameter numbers and channel mode messages are always kept
+///   (data entry depends on the last selected pair), MSB (controllers 0-31) resets the value of its
+///   LSB, and Reset All Controllers and system exclusive events forget previous values.
+/// - Only one EndOfTrack is placed at the end, not earlier than the original one.
+///
+/// # Examples
+///
+/// ```
+/// use ghakuf::messages::*;
+/// use ghakuf::transform::Normalise;
+///
+/// let messages = vec![
+///     Message::MidiEvent { delta_time: 0, event: MidiEvent::NoteOn { ch: 0, note: 64, velocity: 100 } },
+///     Message::MidiEvent { delta_time: 0, event: MidiEvent::NoteOn { ch: 0, note: 60, velocity: 100 } },
+///     Message::MidiEvent { delta_time: 0, event: MidiEvent::ControlChange { ch: 0, control: 7, data: 100 } },
+///     Message::MidiEvent { delta_time: 480, event: MidiEvent::ControlChange { ch: 0, control: 7, data: 100 } },
+///     Message::MidiEvent { delta_time: 0, event: MidiEvent::NoteOff { ch: 0, note: 60, velocity: 64 } },
+///     Message::MidiEvent { delta_time: 0, event: MidiEvent::NoteOff { ch: 0, note: 64, velocity: 64 } },
+/// ];
+/// assert_eq!(
+///     Normalise::new().apply(&messages),
+///     vec![
+///         Message::MidiEvent { delta_time: 0, event: MidiEvent::ControlChange { ch: 0, control: 7, data: 100 } },
+///         Message::MidiEvent { delta_time: 0, event: MidiEvent::NoteOn { ch: 0, note: 60, velocity: 100 } },
+///         Message::MidiEvent { delta_time: 0, event: MidiEvent::NoteOn { ch: 0, note: 64, velocity: 100 } },
+///         Message::MidiEvent { delta_time: 480, event: MidiEvent::NoteOn { ch: 0, note: 60, velocity: 0 } },
+///         Message::MidiEvent { delta_time: 0, event: MidiEvent::NoteOn { ch: 0, note: 64, velocity: 0 } },
+///         Message::MetaEvent { delta_time: 0, event: MetaEvent::EndOfTrack, data: Vec::new() },
+///     ]
+/// );
+/// ```
+#[derive(PartialEq, Eq, Hash, Clone, Copy, Debug)]
+pub struct Normalise {
+    note_off: NoteOffStyle,
+}
+impl Normalise {
+    /// Builds Normalise writing note off as NoteOn with velocity 0.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use ghakuf::transform::Normalise;
+    ///
+    /// let normalise = Normalise::new();
+    /// ```
+    pub fn new() -> Normalise {
+        Normalise {
+            note_off: NoteOffStyle::NoteOnZero,
+        }
+    }
+    /// Sets style of note off.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use ghakuf::messages::*;
+    /// use ghakuf::transform::{Normalise, NoteOffStyle};
+    ///
+    /// let messages = vec![
+    ///     Message::MidiEvent { delta_time: 0, event: MidiEvent::NoteOn { ch: 0, note: 60, velocity: 0 } },
+    /// ];
+    /// assert_eq!(
+    ///     Normalise::new().note_off(NoteOffStyle::NoteOff(64)).apply(&messages)[0],
+    ///     Message::MidiEvent { delta_time: 0, event: MidiEvent::NoteOff { ch: 0, note: 60, velocity: 64 } }
+    /// );
+    /// ```
+    pub fn note_off(&mut self, note_off: NoteOffStyle) -> &mut Normalise {
+        self.note_off = note_off;
+        self
+    }
+    /// Normalises messages (which may contain several tracks separated by TrackChange) and returns
+    /// messages with recomputed delta time.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use ghakuf::messages::*;
+    /// use ghakuf::transform::Normalise;
+    ///
+    /// let messages = vec![
+    ///     Message::MetaEvent { delta_time: 0, event: MetaEvent::EndOfTrack, data: Vec::new() },
+    ///     Message::MidiEvent { delta_time: 240, event: MidiEvent::NoteOn { ch: 0, note: 60, velocity: 0 } },
+    ///     Message::TrackChange,
+    /// ];
+    /// let normalised = Normalise::new().apply(&messages);
+    /// assert_eq!(normalised.len(), 4);
+    /// assert_eq!(
+    ///     normalised[1],
+    ///     Message::MetaEvent { delta_time: 0, event: MetaEvent::EndOfTrack, data: Vec::new() }
+    /// );
+    /// ```
+    pub fn apply(&self, messages: &[Message]) -> Vec<Message> {
+        let mut events = Vec::new();
+        for (i, track) in absolute(&sysex::join(messages))
+            .split(|(_, message)| *message == Message::TrackChange)
+            .enumerate()
+        {
+            if i > 0 {
+                events.push((0, Message::TrackChange));
+            }
+            events.append(&mut self.apply_track(track));
+        }
+        relative(&events)
+    }
+    fn apply_track(&self, track: &[(u64, Message)]) -> Vec<(u64, Message)> {
+        let end = track.iter().map(|&(tick, _)| tick).max().unwrap_or(0);
+        let events: Vec<(u64, Message)> = track
+            .iter()
+            .filter(|(_, message)| {
+                !matches!(
+                    *message,
+                    Message::MetaEvent {
+                        event: MetaEvent::EndOfTrack,
+                        ..
+                    }
+                )
+            })
+            .map(|(tick, message)| (*tick, self.note_off_style(message)))
+            .collect();
+        let mut keys: HashMap<(u8, u8), (u8, bool)> = HashMap::new();
+        let mut last_tick = None;
+        let mut events: Vec<((u64, Order), (u64, Message))> = events
+            .into_iter()
+            .map(|(tick, message)| {
+                if last_tick != Some(tick) {
+                    keys.clear();
+                    last_tick = Some(tick);
+                }
+                ((tick, order(&message, &mut keys)), (tick, message))
+            })
+            .collect();
+        events.sort_by_key(|&(key, _)| key);
+        let mut events: Vec<(u64, Message)> = events.into_iter().map(|(_, event)| event).collect();
+        let mut controllers: HashMap<(u8, u8), u8> = HashMap::new();
+        events.retain(|(_, message)| match *message {
+            Message::MidiEvent {
+                event: MidiEvent::ControlChange { ch, control, data },
+                ..
+            } => match control {
+                6 | 38 | 96..=101 | 120..=127 => {
+                    if control == 121 {
+                        controllers.retain(|&(channel, _), _| channel != ch);
+                    }
+                    true
+                }
+                0..=31 => {
+                    // MSB resets LSB, so MSB after LSB isn't a repeat
+                    let lsb = controllers.remove(&(ch, control + 32));
+                    controllers.insert((ch, control), data) != Some(data) || lsb.is_some()
+                }
+                _ => controllers.insert((ch, control), data) != Some(data),
+            },
+            Message::SysExEvent { .. } => {
+                controllers.clear();
+                true
+            }
+            _ => true,
+        });
+        events.push((
+            end,
+            Message::MetaEvent {
+                delta_time: 0,
+                event: MetaEvent::EndOfTrack,
+                data: Vec::new(),
+            },
+        ));
+        events
+    }
+    fn note_off_style(&self, message: &Message) -> Message {
+        match *message {
+            Message::MidiEvent {
+                delta_time,
+                event:
+                    MidiEvent::NoteOn {
+                        ch,
+                        note,
+                        velocity: 0,
+                    },
+            }
+            | Message::MidiEvent {
+                delta_time,
+                event: MidiEvent::NoteOff { ch, note, .. },
+            } => Message::MidiEvent {
+                delta_time,
+                event: match self.note_off {
+                    NoteOffStyle::NoteOnZero => MidiEvent::NoteOn {
+                        ch,
+                        note,
+                        velocity: 0,
+                    },
+                    NoteOffStyle::NoteOff(velocity) => MidiEvent::NoteOff { ch, note, velocity },
+                },
+            },
+            _ => message.clone(),
+        }
+    }
+}
+impl Default for Normalise {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+// (class, phase, note off or on, channel, note, velocity)
+type Order = (u8, u8, u8, u8, u8, u8);
+
+// Sort key of simultaneous events in normalised form (note off is already in one style here). Keys
+// have (phase, whether note on is waiting for note off) of each (channel, note) at the tick so far, and
+// a note off ending a note on of the same tick moves to the next phase to stay after it.
+fn order(message: &Message, keys: &mut HashMap<(u8, u8), (u8, bool)>) -> Order {
+    match *message {
+        Message::MetaEvent { .. } => (0, 0, 0, 0, 0, 0),
+        Message::MidiEvent {
+            event: MidiEvent::NoteOn { ch, note, velocity },
+            ..
+        } if velocity > 0 => {
+            let key = keys.entry((ch, note)).or_insert((0, false));
+            key.1 = true;
+            (2, key.0, 1, ch, note, velocity)
+        }
+        Message::MidiEvent {
+            event: MidiEvent::NoteOn { ch, note, .. },
+            ..
+        }
+        | Message::MidiEvent {
+            event: MidiEvent::NoteOff { ch, note, .. },
+            ..
+        } => {
+            let key = keys.entry((ch, note)).or_insert((0, false));
+            if key.1 {
+                *key = (key.0.saturating_add(1), false);
+            }
+            (2, key.0, 0, ch, note, 0)
+        }
+        _ => (1, 0, 0, 0, 0, 0),
+    }
+}
+
+/// Message-by-message transform of message streams.
+///
+/// # Examples
+///
+/// ```
+/// use ghakuf::messages::*;
+/// use ghakuf::transform::Transform;
+///
+/// struct NoteOnly {}
+/// impl Transform for NoteOnly {
+///     fn map(&self, message: &Message) -> Option<Message> {
+///         match *message {
+///             Message::MidiEvent { event: MidiEvent::ControlChange { .. }, .. } => None,
+///             _ => Some(message.clone()),
+///         }
+///     }
+/// }
+///
+/// let messages = vec![
+///     Message::MidiEvent { delta_time: 10, event: MidiEvent::ControlChange { ch: 0, control: 7, data: 100 } },
+///     Message::MidiEvent { delta_time: 10, event: MidiEvent::NoteOn { ch: 0, note: 60, velocity: 100 } },
+/// ];
+/// let transformed = NoteOnly {}.apply(&messages);
+/// assert_eq!(transformed.len(), 1);
+/// assert_eq!(transformed[0].delta_time(), 20);
+/// ```
+pub trait Transform {
+    /// Maps message to transformed message, or returns None to drop it. Delta time needn't be cared.
+    fn map(&self, message: &Message) -> Option<Message>;
+    /// Transforms messages. Delta time of dropped message is added to the next message, and TrackChange
+    /// is always kept.
+    fn apply(&self, messages: &[Message]) -> Vec<Message> {
+        let mut transformed = Vec::with_capacity(messages.len());
+        let mut carry = 0;
+        for message in messages {
+            if *message == Message::TrackChange {
+                transformed.push(Message::TrackChange);
+                carry = 0;
+                continue;
+            }
+            match self.map(message) {
+                Some(mut mapped) => {
+                    mapped.set_delta_time(message.delta_time() + carry);
+                    transformed.push(mapped);
+                    carry = 0;
+                }
+                None => carry += message.delta_time(),
+            }
+        }
+        transformed
+    }
+}
+
+/// Composition of transforms applied in order of pushing.
+///
+/// # Examples
+///
+/// ```
+/// use ghakuf::messages::*;
+/// use ghakuf::transform::{Chain, ChannelMap, Transform, Transpose};
+///
+/// let mut transpose = Transpose::new(12);
+/// transpose.skip_drums(true);
+/// let mut channel_map = ChannelMap::new();
+/// channel_map.remap(0, 1);
+/// let mut chain = Chain::new();
+/// chain.push(&transpose).push(&channel_map);
+///
+/// let message = Message::MidiEvent { delta_time: 0, event: MidiEvent::NoteOn { ch: 0, note: 60, velocity: 100 } };
+/// assert_eq!(
+///     chain.map(&message),
+///     Some(Message::MidiEvent { delta_time: 0, event: MidiEvent::NoteOn { ch: 1, note: 72, velocity: 100 } })
+/// );
+/// ```
+pub struct Chain<'a> {
+    transforms: Vec<&'a dyn Transform>,
+}
+impl<'a> Chain<'a> {
+    /// Builds Chain without any transforms.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use ghakuf::transform::Chain;
+    ///
+    /// let chain: Chain = Chain::new();
+    /// ```
+    pub fn new() -> Chain<'a> {
+        Chain {
+            transforms: Vec::new(),
+        }
+    }
+    /// Pushes Transform to Chain.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use ghakuf::transform::{Chain, Velocity};
+    ///
+    /// let mut velocity = Velocity::new();
+    /// velocity.scale(0.5);
+    /// let mut chain = Chain::new();
+    /// chain.push(&velocity);
+    /// ```
+    pub fn push(&mut self, transform: &'a dyn Transform) -> &mut Chain<'a> {
+        self.transforms.push(transform);
+        self
+    }
+}
+impl<'a> Default for Chain<'a> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+impl<'a> Transform for Chain<'a> {
+    fn map(&self, message: &Message) -> Option<Message> {
+        let mut message = message.clone();
+        for transform in &self.transforms {
+            message = transform.map(&message)?;
+        }
+        Some(message)
+    }
+}
+
+/// An enum representing how Transpose treats notes out of range (0-127).
+#[derive(PartialEq, Eq, Hash, Clone, Copy, Debug)]
+pub enum OutOfRange {
+    /// Note is clamped to 0 or 127.
+    Clamp,
+    /// Note event is dropped.
+    Drop,
+}
+
+/// Transposition of NoteOn, NoteOff and PolyphonicKeyPressure.
+///
+/// # Examples
+///
+/// ```
+/// use ghakuf::messages::*;
+/// use ghakuf::transform::{OutOfRange, Transform, Transpose};
+///
+/// let mut transpose = Transpose::new(-24);
+/// transpose.out_of_range(OutOfRange::Drop);
+/// let messages = vec![
+///     Message::MidiEvent { delta_time: 0, event: MidiEvent::NoteOn { ch: 0, note: 60, velocity: 100 } },
+///     Message::MidiEvent { delta_time: 0, event: MidiEvent::NoteOn { ch: 0, note: 12, velocity: 100 } },
+/// ];
+/// assert_eq!(
+///     transpose.apply(&messages),
+///     [Message::MidiEvent { delta_time: 0, event: MidiEvent::NoteOn { ch: 0, note: 36, velocity: 100 } }]
+/// );
+/// ```
+#[derive(PartialEq, Eq, Hash, Clone, Copy, Debug)]
+pub struct Transpose {
+    semitones: i8,
+    out_of_range: OutOfRange,
+    skip_drums: bool,
+}
+impl Transpose {
+    /// Builds Transpose by semitones, clamping notes out of range and transposing all channels.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use ghakuf::messages::*;
+    /// use ghakuf::transform::{Transform, Transpose};
+    ///
+    /// let message = Message::MidiEvent { delta_time: 0, event: MidiEvent::NoteOff { ch: 0, note: 120, velocity: 0 } };
+    /// assert_eq!(
+    ///     Transpose::new(12).map(&message),
+    ///     Some(Message::MidiEvent { delta_time: 0, event: MidiEvent::NoteOff { ch: 0, note: 127, velocity: 0 } })
+    /// );
+    /// ```
+    pub fn new(semitones: i8) -> Transpose {
+        Transpose {
+            semitones,
+            out_of_range: OutOfRange::Clamp,
+            skip_drums: false,
+        }
+    }
+    /// Sets how notes out of range are treated.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use ghakuf::transform::{OutOfRange, Transpose};
+    ///
+    /// let mut transpose = Transpose::new(12);
+    /// transpose.out_of_range(OutOfRange::Drop);
+    /// ```
+    pub fn out_of_range(&mut self, out_of_range: OutOfRange) -> &mut Transpose {
+        self.out_of_range = out_of_range;
+        self
+    }
+    /// Sets whether the percussion channel (channel 10, ch: 9) is skipped or not.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use ghakuf::messages::*;
+    /// use ghakuf::transform::{Transform, Transpose};
+    ///
+    /// let message = Message::MidiEvent { delta_time: 0, event: MidiEvent::NoteOn { ch: 9, note: 36, velocity: 100 } };
+    /// assert_eq!(Transpose::new(5).skip_drums(true).map(&message), Some(message));
+    /// ```
+    pub fn skip_drums(&mut self, skip_drums: bool) -> &mut Transpose {
+        self.skip_drums = skip_drums;
+        self
+    }
+}
+impl Transform for Transpose {
+    fn map(&self, message: &Message) -> Option<Message> {
+        let mut message = message.clone();
+        if let Message::MidiEvent { ref mut event, .. } = message {
+            match *event {
+                MidiEvent::NoteOff {
+                    ch, ref mut note, ..
+                }
+                | MidiEvent::NoteOn {
+                    ch, ref mut note, ..
+                }
+                | MidiEvent::PolyphonicKeyPressure {
+                    ch, ref mut note, ..
+                } if !(self.skip_drums && ch == gm::PERCUSSION_CHANNEL) => {
+                    let transposed = *note as i16 + self.semitones as i16;
+                    if !(0..=127).contains(&transposed) && self.out_of_range == OutOfRange::Drop {
+                        return None;
+                    }
+                    *note = transposed.clamp(0, 127) as u8;
+                }
+                _ => (),
+            }
+        }
+        Some(message)
+    }
+}
+
+/// Velocity curve and scale of NoteOn.
+///
+/// New velocity is `127 * (velocity / 127) ^ curve * scale`, clamped to 1-127 so that note on never becomes
+/// note off. NoteOn with velocity 0 (note off) isn't changed.
+///
+/// # Examples
+///
+/// ```
+/// use ghakuf::messages::*;
+/// use ghakuf::transform::{Transform, Velocity};
+///
+/// let mut velocity = Velocity::new();
+/// velocity.curve(0.5).scale(0.8);
+/// let message = Message::MidiEvent { delta_time: 0, event: MidiEvent::NoteOn { ch: 0, note: 60, velocity: 32 } };
+/// assert_eq!(
+///     velocity.map(&message),
+///     Some(Message::MidiEvent { delta_time: 0, event: MidiEvent::NoteOn { ch: 0, note: 60, velocity: 51 } })
+/// );
+/// ```
+#[derive(PartialEq, Clone, Copy, Debug)]
+pub struct Velocity {
+    curve: f64,
+    scale: f64,
+}
+impl Velocity {
+    /// Builds Velocity with linear curve (1.0) and scale 1.0.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use ghakuf::transform::Velocity;
+    ///
+    /// assert_eq!(Velocity::new().velocity(100), 100);
+    /// ```
+    pub fn new() -> Velocity {
+        Velocity {
+            curve: 1.0,
+            scale: 1.0,
+        }
+    }
+    /// Sets exponent of velocity curve. Curve less than 1.0 makes soft notes louder.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use ghakuf::transform::Velocity;
+    ///
+    /// assert_eq!(Velocity::new().curve(2.0).velocity(64), 32);
+    /// ```
+    pub fn curve(&mut self, curve: f64) -> &mut Velocity {
+        self.curve = curve;
+        self
+    }
+    /// Sets scale of velocity.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use ghakuf::transform::Velocity;
+    ///
+    /// assert_eq!(Velocity::new().scale(1.5).velocity(100), 127);
+    /// ```
+    pub fn scale(&mut self, scale: f64) -> &mut Velocity {
+        self.scale = scale;
+        self
+    }
+    /// Returns transformed velocity.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use ghakuf::transform::Velocity;
+    ///
+    /// assert_eq!(Velocity::new().scale(0.0).velocity(100), 1);
+    /// assert_eq!(Velocity::new().scale(0.0).velocity(0), 0);
+    /// ```
+    pub fn velocity(&self, velocity: u8) -> u8 {
+        if velocity == 0 {
+            return 0;
+        }
+        let velocity = 127.0 * (velocity.min(127) as f64 / 127.0).powf(self.curve) * self.scale;
+        velocity.round().clamp(1.0, 127.0) as u8
+    }
+}
+impl Default for Velocity {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+impl Transform for Velocity {
+    fn map(&self, message: &Message) -> Option<Message> {
+        let mut message = message.clone();
+        if let Message::MidiEvent {
+            event: MidiEvent::NoteOn {
+                ref mut velocity, ..
+            },
+            ..
+        } = message
+        {
+            *velocity = self.velocity(*velocity);
+        }
+        Some(message)
+    }
+}
+
+/// Channel remap table of MIDI events.
+///
+/// # Examples
+///
+/// ```
+/// use ghakuf::messages::*;
+/// use ghakuf::transform::{ChannelMap, Transform};
+///
+/// let mut channel_map = ChannelMap::new();
+/// channel_map.remap(0, 3).remap(3, 0);
+/// let message = Message::MidiEvent { delta_time: 0, event: MidiEvent::ProgramChange { ch: 3, program: 1 } };
+/// assert_eq!(
+///     channel_map.map(&message),
+///     Some(Message::MidiEvent { delta_time: 0, event: MidiEvent::ProgramChange { ch: 0, program: 1 } })
+/// );
+/// ```
+#[derive(PartialEq, Eq, Hash, Clone, Copy, Debug)]
+pub struct ChannelMap {
+    table: [u8; 16],
+}
+impl ChannelMap {
+    /// Builds ChannelMap which maps every channel to itself.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use ghakuf::transform::ChannelMap;
+    ///
+    /// assert_eq!(ChannelMap::new().channel(5), 5);
+    /// ```
+    pub fn new() -> ChannelMap {
+        let mut table = [0; 16];
+        for (ch, mapped) in table.iter_mut().enumerate() {
+            *mapped = ch as u8;
+        }
+        ChannelMap { table }
+    }
+    /// Maps channel (0-15) to another channel.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use ghakuf::transform::ChannelMap;
+    ///
+    /// assert_eq!(ChannelMap::new().remap(1, 9).channel(1), 9);
+    /// ```
+    pub fn remap(&mut self, from: u8, to: u8) -> &mut ChannelMap {
+        self.table[from as usize & 0x0f] = to & 0x0f;
+        self
+    }
+    /// Returns mapped channel.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use ghakuf::transform::ChannelMap;
+    ///
+    /// assert_eq!(ChannelMap::new().channel(15), 15);
+    /// ```
+    pub fn channel(&self, ch: u8) -> u8 {
+        self.table[ch as usize & 0x0f]
+    }
+}
+impl Default for ChannelMap {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+impl Transform for ChannelMap {
+    fn map(&self, message: &Message) -> Option<Message> {
+        let mut message = message.clone();
+        if let Message::MidiEvent { ref mut event, .. } = message {
+            match *event {
+                MidiEvent::NoteOff { ref mut ch, .. }
+                | MidiEvent::NoteOn { ref mut ch, .. }
+                | MidiEvent::PolyphonicKeyPressure { ref mut ch, .. }
+                | MidiEvent::ControlChange { ref mut ch, .. }
+                | MidiEvent::ProgramChange { ref mut ch, .. }
+                | MidiEvent::ChannelPressure { ref mut ch, .. }
+                | MidiEvent::PitchBendChange { ref mut ch, .. }
+                | MidiEvent::Unknown { ref mut ch } => *ch = self.channel(*ch),
+            }
+        }
+        Some(message)
+    }
+}
+
+/// Program remap table of ProgramChange.
+///
+/// # Examples
+///
+/// ```
+/// use ghakuf::messages::*;
+/// use ghakuf::transform::{ProgramMap, Transform};
+///
+/// let mut program_map = ProgramMap::new();
+/// program_map.remap(0, 1); // Acoustic Grand Piano -> Bright Acoustic Piano
+/// let message = Message::MidiEvent { delta_time: 0, event: MidiEvent::ProgramChange { ch: 0, program: 0 } };
+/// assert_eq!(
+///     program_map.map(&message),
+///     Some(Message::MidiEvent { delta_time: 0, event: MidiEvent::ProgramChange { ch: 0, program: 1 } })
+/// );
+/// ```
+#[derive(PartialEq, Eq, Hash, Clone, Copy, Debug)]
+pub struct ProgramMap {
+    table: [u8; 128],
+}
+impl ProgramMap {
+    /// Builds ProgramMap which maps every program to itself.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use ghakuf::transform::ProgramMap;
+    ///
+    /// assert_eq!(ProgramMap::new().program(42), 42);
+    /// ```
+    pub fn new() -> ProgramMap {
+        let mut table = [0; 128];
+        for (program, mapped) in table.iter_mut().enumerate() {
+            *mapped = program as u8;
+        }
+        ProgramMap { table }
+    }
+    /// Maps program (0-127) to another program.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use ghakuf::transform::ProgramMap;
+    ///
+    /// assert_eq!(ProgramMap::new().remap(24, 25).program(24), 25);
+    /// ```
+    pub fn remap(&mut self, from: u8, to: u8) -> &mut ProgramMap {
+        self.table[from as usize & 0x7f] = to & 0x7f;
+        self
+    }
+    /// Returns mapped program.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use ghakuf::transform::ProgramMap;
+    ///
+    /// assert_eq!(ProgramMap::new().program(127), 127);
+    /// ```
+    pub fn program(&self, program: u8) -> u8 {
+        self.table[program as usize & 0x7f]
+    }
+}
+impl Default for ProgramMap {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+impl Transform for ProgramMap {
+    fn map(&self, message: &Message) -> Option<Message> {
+        let mut message = message.clone();
+        if let Message::MidiEvent {
+            event: MidiEvent::ProgramChange {
+                ref mut program, ..
+            },
+            ..
+        } = message
+        {
+            *program = self.program(*program);
+        }
+        Some(message)
+    }
+}
+
+#[cfg(test)]
+mod transform_tests {
+    use transform::*;
+    fn note(delta_time: u32, note: u8, velocity: u8) -> Message {
+        Message::MidiEvent {
+            delta_time,
+            event: MidiEvent::NoteOn {
+                ch: 0,
+                note,
+                velocity,
+            },
+        }
+    }
+    fn end_of_track(delta_time: u32) -> Message {
+        Message::MetaEvent {
+            delta_time,
+            event: MetaEvent::EndOfTrack,
+            data: Vec::new(),
+        }
+    }
+    #[test]
+    fn note_off_never_precedes_note_on() {
+        let messages = vec![
+            note(50, 60, 100),
+            note(5, 60, 0),
+            note(200, 62, 100),
+            note(30, 62, 0),
+            end_of_track(0),
+        ];
+        for &mode in &[DurationMode::Preserve, DurationMode::Snap] {
+            for &strength in &[0.0, 0.3, 1.0] {
+                let quantised = Quantise::new(480, 4)
+                    .triplet(true)
+                    .strength(strength)
+                    .duration(mode)
+                    .apply(&messages);
+                let events = absolute(&quantised);
+                for pair in events.chunks(2).take(2) {
+                    assert!(pair[0].0 < pair[1].0, "{:?} {:?}", mode, events);
+                }
+                assert_eq!(
+                    events.last().map(|&(tick, _)| tick),
+                    events.iter().map(|&(tick, _)| tick).max()
+                );
+            }
+        }
+    }
+    #[test]
+    fn quantise_tracks() {
+        let messages = vec![
+            note(10, 60, 100),
+            note(100, 60, 0),
+            end_of_track(0),
+            Message::TrackChange,
+            note(470, 64, 100),
+            note(100, 64, 0),
+            end_of_track(500),
+        ];
+        let quantised = Quantise::new(480, 4)
+            .duration(DurationMode::Snap)
+            .apply(&messages);
+        let ticks: Vec<u64> = absolute(&quantised).iter().map(|&(tick, _)| tick).collect();
+        assert_eq!(ticks, [0, 480, 480, 0, 480, 960, 1070]);
+    }
+    #[test]
+    fn normalise_track() {
+        let cc = |delta_time, control, data| Message::MidiEvent {
+            delta_time,
+            event: MidiEvent::ControlChange {
+                ch: 0,
+                control,
+                data,
+            },
+        };
+        let sys_ex = |delta_time, event, data: &[u8]| Message::SysExEvent {
+            delta_time,
+            event,
+            data: data.to_vec(),
+        };
+        let messages = vec![
+            note(0, 62, 100),
+            cc(0, 7, 100),
+            end_of_track(0),
+            note(0, 60, 100),
+            sys_ex(0, SysExEvent::F0, &[0x7e, 0x7f]),
+            sys_ex(10, SysExEvent::F7, &[0x09, 0x01, 0xf7]),
+            cc(0, 7, 100),
+            cc(0, 6, 2),
+            cc(0, 6, 2),
+            cc(0, 121, 0),
+            cc(0, 7, 100),
+            Message::MidiEvent {
+                delta_time: 470,
+                event: MidiEvent::NoteOff {
+                    ch: 0,
+                    note: 60,
+                    velocity: 64,
+                },
+            },
+            note(0, 62, 0),
+            end_of_track(0),
+        ];
+        assert_eq!(
+            Normalise::new().apply(&messages),
+            vec![
+                cc(0, 7, 100),
+                sys_ex(0, SysExEvent::F0, &[0x7e, 0x7f, 0x09, 0x01, 0xf7]),
+                note(0, 60, 100),
+                note(0, 62, 100),
+                cc(10, 7, 100),
+                cc(0, 6, 2),
+                cc(0, 6, 2),
+                cc(0, 121, 0),
+                cc(0, 7, 100),
+                note(470, 60, 0),
+                note(0, 62, 0),
+                end_of_track(0),
+            ]
+        );
+    }
+    #[test]
+    fn normalise_parameter_numbers_and_meta_order() {
+        let cc = |control, data| Message::MidiEvent {
+            delta_time: 0,
+            event: MidiEvent::ControlChange {
+                ch: 0,
+                control,
+                data,
+            },
+        };
+        let messages = vec![
+            cc(101, 0),
+            cc(100, 0),
+            cc(6, 2),
+            cc(99, 1),
+            cc(98, 8),
+            cc(6, 64),
+            cc(101, 0),
+            cc(100, 0),
+            cc(6, 12),
+        ];
+        let mut expected = messages.clone();
+        expected.push(end_of_track(0));
+        assert_eq!(Normalise::new().apply(&messages), expected);
+
+        let gm_system_on = Message::SysExEvent {
+            delta_time: 0,
+            event: SysExEvent::F0,
+            data: vec![0x7e, 0x7f, 0x09, 0x01, 0xf7],
+        };
+        let marker = Message::MetaEvent {
+            delta_time: 0,
+            event: MetaEvent::Marker,
+            data: b"A".to_vec(),
+        };
+        assert_eq!(
+            Normalise::new().apply(&[cc(7, 50), gm_system_on.clone(), marker.clone()]),
+            vec![marker, cc(7, 50), gm_system_on, end_of_track(0)]
+        );
+    }
+    #[test]
+    fn normalise_msb_resets_lsb() {
+        let cc = |control, data| Message::MidiEvent {
+            delta_time: 0,
+            event: MidiEvent::ControlChange {
+                ch: 0,
+                control,
+                data,
+            },
+        };
+        for messages in &[
+            vec![cc(7, 100), cc(39, 50), cc(7, 101), cc(39, 50)],
+            vec![cc(0, 1), cc(32, 2), cc(0, 1), cc(32, 2)],
+        ] {
+            let mut expected = messages.clone();
+            expected.push(end_of_track(0));
+            assert_eq!(Normalise::new().apply(messages), expected);
+        }
+        assert_eq!(
+            Normalise::new().apply(&[cc(7, 100), cc(7, 100), cc(39, 50), cc(39, 50)]),
+            vec![cc(7, 100), cc(39, 50), end_of_track(0)]
+        );
+    }
+    #[test]
+    fn normalise_zero_length_notes() {
+        assert_eq!(
+            Normalise::new().apply(&[note(0, 60, 100), note(0, 60, 0)]),
+            vec![note(0, 60, 100), note(0, 60, 0), end_of_track(0)]
+        );
+        assert_eq!(
+            Normalise::new().apply(&[
+                note(0, 62, 100),
+                note(0, 60, 100),
+                note(10, 60, 0),
+                note(0, 60, 100),
+                note(0, 60, 0),
+                note(0, 62, 0),
+            ]),
+            vec![
+                note(0, 60, 100),
+                note(0, 62, 100),
+                note(10, 60, 0),
+                note(0, 62, 0),
+                note(0, 60, 100),
+                note(0, 60, 0),
+                end_of_track(0),
+            ]
+        );
+    }
+}
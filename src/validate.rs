@@ -0,0 +1,220 @@
+use messages::*;
+use smf::Smf;
+use std::collections::HashMap;
+use std::fmt;
+use transform::absolute;
+
+/// An enum representing a problem found in SMF by `validate`.
+///
+/// Tick is absolute in the track, and track is 0-based.
+#[derive(PartialEq, Clone, Debug)]
+pub enum Issue {
+    /// Format 0 SMF doesn't have exactly one track.
+    TrackCount { tracks: usize },
+    /// Track doesn't end with EndOfTrack.
+    MissingEndOfTrack { track: usize },
+    /// Event is found after EndOfTrack.
+    EventAfterEndOfTrack { track: usize, tick: u64 },
+    /// Meta event data length is invalid for the event.
+    InvalidMetaLength {
+        track: usize,
+        tick: u64,
+        event: MetaEvent,
+        len: usize,
+    },
+    /// MIDI event has data byte over 0x7f.
+    DataOutOfRange { track: usize, tick: u64 },
+    /// Note is still sounding at the end of track.
+    UnterminatedNote {
+        track: usize,
+        tick: u64,
+        ch: u8,
+        note: u8,
+    },
+    /// Note off is found without note on.
+    UnmatchedNoteOff {
+        track: usize,
+        tick: u64,
+        ch: u8,
+        note: u8,
+    },
+}
+impl fmt::Display for Issue {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        use validate::Issue::*;
+        match *self {
+            TrackCount { tracks } => write!(f, "Format 0 SMF has {} tracks.", tracks),
+            MissingEndOfTrack { track } => {
+                write!(f, "Track {} doesn't end with EndOfTrack.", track)
+            }
+            EventAfterEndOfTrack { track, tick } => write!(
+                f,
+                "Track {} has an event after EndOfTrack at tick {}.",
+                track, tick
+            ),
+            InvalidMetaLength {
+                track,
+                tick,
+                ref event,
+                len,
+            } => write!(
+                f,
+                "Track {} has {} with invalid length {} at tick {}.",
+                track, event, len, tick
+            ),
+            DataOutOfRange { track, tick } => write!(
+                f,
+                "Track {} has a MIDI event with data over 0x7f at tick {}.",
+                track, tick
+            ),
+            UnterminatedNote {
+                track,
+                tick,
+                ch,
+                note,
+            } => write!(
+                f,
+                "Track {} has an unterminated note (ch: {}, note: {}) from tick {}.",
+                track, ch, note, tick
+            ),
+            UnmatchedNoteOff {
+                track,
+                tick,
+                ch,
+                note,
+            } => write!(
+                f,
+                "Track {} has a note off (ch: {}, note: {}) without note on at tick {}.",
+                track, ch, note, tick
+            ),
+        }
+    }
+}
+
+/// Validates SMF and returns problems found.
+///
+/// # Examples
+///
+/// ```
+/// use ghakuf::messages::*;
+/// use ghakuf::smf::Smf;
+/// use ghakuf::validate::{validate, Issue};
+///
+/// let mut smf = Smf::new(0, 480);
+/// smf.tracks.push(vec![
+///     Message::MidiEvent { delta_time: 0, event: MidiEvent::NoteOn { ch: 0, note: 60, velocity: 100 } },
+/// ]);
+/// assert_eq!(
+///     validate(&smf),
+///     vec![
+///         Issue::MissingEndOfTrack { track: 0 },
+///         Issue::UnterminatedNote { track: 0, tick: 0, ch: 0, note: 60 },
+///     ]
+/// );
+/// ```
+pub fn validate(smf: &Smf) -> Vec<Issue> {
+    let mut issues = Vec::new();
+    if smf.format == 0 && smf.tracks.len() != 1 {
+        issues.push(Issue::TrackCount {
+            tracks: smf.tracks.len(),
+        });
+    }
+    for (track, messages) in smf.tracks.iter().enumerate() {
+        let events = absolute(messages);
+        match events
+            .iter()
+            .position(|(_, message)| is_end_of_track(message))
+        {
+            Some(index) if index + 1 < events.len() => issues.push(Issue::EventAfterEndOfTrack {
+                track,
+                tick: events[index + 1].0,
+            }),
+            Some(_) => (),
+            None => issues.push(Issue::MissingEndOfTrack { track }),
+        }
+        let mut sounding: HashMap<(u8, u8), Vec<u64>> = HashMap::new();
+        for &(tick, ref message) in &events {
+            match *message {
+                Message::MetaEvent {
+                    ref event,
+                    ref data,
+                    ..
+                } if !valid_meta_length(event, data.len()) => {
+                    issues.push(Issue::InvalidMetaLength {
+                        track,
+                        tick,
+                        event: event.clone(),
+                        len: data.len(),
+                    });
+                }
+                Message::MidiEvent { ref event, .. } => {
+                    if event.binary()[1..].iter().any(|&byte| byte > 0x7f) {
+                        issues.push(Issue::DataOutOfRange { track, tick });
+                    }
+                    match *event {
+                        MidiEvent::NoteOn { ch, note, velocity } if velocity > 0 => {
+                            sounding.entry((ch, note)).or_default().push(tick)
+                        }
+                        MidiEvent::NoteOn { ch, note, .. }
+                        | MidiEvent::NoteOff { ch, note, .. } => {
+                            let on = sounding.get_mut(&(ch, note)).and_then(|ticks| {
+                                if ticks.is_empty() {
+                                    None
+                                } else {
+                                    Some(ticks.remove(0))
+                                }
+                            });
+                            if on.is_none() {
+                                issues.push(Issue::UnmatchedNoteOff {
+                                    track,
+                                    tick,
+                                    ch,
+                                    note,
+                                });
+                            }
+                        }
+                        _ => (),
+                    }
+                }
+                _ => (),
+            }
+        }
+        let mut unterminated: Vec<(u64, u8, u8)> = sounding
+            .into_iter()
+            .flat_map(|((ch, note), ticks)| ticks.into_iter().map(move |tick| (tick, ch, note)))
+            .collect();
+        unterminated.sort();
+        issues.extend(
+            unterminated
+                .into_iter()
+                .map(|(tick, ch, note)| Issue::UnterminatedNote {
+                    track,
+                    tick,
+                    ch,
+                    note,
+                }),
+        );
+    }
+    issues
+}
+
+fn is_end_of_track(message: &Message) -> bool {
+    match *message {
+        Message::MetaEvent { ref event, .. } => *event == MetaEvent::EndOfTrack,
+        _ => false,
+    }
+}
+
+fn valid_meta_length(event: &MetaEvent, len: usize) -> bool {
+    use messages::MetaEvent::*;
+    match *event {
+        SequenceNumber => len == 0 || len == 2,
+        MIDIChannelPrefix | MidiPort => len == 1,
+        EndOfTrack => len == 0,
+        SetTempo => len == 3,
+        SMTPEOffset => len == 5,
+        TimeSignature => len == 4,
+        KeySignature => len == 2,
+        _ => true,
+    }
+}
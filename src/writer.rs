@@ -1,8 +1,11 @@
 use byteorder::{BigEndian, WriteBytesExt};
 use formats::*;
 use messages::*;
-use std::io::Write;
+use std::borrow::Cow;
+use std::collections::VecDeque;
+use std::io::{Seek, SeekFrom, Write};
 use std::{fs, io, path};
+use sysex;
 
 /// `ghakuf`'s SMF builder.
 ///
@@ -56,6 +59,9 @@ pub struct Writer<'a> {
     format: Format,
     time_base: u16,
     running_status: bool,
+    optimise_size: bool,
+    sys_ex_packet_size: usize,
+    sys_ex_packet_gap: u32,
 }
 impl<'a> Writer<'a> {
     /// Builds Writer with initial value.
@@ -66,6 +72,9 @@ impl<'a> Writer<'a> {
     /// | format | ghakuf::formats::Format | ghakuf::formats::Format::F1 |
     /// | time_base | u16 | 480 |
     /// | running_status | bool | false |
+    /// | optimise_size | bool | false |
+    /// | sys_ex_packet_size | usize | 0 (doesn't split) |
+    /// | sys_ex_packet_gap | u32 | 0 |
     ///
     /// # Examples
     ///
@@ -80,6 +89,9 @@ impl<'a> Writer<'a> {
             format: Format::F1,
             time_base: 480,
             running_status: false,
+            optimise_size: false,
+            sys_ex_packet_size: 0,
+            sys_ex_packet_gap: 0,
         }
     }
     /// Returns keeping messages by borrowing.
@@ -196,6 +208,103 @@ impl<'a> Writer<'a> {
         self.running_status = running_status;
         self
     }
+    /// Sets bool value whether you optimise byte size or not.
+    ///
+    /// Size optimisation uses running status, writes NoteOff as NoteOn with velocity 0 (release velocity
+    /// is dropped), and reorders simultaneous MIDI events of different channels so that events sharing
+    /// status byte are grouped. Order of events in each channel and order against meta and system
+    /// exclusive events are kept.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use ghakuf::messages::*;
+    /// use ghakuf::writer::Writer;
+    ///
+    /// let messages = vec![
+    ///     Message::MidiEvent { delta_time: 0, event: MidiEvent::NoteOn { ch: 0, note: 60, velocity: 100 } },
+    ///     Message::MidiEvent { delta_time: 0, event: MidiEvent::NoteOn { ch: 1, note: 48, velocity: 100 } },
+    ///     Message::MidiEvent { delta_time: 0, event: MidiEvent::NoteOn { ch: 0, note: 64, velocity: 100 } },
+    ///     Message::MidiEvent { delta_time: 480, event: MidiEvent::NoteOff { ch: 0, note: 60, velocity: 64 } },
+    ///     Message::MidiEvent { delta_time: 0, event: MidiEvent::NoteOff { ch: 0, note: 64, velocity: 64 } },
+    /// ];
+    /// let mut writer = Writer::new();
+    /// for message in &messages {
+    ///     writer.push(message);
+    /// }
+    /// writer.optimise_size(true);
+    /// let mut data: Vec<u8> = Vec::new();
+    /// writer.write_to(&mut data).unwrap();
+    /// assert_eq!(
+    ///     &data[22..],
+    ///     &[0x00, 0x90, 60, 100, 0x00, 64, 100, 0x00, 0x91, 48, 100, 0x83, 0x60, 0x90, 60, 0, 0x00, 64, 0]
+    /// );
+    /// assert_eq!(writer.bytes_saved(), 2);
+    /// ```
+    pub fn optimise_size(&mut self, optimise_size: bool) -> &mut Writer<'a> {
+        self.optimise_size = optimise_size;
+        self
+    }
+    /// Returns how many bytes running status and size optimisation save, compared with writing status
+    /// byte of every MIDI event.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use ghakuf::messages::*;
+    /// use ghakuf::writer::Writer;
+    ///
+    /// let messages = vec![
+    ///     Message::MidiEvent { delta_time: 0, event: MidiEvent::NoteOn { ch: 0, note: 60, velocity: 100 } },
+    ///     Message::MidiEvent { delta_time: 480, event: MidiEvent::NoteOn { ch: 0, note: 60, velocity: 0 } },
+    /// ];
+    /// let mut writer = Writer::new();
+    /// for message in &messages {
+    ///     writer.push(message);
+    /// }
+    /// assert_eq!(writer.bytes_saved(), 0);
+    /// writer.running_status(true);
+    /// assert_eq!(writer.bytes_saved(), 1);
+    /// ```
+    pub fn bytes_saved(&self) -> usize {
+        let plain: usize = self
+            .track_len_filo(&self.split_messages(), false)
+            .iter()
+            .sum();
+        let written: usize = self
+            .track_len_filo(&self.prepared_messages(), self.uses_running_status())
+            .iter()
+            .sum();
+        plain.saturating_sub(written)
+    }
+    /// Sets maximum data size of system exclusive packet. Longer system exclusive (F0) message is split into F0 packet and F7 continuation packets (0 doesn't split).
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use ghakuf::writer::Writer;
+    ///
+    /// let mut writer: Writer = Writer::new();
+    /// writer.sys_ex_packet_size(128).sys_ex_packet_gap(10);
+    /// ```
+    pub fn sys_ex_packet_size(&mut self, sys_ex_packet_size: usize) -> &mut Writer<'a> {
+        self.sys_ex_packet_size = sys_ex_packet_size;
+        self
+    }
+    /// Sets delta time between split system exclusive packets. The gaps are subtracted from delta times of the following messages in the track as far as possible, so that their timing isn't changed.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use ghakuf::writer::Writer;
+    ///
+    /// let mut writer: Writer = Writer::new();
+    /// writer.sys_ex_packet_gap(10);
+    /// ```
+    pub fn sys_ex_packet_gap(&mut self, sys_ex_packet_gap: u32) -> &mut Writer<'a> {
+        self.sys_ex_packet_gap = sys_ex_packet_gap;
+        self
+    }
     /// Writes out SMF messages you stored.
     ///
     /// # Examples
@@ -245,21 +354,46 @@ impl<'a> Writer<'a> {
                 .create(true)
                 .open(path)?,
         );
-        file.write(Tag::Header.binary())?;
-        file.write(&[0, 0, 0, 6])?;
-        file.write(&self.format.binary())?;
+        self.write_to(&mut file)?;
+        file.flush()
+    }
+    /// Writes SMF to a `Write` object.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use ghakuf::messages::*;
+    /// use ghakuf::writer::*;
+    ///
+    /// let message = Message::MetaEvent {
+    ///     delta_time: 0,
+    ///     event: MetaEvent::EndOfTrack,
+    ///     data: Vec::new(),
+    /// };
+    /// let mut writer = Writer::new();
+    /// writer.push(&message);
+    /// let mut data: Vec<u8> = Vec::new();
+    /// assert!(writer.write_to(&mut data).is_ok());
+    /// assert_eq!(&data[0..4], b"MThd");
+    /// assert_eq!(data.len(), 14 + 8 + 4);
+    /// ```
+    pub fn write_to<W: Write>(&self, file: &mut W) -> Result<(), io::Error> {
+        file.write_all(Tag::Header.binary())?;
+        file.write_all(&[0, 0, 0, 6])?;
+        file.write_all(&self.format.binary())?;
         file.write_u16::<BigEndian>(self.track_number())?;
         file.write_u16::<BigEndian>(self.time_base)?;
-        let mut track_len_filo = self.track_len_filo();
+        let messages = self.prepared_messages();
+        let mut track_len_filo = self.track_len_filo(&messages, self.uses_running_status());
         if self.messages.len() > 0 && *self.messages[0] != Message::TrackChange {
-            file.write(&Message::TrackChange.binary())?;
+            file.write_all(&Message::TrackChange.binary())?;
             file.write_u32::<BigEndian>(track_len_filo.pop().unwrap() as u32)?;
         }
         let mut pre_status_byte: Option<u8> = None;
-        for message in &self.messages {
+        for message in &messages {
             match **message {
                 Message::TrackChange => {
-                    file.write(&Message::TrackChange.binary())?;
+                    file.write_all(&Message::TrackChange.binary())?;
                     file.write_u32::<BigEndian>(track_len_filo.pop().unwrap() as u32)?;
                     pre_status_byte = None;
                     debug!("wrote track change");
@@ -274,32 +408,114 @@ impl<'a> Writer<'a> {
                     match pre_status_byte {
                         Some(pre_status_byte) if pre_status_byte == tmp_status_byte => {
                             let tmp_message = message.binary();
-                            file.write(&tmp_message[0..delta_time.len()])?;
-                            file.write(&message.binary()[delta_time.len() + 1..])?;
+                            file.write_all(&tmp_message[0..delta_time.len()])?;
+                            file.write_all(&message.binary()[delta_time.len() + 1..])?;
                             trace!("wrote some message with running status");
                         }
                         _ => {
-                            file.write(&message.binary())?;
+                            file.write_all(&message.binary())?;
                             trace!("wrote some message");
-                            if self.running_status {
+                            if self.uses_running_status() {
                                 pre_status_byte = Some(tmp_status_byte);
                             }
                         }
                     };
                 }
                 _ => {
-                    file.write(&message.binary())?;
+                    file.write_all(&message.binary())?;
                     trace!("wrote some message");
                 }
             }
         }
-        Ok(file.flush()?)
+        Ok(())
+    }
+    fn uses_running_status(&self) -> bool {
+        self.running_status || self.optimise_size
+    }
+    fn prepared_messages(&self) -> Vec<Cow<'a, Message>> {
+        let messages = self.split_messages();
+        if !self.optimise_size {
+            return messages;
+        }
+        let mut optimised: Vec<Cow<'a, Message>> = Vec::with_capacity(messages.len());
+        // simultaneous MIDI events which may be reordered across channels
+        let mut group: Vec<Cow<'a, Message>> = Vec::new();
+        let mut status: Option<u8> = None;
+        for message in messages {
+            let message = match *message {
+                Message::MidiEvent {
+                    delta_time,
+                    event: MidiEvent::NoteOff { ch, note, .. },
+                } => Cow::Owned(Message::MidiEvent {
+                    delta_time,
+                    event: MidiEvent::NoteOn {
+                        ch,
+                        note,
+                        velocity: 0,
+                    },
+                }),
+                _ => message,
+            };
+            match *message {
+                Message::MidiEvent { delta_time: 0, .. } if !group.is_empty() => {
+                    group.push(message);
+                    continue;
+                }
+                Message::MidiEvent { .. } => {
+                    optimised.append(&mut group_statuses(group, &mut status));
+                    group = vec![message];
+                    continue;
+                }
+                _ => (),
+            }
+            optimised.append(&mut group_statuses(group, &mut status));
+            group = Vec::new();
+            if *message == Message::TrackChange {
+                status = None;
+            }
+            optimised.push(message);
+        }
+        optimised.append(&mut group_statuses(group, &mut status));
+        optimised
+    }
+    fn split_messages(&self) -> Vec<Cow<'a, Message>> {
+        if self.sys_ex_packet_size == 0 {
+            return self
+                .messages
+                .iter()
+                .map(|&message| Cow::Borrowed(message))
+                .collect();
+        }
+        let mut messages: Vec<Cow<'a, Message>> = Vec::new();
+        // gaps between packets which haven't been subtracted from the following delta times yet
+        let mut carry: u32 = 0;
+        for &message in &self.messages {
+            let mut packets =
+                sysex::split(message, self.sys_ex_packet_size, self.sys_ex_packet_gap);
+            if *message == Message::TrackChange {
+                carry = 0;
+            } else if carry > 0 {
+                let delta_time = packets[0].delta_time();
+                let subtracted = carry.min(delta_time);
+                packets[0].set_delta_time(delta_time - subtracted);
+                carry -= subtracted;
+            }
+            if packets.len() > 1 {
+                carry += self.sys_ex_packet_gap * (packets.len() as u32 - 1);
+                messages.extend(packets.into_iter().map(Cow::Owned));
+            } else if packets[0] == *message {
+                messages.push(Cow::Borrowed(message));
+            } else {
+                messages.push(Cow::Owned(packets.remove(0)));
+            }
+        }
+        messages
     }
-    fn track_len_filo(&self) -> Vec<usize> {
+    fn track_len_filo(&self, messages: &[Cow<'a, Message>], running_status: bool) -> Vec<usize> {
         // First In Last Out
         let mut tracks_len: Vec<usize> = vec![0];
         let mut pre_status_byte: Option<u8> = None;
-        for message in &self.messages {
+        for message in messages {
             match **message {
                 Message::TrackChange => {
                     tracks_len.insert(0, 0);
@@ -313,7 +529,7 @@ impl<'a> Writer<'a> {
                             tracks_len[0] -= 1;
                         }
                         _ => {
-                            if self.running_status {
+                            if running_status {
                                 pre_status_byte = Some(tmp_status_byte);
                             }
                         }
@@ -341,3 +557,378 @@ impl<'a> Writer<'a> {
         number
     }
 }
+
+// writes data at position of sink and seeks back to the end
+type Patch<W> = fn(&mut W, u64, &[u8]) -> io::Result<()>;
+
+/// `ghakuf`'s streaming SMF builder.
+///
+/// This writes events of a track as they arrive instead of keeping messages, so that very long SMF can
+/// be built with little memory. Length of each `MTrk` chunk is filled in at the end of the track, by
+/// seeking back on seekable sink (`StreamWriter::seekable`) or through a buffer of the current track for
+/// other sinks (`StreamWriter::new`). Tracks are separated by `begin_track` and `end_track` instead of
+/// TrackChange message, and you should push MetaEvent::EndOfTrack at the end of each track.
+///
+/// # Examples
+///
+/// ```
+/// use ghakuf::messages::*;
+/// use ghakuf::smf::Smf;
+/// use ghakuf::writer::StreamWriter;
+/// use std::io::Cursor;
+///
+/// let mut writer = StreamWriter::seekable(Cursor::new(Vec::new()), 1, 480).unwrap();
+/// writer.running_status(true);
+/// for ch in 0..2 {
+///     writer.begin_track().unwrap();
+///     for i in 0..1000 {
+///         writer
+///             .push_event(&Message::MidiEvent {
+///                 delta_time: 120,
+///                 event: MidiEvent::NoteOn { ch, note: 60, velocity: if i % 2 == 0 { 100 } else { 0 } },
+///             })
+///             .unwrap();
+///     }
+///     writer
+///         .push_event(&Message::MetaEvent { delta_time: 0, event: MetaEvent::EndOfTrack, data: Vec::new() })
+///         .unwrap();
+///     writer.end_track().unwrap();
+/// }
+/// let data = writer.finish().unwrap().into_inner();
+/// let smf = Smf::from_bytes(&data).unwrap();
+/// assert_eq!(smf.tracks.len(), 2);
+/// assert_eq!(smf.tracks[1].len(), 1001);
+/// ```
+pub struct StreamWriter<W: Write> {
+    sink: W,
+    // only for seekable sink
+    patch: Option<Patch<W>>,
+    // position of sink at the end of written data
+    position: u64,
+    // position of MThd chunk
+    header: u64,
+    // number of tracks written in header, only for non-seekable sink
+    declared_tracks: Option<u16>,
+    tracks: u16,
+    running_status: bool,
+    in_track: bool,
+    // position of length of current MTrk chunk, only for seekable sink
+    track_start: u64,
+    track_len: u64,
+    // current track, only for non-seekable sink
+    buffer: Vec<u8>,
+    pre_status_byte: Option<u8>,
+}
+impl<W: Write> StreamWriter<W> {
+    /// Builds StreamWriter for any sink, and writes header with number of tracks you are going to write.
+    ///
+    /// Events of each track are buffered until `end_track`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use ghakuf::messages::*;
+    /// use ghakuf::writer::StreamWriter;
+    ///
+    /// let mut writer = StreamWriter::new(Vec::new(), 0, 1, 480).unwrap();
+    /// writer.begin_track().unwrap();
+    /// writer
+    ///     .push_event(&Message::MetaEvent { delta_time: 0, event: MetaEvent::EndOfTrack, data: Vec::new() })
+    ///     .unwrap();
+    /// writer.end_track().unwrap();
+    /// let data = writer.finish().unwrap();
+    /// assert_eq!(&data[8..12], &[0, 0, 0, 1]);
+    /// assert_eq!(&data[14..], b"MTrk\x00\x00\x00\x04\x00\xff\x2f\x00");
+    /// ```
+    pub fn new(sink: W, format: u16, tracks: u16, time_base: u16) -> io::Result<StreamWriter<W>> {
+        let mut writer = StreamWriter::with_sink(sink, None, 0);
+        writer.declared_tracks = Some(tracks);
+        writer.write_header(format, tracks, time_base)?;
+        Ok(writer)
+    }
+    fn with_sink(sink: W, patch: Option<Patch<W>>, position: u64) -> StreamWriter<W> {
+        StreamWriter {
+            sink,
+            patch,
+            position,
+            header: position,
+            declared_tracks: None,
+            tracks: 0,
+            running_status: false,
+            in_track: false,
+            track_start: 0,
+            track_len: 0,
+            buffer: Vec::new(),
+            pre_status_byte: None,
+        }
+    }
+    /// Sets bool value whether you adopt running status or not.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use ghakuf::writer::StreamWriter;
+    ///
+    /// let mut writer = StreamWriter::new(Vec::new(), 0, 1, 480).unwrap();
+    /// writer.running_status(true);
+    /// ```
+    pub fn running_status(&mut self, running_status: bool) -> &mut StreamWriter<W> {
+        self.running_status = running_status;
+        self
+    }
+    /// Begins a track.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use ghakuf::writer::StreamWriter;
+    ///
+    /// let mut writer = StreamWriter::new(Vec::new(), 0, 1, 480).unwrap();
+    /// assert!(writer.begin_track().is_ok());
+    /// assert!(writer.begin_track().is_err());
+    /// ```
+    pub fn begin_track(&mut self) -> io::Result<()> {
+        if self.in_track {
+            return Err(invalid_input("track has already begun"));
+        }
+        match self.declared_tracks {
+            Some(tracks) if self.tracks >= tracks => {
+                return Err(invalid_input("more tracks than declared in header"))
+            }
+            _ => (),
+        }
+        // number of tracks in header is u16
+        if self.tracks.checked_add(1).is_none() {
+            return Err(invalid_input("more than 65535 tracks"));
+        }
+        if self.patch.is_some() {
+            self.track_start = self.position + 4;
+            self.sink.write_all(Tag::Track.binary())?;
+            self.sink.write_all(&[0, 0, 0, 0])?;
+            self.position += 8;
+        }
+        self.in_track = true;
+        self.track_len = 0;
+        self.pre_status_byte = None;
+        debug!("began track {}", self.tracks);
+        Ok(())
+    }
+    /// Writes message to the current track.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use ghakuf::messages::*;
+    /// use ghakuf::writer::StreamWriter;
+    ///
+    /// let message = Message::MidiEvent {
+    ///     delta_time: 0,
+    ///     event: MidiEvent::NoteOn { ch: 0, note: 0x40, velocity: 0 },
+    /// };
+    /// let mut writer = StreamWriter::new(Vec::new(), 0, 1, 480).unwrap();
+    /// assert!(writer.push_event(&message).is_err());
+    /// writer.begin_track().unwrap();
+    /// assert!(writer.push_event(&message).is_ok());
+    /// assert!(writer.push_event(&Message::TrackChange).is_err());
+    /// ```
+    pub fn push_event(&mut self, message: &Message) -> io::Result<()> {
+        if !self.in_track {
+            return Err(invalid_input("event is pushed outside track"));
+        }
+        let mut binary = match *message {
+            Message::TrackChange => {
+                return Err(invalid_input(
+                    "TrackChange is pushed instead of end_track and begin_track",
+                ))
+            }
+            _ => message.binary(),
+        };
+        if let Message::MidiEvent {
+            delta_time,
+            ref event,
+        } = *message
+        {
+            let status_byte = event.status_byte();
+            if self.pre_status_byte == Some(status_byte) {
+                binary.remove(VLQ::new(delta_time).len());
+                trace!("wrote some message with running status");
+            } else if self.running_status {
+                self.pre_status_byte = Some(status_byte);
+            }
+        }
+        if self.patch.is_some() {
+            self.sink.write_all(&binary)?;
+            self.position += binary.len() as u64;
+        } else {
+            self.buffer.extend_from_slice(&binary);
+        }
+        self.track_len += binary.len() as u64;
+        Ok(())
+    }
+    /// Ends the current track and fills in its length.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use ghakuf::writer::StreamWriter;
+    ///
+    /// let mut writer = StreamWriter::new(Vec::new(), 0, 1, 480).unwrap();
+    /// assert!(writer.end_track().is_err());
+    /// writer.begin_track().unwrap();
+    /// assert!(writer.end_track().is_ok());
+    /// ```
+    pub fn end_track(&mut self) -> io::Result<()> {
+        if !self.in_track {
+            return Err(invalid_input("track hasn't begun"));
+        }
+        if self.track_len > u32::MAX as u64 {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "track is longer than 4 GiB",
+            ));
+        }
+        let track_len = (self.track_len as u32).to_be_bytes();
+        match self.patch {
+            Some(patch) => patch(&mut self.sink, self.track_start, &track_len)?,
+            None => {
+                self.sink.write_all(Tag::Track.binary())?;
+                self.sink.write_all(&track_len)?;
+                self.sink.write_all(&self.buffer)?;
+                self.position += 8 + self.track_len;
+                self.buffer.clear();
+            }
+        }
+        self.in_track = false;
+        self.tracks += 1;
+        debug!("ended track with {} bytes", self.track_len);
+        Ok(())
+    }
+    /// Finishes writing, and returns the sink.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use ghakuf::writer::StreamWriter;
+    ///
+    /// let writer = StreamWriter::new(Vec::new(), 1, 2, 480).unwrap();
+    /// assert!(writer.finish().is_err());
+    /// ```
+    pub fn finish(mut self) -> io::Result<W> {
+        if self.in_track {
+            return Err(invalid_input("track hasn't ended"));
+        }
+        match (self.declared_tracks, self.patch) {
+            (Some(tracks), _) if tracks != self.tracks => {
+                return Err(invalid_input(&format!(
+                    "{} tracks are declared in header, but {} tracks are written",
+                    tracks, self.tracks
+                )))
+            }
+            (None, Some(patch)) => {
+                patch(&mut self.sink, self.header + 10, &self.tracks.to_be_bytes())?
+            }
+            _ => (),
+        }
+        self.sink.flush()?;
+        Ok(self.sink)
+    }
+    fn write_header(&mut self, format: u16, tracks: u16, time_base: u16) -> io::Result<()> {
+        self.sink.write_all(Tag::Header.binary())?;
+        self.sink.write_all(&[0, 0, 0, 6])?;
+        self.sink.write_all(&Format::new(format).binary())?;
+        self.sink.write_u16::<BigEndian>(tracks)?;
+        self.sink.write_u16::<BigEndian>(time_base)?;
+        self.position += 14;
+        Ok(())
+    }
+}
+impl<W: Write + Seek> StreamWriter<W> {
+    /// Builds StreamWriter for seekable sink (such as `File` or `BufWriter<File>`), and writes header.
+    ///
+    /// Events are written to the sink directly, and lengths of tracks and the number of tracks are
+    /// filled in by seeking back.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use ghakuf::writer::StreamWriter;
+    /// use std::fs::File;
+    /// use std::io::BufWriter;
+    ///
+    /// let file = BufWriter::new(File::create("tests/stream_writer_doctest.mid").unwrap());
+    /// let mut writer = StreamWriter::seekable(file, 1, 480).unwrap();
+    /// writer.begin_track().unwrap();
+    /// writer.end_track().unwrap();
+    /// assert!(writer.finish().is_ok());
+    /// ```
+    pub fn seekable(mut sink: W, format: u16, time_base: u16) -> io::Result<StreamWriter<W>> {
+        let position = sink.stream_position()?;
+        let mut writer = StreamWriter::with_sink(sink, Some(patch::<W>), position);
+        writer.write_header(format, 0, time_base)?;
+        Ok(writer)
+    }
+}
+
+fn patch<W: Write + Seek>(sink: &mut W, position: u64, data: &[u8]) -> io::Result<()> {
+    let end = sink.stream_position()?;
+    sink.seek(SeekFrom::Start(position))?;
+    sink.write_all(data)?;
+    sink.seek(SeekFrom::Start(end))?;
+    Ok(())
+}
+
+fn invalid_input(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidInput, message)
+}
+
+// Reorders simultaneous MIDI events so that channel keeping running status comes first and events of
+// each channel are written together, keeping order in each channel.
+fn group_statuses<'a>(
+    group: Vec<Cow<'a, Message>>,
+    status: &mut Option<u8>,
+) -> Vec<Cow<'a, Message>> {
+    let delta_time = match group.first() {
+        Some(message) => message.delta_time(),
+        None => return group,
+    };
+    let mut channels: Vec<VecDeque<Cow<'a, Message>>> = Vec::new();
+    for message in group {
+        let channel = status_byte(&message) & 0x0f;
+        match channels
+            .iter_mut()
+            .find(|queue| queue.front().map(|front| status_byte(front) & 0x0f) == Some(channel))
+        {
+            Some(queue) => queue.push_back(message),
+            None => channels.push(VecDeque::from(vec![message])),
+        }
+    }
+    let mut grouped = Vec::new();
+    let mut channel = 0;
+    while !channels.is_empty() {
+        if channels[channel].front().map(|front| status_byte(front)) != *status {
+            channel = channels
+                .iter()
+                .position(|queue| queue.front().map(|front| status_byte(front)) == *status)
+                .unwrap_or(0);
+        }
+        let mut message = channels[channel].pop_front().unwrap();
+        *status = Some(status_byte(&message));
+        let expected = if grouped.is_empty() { delta_time } else { 0 };
+        if message.delta_time() != expected {
+            message.to_mut().set_delta_time(expected);
+        }
+        grouped.push(message);
+        if channels[channel].is_empty() {
+            channels.remove(channel);
+            channel = 0;
+        }
+    }
+    grouped
+}
+
+fn status_byte(message: &Message) -> u8 {
+    match *message {
+        Message::MidiEvent { ref event, .. } => event.status_byte(),
+        _ => 0,
+    }
+}
@@ -1,5 +1,7 @@
 extern crate byteorder;
 extern crate ghakuf;
+#[cfg(feature = "serde")]
+extern crate serde_json;
 
 use byteorder::{BigEndian, WriteBytesExt};
 use ghakuf::messages::*;
@@ -267,3 +269,623 @@ fn test_messages_skipped() -> Vec<Message> {
     });
     test_messages
 }
+
+#[test]
+fn chase_integration_testing() {
+    use ghakuf::state::ChannelState;
+    let path = path::Path::new("tests/test.mid");
+    let mut state = ChannelState::new();
+    {
+        let mut reader = Reader::new(&mut state, &path).unwrap();
+        assert!(reader.read().is_ok());
+    }
+    assert_eq!(
+        state.chase(100, true),
+        vec![Message::MidiEvent {
+            delta_time: 0,
+            event: MidiEvent::NoteOn {
+                ch: 0,
+                note: 0x40,
+                velocity: 0x7f,
+            },
+        }]
+    );
+    assert!(state.chase(289, true).is_empty());
+}
+
+#[test]
+fn sys_ex_packet_integration_testing() {
+    let path = path::Path::new("tests/test_sys_ex_packets.mid");
+    let sys_ex = vec![0x7e, 0x7f, 0x06, 0x02, 0x41, 0x00, 0x00, 0x01, 0x02, 0xf7];
+    let messages = vec![
+        Message::SysExEvent {
+            delta_time: 0,
+            event: SysExEvent::F0,
+            data: sys_ex.clone(),
+        },
+        Message::SysExEvent {
+            delta_time: 0,
+            event: SysExEvent::F7,
+            data: vec![0xf8],
+        },
+        Message::MidiEvent {
+            delta_time: 100,
+            event: MidiEvent::NoteOn {
+                ch: 0,
+                note: 0x3c,
+                velocity: 0x7f,
+            },
+        },
+        Message::MetaEvent {
+            delta_time: 0,
+            event: MetaEvent::EndOfTrack,
+            data: Vec::new(),
+        },
+    ];
+    let mut writer = Writer::new();
+    writer.sys_ex_packet_size(4).sys_ex_packet_gap(5);
+    for message in &messages {
+        writer.push(&message);
+    }
+    assert!(writer.write(&path).is_ok());
+
+    let mut split = CollectHandler { messages: Vec::new() };
+    {
+        let mut reader = Reader::new(&mut split, &path).unwrap();
+        assert!(reader.read().is_ok());
+    }
+    assert_eq!(split.messages.len(), 6);
+    assert_eq!(
+        split.messages[2],
+        Message::SysExEvent {
+            delta_time: 5,
+            event: SysExEvent::F7,
+            data: vec![0x02, 0xf7],
+        }
+    );
+    assert_eq!(split.messages[3], messages[1]);
+    assert_eq!(split.messages[4].delta_time(), 90);
+
+    let mut joined = CollectHandler { messages: Vec::new() };
+    {
+        let mut reader = Reader::new(&mut joined, &path).unwrap();
+        reader.join_sys_ex(true);
+        assert!(reader.read().is_ok());
+    }
+    assert_eq!(joined.messages, ghakuf::sysex::join(&split.messages));
+    assert_eq!(joined.messages[0], messages[0]);
+    assert_eq!(
+        joined.messages[1],
+        Message::SysExEvent {
+            delta_time: 10,
+            event: SysExEvent::F7,
+            data: vec![0xf8],
+        }
+    );
+    assert_eq!(joined.messages[2].delta_time(), 90);
+}
+struct CollectHandler {
+    messages: Vec<Message>,
+}
+impl Handler for CollectHandler {
+    fn meta_event(&mut self, delta_time: u32, event: &MetaEvent, data: &Vec<u8>) {
+        self.messages.push(Message::MetaEvent {
+            delta_time,
+            event: event.clone(),
+            data: data.clone(),
+        });
+    }
+    fn midi_event(&mut self, delta_time: u32, event: &MidiEvent) {
+        self.messages.push(Message::MidiEvent {
+            delta_time,
+            event: event.clone(),
+        });
+    }
+    fn sys_ex_event(&mut self, delta_time: u32, event: &SysExEvent, data: &Vec<u8>) {
+        self.messages.push(Message::SysExEvent {
+            delta_time,
+            event: event.clone(),
+            data: data.clone(),
+        });
+    }
+}
+
+#[test]
+fn lyrics_integration_testing() {
+    use ghakuf::encoding::Encoding;
+    use ghakuf::lyrics::{Break, LyricStyle, Lyrics, LyricsHandler};
+    let path = path::Path::new("tests/test_lyrics.mid");
+    let mut lyrics = Lyrics::new();
+    lyrics.title.push("Frog Song".to_string());
+    lyrics.language = Some("Japanese".to_string());
+    lyrics
+        .push(0, "かえるの", Break::Paragraph)
+        .push(480, "うたが", Break::Syllable)
+        .push(960, "きこえて", Break::Line)
+        .push(1440, "くるよ", Break::Paragraph);
+    let mut messages = vec![
+        Message::MetaEvent {
+            delta_time: 960,
+            event: MetaEvent::SetTempo,
+            data: vec![0x0f, 0x42, 0x40], // 1,000,000 microseconds per quarter note
+        },
+        Message::MetaEvent {
+            delta_time: 0,
+            event: MetaEvent::EndOfTrack,
+            data: Vec::new(),
+        },
+    ];
+    for style in &[LyricStyle::Lyric, LyricStyle::Karaoke] {
+        let mut track = lyrics.messages(*style, Encoding::ShiftJis).unwrap();
+        messages.push(Message::TrackChange);
+        messages.append(&mut track);
+        let mut writer = Writer::new();
+        for message in &messages {
+            writer.push(&message);
+        }
+        assert!(writer.write(&path).is_ok());
+
+        let mut handler = LyricsHandler::new();
+        {
+            let mut reader = Reader::new(&mut handler, &path).unwrap();
+            assert!(reader.read().is_ok());
+        }
+        let read = handler.lyrics().unwrap();
+        assert_eq!(read.title, lyrics.title);
+        assert_eq!(read.language, lyrics.language);
+        assert_eq!(read.text(), lyrics.text());
+        let seconds: Vec<f64> = read.entries.iter().map(|entry| entry.seconds).collect();
+        assert_eq!(seconds, vec![0.0, 0.5, 1.0, 2.0]);
+        messages.truncate(2);
+    }
+}
+
+#[test]
+fn sequencer_specific_integration_testing() {
+    use ghakuf::sysex::ManufacturerId;
+    use std::any::Any;
+    let path = path::Path::new("tests/test_sequencer_specific.mid");
+    let id = ManufacturerId::Extended(0x00, 0x7f);
+    let messages = vec![
+        Message::sequencer_specific_meta_event(0, id, &[0x01, 0x7f, 0x00, 0x00]),
+        Message::sequencer_specific_meta_event(10, id, &[0x02]),
+        Message::sequencer_specific_meta_event(20, ManufacturerId::Standard(0x43), &[0x01, 0x00, 0x00, 0x00]),
+        Message::MetaEvent {
+            delta_time: 0,
+            event: MetaEvent::EndOfTrack,
+            data: Vec::new(),
+        },
+    ];
+    let mut writer = Writer::new();
+    for message in &messages {
+        writer.push(&message);
+    }
+    assert!(writer.write(&path).is_ok());
+
+    let parser = |data: &[u8]| -> Option<Box<dyn Any>> {
+        match data {
+            [0x01, r, g, b] => Some(Box::new((*r, *g, *b))),
+            _ => None,
+        }
+    };
+    let mut handler = SequencerSpecificHandler { tick: 0, colors: Vec::new() };
+    {
+        let mut reader = Reader::new(&mut handler, &path).unwrap();
+        reader.register_sequencer_specific(id, &parser);
+        assert!(reader.read().is_ok());
+    }
+    assert_eq!(handler.colors, vec![(0, id, (0x7f, 0x00, 0x00))]);
+    assert_eq!(handler.tick, 30);
+}
+struct SequencerSpecificHandler {
+    tick: u32,
+    colors: Vec<(u32, ghakuf::sysex::ManufacturerId, (u8, u8, u8))>,
+}
+impl Handler for SequencerSpecificHandler {
+    fn meta_event(&mut self, delta_time: u32, _: &MetaEvent, _: &Vec<u8>) {
+        self.tick += delta_time;
+    }
+    fn sequencer_specific_event(
+        &mut self,
+        _: u32,
+        manufacturer: &ghakuf::sysex::ManufacturerId,
+        value: &dyn std::any::Any,
+    ) {
+        let color = value.downcast_ref::<(u8, u8, u8)>().unwrap();
+        self.colors.push((self.tick, *manufacturer, *color));
+    }
+}
+
+#[test]
+fn routing_integration_testing() {
+    use ghakuf::routing::{Route, Router};
+    let path = path::Path::new("tests/test_routing.mid");
+    let messages = vec![
+        Message::MetaEvent { delta_time: 0, event: MetaEvent::MidiPort, data: vec![1] },
+        Message::MetaEvent { delta_time: 0, event: MetaEvent::MIDIChannelPrefix, data: vec![9] },
+        Message::MetaEvent { delta_time: 0, event: MetaEvent::SequenceOrTrackName, data: b"Drums".to_vec() },
+        Message::SysExEvent { delta_time: 0, event: SysExEvent::F0, data: vec![0x7e, 0x7f, 0x09, 0x01, 0xf7] },
+        Message::MidiEvent { delta_time: 0, event: MidiEvent::NoteOn { ch: 9, note: 36, velocity: 100 } },
+        Message::MetaEvent { delta_time: 10, event: MetaEvent::Marker, data: b"A".to_vec() },
+        Message::MetaEvent { delta_time: 0, event: MetaEvent::EndOfTrack, data: Vec::new() },
+        Message::TrackChange,
+        Message::MetaEvent { delta_time: 0, event: MetaEvent::Marker, data: b"B".to_vec() },
+        Message::MidiEvent { delta_time: 0, event: MidiEvent::NoteOn { ch: 0, note: 60, velocity: 100 } },
+        Message::MetaEvent { delta_time: 0, event: MetaEvent::EndOfTrack, data: Vec::new() },
+    ];
+    let mut writer = Writer::new();
+    writer.format(1);
+    for message in &messages {
+        writer.push(&message);
+    }
+    assert!(writer.write(&path).is_ok());
+
+    let mut handler = RoutingHandler { events: Vec::new() };
+    {
+        let mut router = Router::new(&mut handler);
+        let mut reader = Reader::new(&mut router, &path).unwrap();
+        assert!(reader.read().is_ok());
+    }
+    let drums = Route { channel: Some(9), port: Some(1) };
+    let port = Route { channel: None, port: Some(1) };
+    assert_eq!(
+        handler.events,
+        vec![
+            ("MidiPort".to_string(), port),
+            ("MIDIChannelPrefix".to_string(), drums),
+            ("SequenceOrTrackName".to_string(), drums),
+            ("F0".to_string(), drums),
+            ("NoteOn".to_string(), port),
+            ("Marker".to_string(), port),
+            ("EndOfTrack".to_string(), port),
+            ("Marker".to_string(), Route::default()),
+            ("NoteOn".to_string(), Route::default()),
+            ("EndOfTrack".to_string(), Route::default()),
+        ]
+    );
+    assert_eq!(drums.index(), Some(25));
+}
+struct RoutingHandler {
+    events: Vec<(String, ghakuf::routing::Route)>,
+}
+impl ghakuf::routing::RoutedHandler for RoutingHandler {
+    fn meta_event(&mut self, _: u32, event: &MetaEvent, _: &Vec<u8>, route: ghakuf::routing::Route) {
+        self.events.push((format!("{}", event), route));
+    }
+    fn midi_event(&mut self, _: u32, _: &MidiEvent, port: Option<u8>) {
+        self.events.push(("NoteOn".to_string(), ghakuf::routing::Route { channel: None, port }));
+    }
+    fn sys_ex_event(&mut self, _: u32, event: &SysExEvent, _: &Vec<u8>, route: ghakuf::routing::Route) {
+        self.events.push((format!("{}", event), route));
+    }
+}
+
+#[test]
+fn transform_integration_testing() {
+    use ghakuf::smf::Smf;
+    use ghakuf::transform::{Chain, ChannelMap, OutOfRange, ProgramMap, Transform, Transpose, Velocity};
+    let path = path::Path::new("tests/test_transform.mid");
+    let mut transpose = Transpose::new(60);
+    transpose.out_of_range(OutOfRange::Drop).skip_drums(true);
+    let mut velocity = Velocity::new();
+    velocity.scale(0.5);
+    let mut channel_map = ChannelMap::new();
+    channel_map.remap(0, 1);
+    let mut program_map = ProgramMap::new();
+    program_map.remap(0, 40);
+    let mut chain = Chain::new();
+    chain.push(&transpose).push(&velocity).push(&channel_map).push(&program_map);
+
+    let mut smf = Smf::read(path::Path::new("tests/test.mid")).unwrap();
+    let original = smf.clone();
+    smf.transform(&chain);
+    assert!(smf.write(&path).is_ok());
+    let transformed = Smf::read(&path).unwrap();
+    assert_eq!(transformed, smf);
+    assert_eq!(transformed.tracks.len(), original.tracks.len());
+    for (track, original) in transformed.tracks.iter().zip(original.tracks.iter()) {
+        let duration = |messages: &Vec<Message>| -> u32 { messages.iter().map(|message| message.delta_time()).sum() };
+        assert_eq!(duration(track), duration(original));
+        let expected: Vec<Message> = original.iter().filter_map(|message| chain.map(message)).collect();
+        assert_eq!(track.len(), expected.len());
+    }
+}
+
+#[test]
+fn rmid_validate_integration_testing() {
+    use ghakuf::rmid;
+    use ghakuf::smf::Smf;
+    use ghakuf::validate::{validate, Issue};
+    use std::fs;
+    let path = path::Path::new("tests/test_rmid.rmi");
+    let smf = Smf::read(path::Path::new("tests/test.mid")).unwrap();
+    assert_eq!(validate(&smf), vec![]);
+
+    let data = fs::read("tests/test.mid").unwrap();
+    assert!(fs::write(path, rmid::wrap(&data)).is_ok());
+    let rmid = fs::read(path).unwrap();
+    assert!(rmid::is_rmid(&rmid));
+    assert_eq!(rmid::unwrap(&rmid), Ok(&data[..]));
+    assert_eq!(Smf::from_bytes(&rmid).unwrap(), smf);
+
+    let mut merged = smf.clone();
+    merged.set_format(0);
+    assert_eq!(validate(&merged), vec![]);
+    merged.format = 1;
+    merged.tracks[0].pop();
+    assert_eq!(validate(&merged), vec![Issue::MissingEndOfTrack { track: 0 }]);
+}
+
+#[test]
+fn text_integration_testing() {
+    use ghakuf::smf::Smf;
+    use ghakuf::text::Text;
+    use std::fs;
+    let data = fs::read("tests/test.mid").unwrap();
+    let fixture = fs::read_to_string("tests/test_text.txt").unwrap();
+    let text: Text = fixture.parse().unwrap();
+    assert!(text.running_status);
+    assert_eq!(text.to_bytes(), data);
+    assert_eq!(text.smf, Smf::read(path::Path::new("tests/test.mid")).unwrap());
+    assert_eq!(Text::from_bytes(&data).unwrap().to_string(), fixture);
+}
+
+#[test]
+fn midicsv_integration_testing() {
+    use ghakuf::midicsv;
+    use ghakuf::text::Text;
+    use std::fs;
+    let data = fs::read("tests/test.mid").unwrap();
+    let text = Text::from_bytes(&data).unwrap();
+    let csv = midicsv::to_csv(&text.smf);
+    assert!(csv.starts_with("0, 0, Header, 1, 2, 480\n1, 0, Start_track\n1, 0, Tempo, 588235\n"));
+    assert_eq!(midicsv::from_csv(&csv).unwrap(), text.smf);
+    let csv = midicsv::text_to_csv(&text);
+    assert!(csv.starts_with("# ghakuf running_status=on\n0, 0, Header, 1, 2, 480\n"));
+    assert_eq!(midicsv::text_from_csv(&csv).unwrap().to_bytes(), data);
+}
+
+#[cfg(feature = "serde")]
+#[test]
+fn serde_integration_testing() {
+    use ghakuf::formats::Format;
+    let mut messages = test_messages();
+    messages.push(Message::MetaEvent {
+        delta_time: 0,
+        event: MetaEvent::Unknown { event_type: 0x60 },
+        data: vec![0x01],
+    });
+    messages.push(Message::MidiEvent {
+        delta_time: 0,
+        event: MidiEvent::PitchBendChange { ch: 1, data: -8192 },
+    });
+    messages.push(Message::SysExEvent {
+        delta_time: 0,
+        event: SysExEvent::F0,
+        data: vec![0x7e, 0x7f, 0x09, 0x01, 0xf7],
+    });
+    messages.push(Message::SysExEvent {
+        delta_time: 0,
+        event: SysExEvent::Unknown { status: 0xf5 },
+        data: Vec::new(),
+    });
+    let json = serde_json::to_string(&messages).unwrap();
+    assert_eq!(serde_json::from_str::<Vec<Message>>(&json).unwrap(), messages);
+    assert!(json.contains(r#"{"type":"SysExEvent","delta_time":0,"event":{"type":"F0"},"data":[126,127,9,1,247]}"#));
+    assert!(json.contains(r#"{"type":"PitchBendChange","ch":1,"data":-8192}"#));
+    assert!(json.contains(r#"{"type":"Unknown","status":245}"#));
+    assert!(json.contains(r#"{"type":"TrackChange"}"#));
+    let formats = [Format::F0, Format::F1, Format::F2, Format::Unknown];
+    let json = serde_json::to_string(&formats).unwrap();
+    assert_eq!(json, r#"["F0","F1","F2","Unknown"]"#);
+    assert_eq!(serde_json::from_str::<Vec<Format>>(&json).unwrap(), formats);
+}
+
+#[test]
+fn diff_integration_testing() {
+    use ghakuf::diff::{Change, Diff};
+    use ghakuf::smf::Smf;
+    use ghakuf::text::Text;
+    use ghakuf::transform::Transpose;
+    use std::fs;
+    let data = fs::read("tests/test.mid").unwrap();
+    let old = Smf::from_bytes(&data).unwrap();
+    let mut text = Text::from_bytes(&data).unwrap();
+    text.running_status = !text.running_status;
+    let rewritten = Smf::from_bytes(&text.to_bytes()).unwrap();
+    assert!(Diff::new().compare(&old, &rewritten).is_empty());
+
+    let mut transposed = old.clone();
+    transposed.transform(&Transpose::new(12));
+    let report = Diff::new().compare(&old, &transposed);
+    assert_eq!(report.changes.len(), 12);
+    assert!(report
+        .changes
+        .iter()
+        .all(|change| matches!(*change, Change::Removed { track: 1, .. } | Change::Added { track: 1, .. })));
+}
+
+#[test]
+fn normalise_integration_testing() {
+    use ghakuf::smf::Smf;
+    use ghakuf::sysex;
+    use ghakuf::text::Text;
+    use ghakuf::transform::Normalise;
+    use std::fs;
+    let note = |delta_time, event| Message::MidiEvent { delta_time, event };
+    let on = |note| MidiEvent::NoteOn { ch: 0, note, velocity: 127 };
+    let off = |note| MidiEvent::NoteOff { ch: 0, note, velocity: 64 };
+    let volume = Message::MidiEvent {
+        delta_time: 0,
+        event: MidiEvent::ControlChange { ch: 0, control: 7, data: 100 },
+    };
+    let gm_on = Message::SysExEvent {
+        delta_time: 0,
+        event: SysExEvent::F0,
+        data: vec![0x7e, 0x7f, 0x09, 0x01, 0xf7],
+    };
+    let end_of_track = Message::MetaEvent {
+        delta_time: 0,
+        event: MetaEvent::EndOfTrack,
+        data: Vec::new(),
+    };
+
+    let mut exported = Smf::from_bytes(&fs::read("tests/test.mid").unwrap()).unwrap();
+    exported.tracks[1].insert(0, volume.clone());
+    exported.tracks[1].insert(0, gm_on.clone());
+    let mut another = exported.clone();
+    let mut track = sysex::split(&gm_on, 3, 0);
+    track.append(&mut vec![
+        note(0, on(60)),
+        volume.clone(),
+        volume,
+        note(48, on(62)),
+        note(0, off(60)),
+        note(48, on(64)),
+        note(0, off(62)),
+        end_of_track,
+        note(192, off(64)),
+    ]);
+    another.tracks[1] = track;
+    assert_ne!(Text::new(exported.clone()).to_bytes(), Text::new(another.clone()).to_bytes());
+
+    let normalise = Normalise::new();
+    exported.normalise(&normalise);
+    another.normalise(&normalise);
+    assert_eq!(Text::new(exported).to_bytes(), Text::new(another).to_bytes());
+}
+
+#[test]
+fn optimise_size_integration_testing() {
+    use ghakuf::smf::Smf;
+    use ghakuf::transform::absolute;
+    let event = |delta_time, event| Message::MidiEvent { delta_time, event };
+    let mut messages = vec![Message::MetaEvent {
+        delta_time: 0,
+        event: MetaEvent::SetTempo,
+        data: vec![0x07, 0xa1, 0x20],
+    }];
+    for &(delta_time, velocity) in &[(0, 100), (480, 90), (480, 80)] {
+        for &note in &[60, 64, 67] {
+            messages.push(event(delta_time, MidiEvent::NoteOn { ch: 0, note, velocity }));
+            messages.push(event(0, MidiEvent::NoteOn { ch: 1, note: note - 24, velocity }));
+        }
+        messages.push(event(0, MidiEvent::ControlChange { ch: 1, control: 10, data: velocity }));
+        messages.push(event(240, MidiEvent::PitchBendChange { ch: 0, data: 100 }));
+        for &note in &[60, 64, 67] {
+            messages.push(event(240, MidiEvent::NoteOff { ch: 0, note, velocity: 64 }));
+            messages.push(event(0, MidiEvent::NoteOff { ch: 1, note: note - 24, velocity: 64 }));
+        }
+    }
+    messages.push(Message::MetaEvent {
+        delta_time: 0,
+        event: MetaEvent::EndOfTrack,
+        data: Vec::new(),
+    });
+
+    let mut writer = Writer::new();
+    writer.format(0);
+    for message in &messages {
+        writer.push(message);
+    }
+    let mut plain: Vec<u8> = Vec::new();
+    writer.write_to(&mut plain).unwrap();
+    let mut running: Vec<u8> = Vec::new();
+    writer.running_status(true).write_to(&mut running).unwrap();
+    let mut optimised: Vec<u8> = Vec::new();
+    writer.optimise_size(true).write_to(&mut optimised).unwrap();
+    assert!(optimised.len() < running.len());
+    assert_eq!(writer.bytes_saved(), plain.len() - optimised.len());
+
+    let channel = |messages: &[Message], ch: u8| -> Vec<(u64, MidiEvent)> {
+        absolute(messages)
+            .into_iter()
+            .filter_map(|(tick, message)| match message {
+                Message::MidiEvent { event, .. } if event.status_byte() & 0x0f == ch => match event {
+                    MidiEvent::NoteOff { ch, note, .. } => {
+                        Some((tick, MidiEvent::NoteOn { ch, note, velocity: 0 }))
+                    }
+                    event => Some((tick, event)),
+                },
+                _ => None,
+            })
+            .collect()
+    };
+    let read = Smf::from_bytes(&optimised).unwrap();
+    for ch in 0..2 {
+        assert_eq!(channel(&read.tracks[0], ch), channel(&messages, ch));
+    }
+    assert_eq!(read.tracks[0][0], messages[0]);
+    assert_eq!(read.tracks[0].last(), messages.last());
+}
+
+#[test]
+fn stream_writer_integration_testing() {
+    use std::io::{Cursor, Seek, SeekFrom};
+    let end_of_track = Message::MetaEvent {
+        delta_time: 0,
+        event: MetaEvent::EndOfTrack,
+        data: Vec::new(),
+    };
+    let mut tracks: Vec<Vec<Message>> = Vec::new();
+    for ch in 0..3 {
+        let mut track = Vec::new();
+        for i in 0..20000u32 {
+            track.push(Message::MidiEvent {
+                delta_time: i % 7 * 60,
+                event: match i % 3 {
+                    0 => MidiEvent::NoteOn { ch, note: (i % 128) as u8, velocity: 100 },
+                    1 => MidiEvent::NoteOn { ch, note: (i % 128) as u8, velocity: 0 },
+                    _ => MidiEvent::ControlChange { ch, control: 1, data: (i % 128) as u8 },
+                },
+            });
+        }
+        track.push(end_of_track.clone());
+        tracks.push(track);
+    }
+
+    let mut writer = Writer::new();
+    writer.running_status(true);
+    for (i, track) in tracks.iter().enumerate() {
+        if i > 0 {
+            writer.push(&Message::TrackChange);
+        }
+        for message in track {
+            writer.push(message);
+        }
+    }
+    let mut expected: Vec<u8> = Vec::new();
+    writer.write_to(&mut expected).unwrap();
+
+    let mut buffered = StreamWriter::new(Vec::new(), 1, tracks.len() as u16, 480).unwrap();
+    let mut cursor = Cursor::new(b"prefix".to_vec());
+    cursor.seek(SeekFrom::End(0)).unwrap();
+    let mut seekable = StreamWriter::seekable(cursor, 1, 480).unwrap();
+    buffered.running_status(true);
+    seekable.running_status(true);
+    for track in &tracks {
+        buffered.begin_track().unwrap();
+        seekable.begin_track().unwrap();
+        for message in track {
+            buffered.push_event(message).unwrap();
+            seekable.push_event(message).unwrap();
+        }
+        buffered.end_track().unwrap();
+        seekable.end_track().unwrap();
+    }
+    assert_eq!(buffered.finish().unwrap(), expected);
+    let written = seekable.finish().unwrap().into_inner();
+    assert_eq!(&written[..6], b"prefix");
+    assert_eq!(&written[6..], &expected[..]);
+
+    let mut full = StreamWriter::seekable(Cursor::new(Vec::new()), 1, 480).unwrap();
+    for _ in 0..u16::MAX {
+        full.begin_track().unwrap();
+        full.end_track().unwrap();
+    }
+    assert_eq!(
+        full.begin_track().unwrap_err().kind(),
+        std::io::ErrorKind::InvalidInput
+    );
+    assert_eq!(&full.finish().unwrap().into_inner()[10..12], &[0xff, 0xff]);
+}